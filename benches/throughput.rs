@@ -0,0 +1,59 @@
+//! Measures instructions per second of the interpreter.
+//! Run with `cargo bench --bench throughput`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use chipmunk::engine::isa;
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::memory::{Memory, PROGRAM_START};
+use chipmunk::engine::peripheral::Bus;
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::rom::*;
+
+const COUNT: u64 = 20_000_000;
+
+/// Run `body` `COUNT` times, and print how many millions of runs are done in a second.
+fn measure(name: &str, mut body: impl FnMut(u64)) {
+    let start = Instant::now();
+    for index in 0..COUNT {
+        body(index);
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    println!("{:<16} {:>8.1} M/s", name, COUNT as f64 / seconds / 1e6);
+}
+
+fn main() {
+    // Loop of arithmetic, draw, store and load instructions.
+    let rom = Rom::new()
+        .label("loop")
+        .add(V0, 1)
+        .ld(V1, V0)
+        .and(V1, V2)
+        .ld_i("ball")
+        .drw(V0, V1, 5)
+        .ld_i(0x300)
+        .save(V3)
+        .load(V3)
+        .jp("loop")
+        .sprite("ball", &[0x20, 0x70, 0xF8, 0x70, 0x20])
+        .build()
+        .unwrap();
+    let code_len = rom.len() - 5;
+
+    let memory = Memory::from_rom(&rom);
+    let addr = |index: u64| (PROGRAM_START + (index as usize * 2) % code_len) as u16;
+    measure("decode", |index| {
+        let pc = black_box(addr(index));
+        black_box(isa::parse_instruction(&[memory.read(pc), memory.read(pc + 1)]));
+    });
+    measure("cached fetch", |index| {
+        black_box(memory.fetch(black_box(addr(index))));
+    });
+
+    let quirks = Quirks{ display_wait: false, ..Quirks::default() };
+    let mut machine = Machine::new(&rom, quirks);
+    measure("step", |_| {
+        black_box(machine.step().unwrap());
+    });
+}
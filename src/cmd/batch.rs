@@ -0,0 +1,77 @@
+use std::{fs, io::{self, Write}, path::Path, sync::Arc, thread};
+
+use chipmunk::engine::batch::{self, Job};
+use chipmunk::engine::quirks::QuirksProfile;
+
+use super::{Args, parse_number, parse_range, read_rom};
+
+/// Take out `--quirks` option of comma separated profiles, or `all`. Default profile is `chipmunk`.
+fn profiles_option(args: &mut Args) -> Result<Vec<QuirksProfile>, String> {
+    match args.value("--quirks")?.as_deref() {
+        Some("all") => Ok(QuirksProfile::ALL.to_vec()),
+        Some(names) => names.split(',')
+            .map(|name| QuirksProfile::from_name(name).ok_or(format!("Unknown quirks profile {}", name)))
+            .collect(),
+        None => Ok(vec![QuirksProfile::Chipmunk]),
+    }
+}
+
+/// Provides file name and bytes of ROM.
+type NamedRom = (String, Arc<[u8]>);
+
+/// Read given ROM, or every `.ch8` file of given directory in name order.
+fn read_roms(path: &str) -> Result<Vec<NamedRom>, String> {
+    let file_name = |path: &Path| path.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+    if !Path::new(path).is_dir() {
+        return Ok(vec![(file_name(Path::new(path)), read_rom(path)?.into())]);
+    }
+
+    let entries = fs::read_dir(path).map_err(|err| format!("{} : {}", path, err))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let entry_path = entry.map_err(|err| format!("{} : {}", path, err))?.path();
+        if entry_path.is_file() && entry_path.extension().is_some_and(|ext| ext == "ch8") {
+            paths.push(entry_path);
+        }
+    }
+    paths.sort();
+    paths.iter()
+        .map(|rom_path| Ok((file_name(rom_path), read_rom(&rom_path.to_string_lossy())?.into())))
+        .collect()
+}
+
+/// Run ROMs under every given profile and seed on worker threads, and write summary.
+pub fn execute(mut args: Args) -> Result<(), String> {
+    let profiles = profiles_option(&mut args)?;
+    let seeds: (u64, u64) = parse_range(&args.value("--seeds")?.unwrap_or_else(|| "0-0".to_string()))?;
+    let frames: u64 = parse_number(&args.value("--frames")?.unwrap_or_else(|| "600".to_string()))?;
+    let cycles: usize = parse_number(&args.value("--cycles")?.unwrap_or_else(|| "15".to_string()))?;
+    let threads: usize = match args.value("--threads")? {
+        Some(threads) => parse_number(&threads)?,
+        None => thread::available_parallelism().map_or(1, |threads| threads.get()),
+    };
+    let format = args.value("--format")?;
+    let out_path = args.value("-o")?;
+    let path = args.positional(1)?.remove(0);
+
+    let mut jobs = Vec::new();
+    for (name, rom) in read_roms(&path)? {
+        for &profile in &profiles {
+            for seed in seeds.0..=seeds.1 {
+                jobs.push(Job { name: name.clone(), rom: rom.clone(), profile, seed, frames, cycles });
+            }
+        }
+    }
+    let reports = batch::run(&jobs, threads);
+
+    let mut out: Box<dyn Write> = match out_path {
+        Some(path) => Box::new(io::BufWriter::new(fs::File::create(&path).map_err(|err| err.to_string())?)),
+        None => Box::new(io::BufWriter::new(io::stdout())),
+    };
+    match format.as_deref() {
+        Some("csv") | None => batch::write_csv(&reports, &mut out),
+        Some("json") => batch::write_json(&reports, &mut out),
+        Some(other) => return Err(format!("Unknown format {}", other)),
+    }.map_err(|err| err.to_string())?;
+    out.flush().map_err(|err| err.to_string())
+}
@@ -0,0 +1,38 @@
+use std::{fs, io::{self, Write}, path::Path};
+
+use chipmunk::engine::cfg::Cfg;
+
+use super::{Args, read_rom};
+
+/// Write DOT graph of every function and call graph of given ROM.
+/// With `-o DIR`, each graph is written into its own file. Otherwise all graphs are written into stdout.
+pub fn execute(mut args: Args) -> Result<(), String> {
+    let out_dir = args.value("-o")?;
+    let rom_path = args.positional(1)?.remove(0);
+
+    let cfg = Cfg::build(&read_rom(&rom_path)?);
+    match out_dir {
+        Some(dir) => {
+            fs::create_dir_all(&dir).map_err(|err| format!("{} : {}", dir, err))?;
+            let create = |name: &str| {
+                let path = Path::new(&dir).join(format!("{}.dot", name));
+                fs::File::create(&path).map(io::BufWriter::new).map_err(|err| format!("{} : {}", path.display(), err))
+            };
+            for function in cfg.functions.values() {
+                let mut out = create(&function.name())?;
+                cfg.write_function_dot(function, &mut out).and_then(|_| out.flush()).map_err(|err| err.to_string())?;
+            }
+            let mut out = create("calls")?;
+            cfg.write_call_graph_dot(&mut out).and_then(|_| out.flush()).map_err(|err| err.to_string())?;
+            println!("{} function(s) written into {}", cfg.functions.len(), dir);
+            Ok(())
+        },
+        None => {
+            let mut out = io::BufWriter::new(io::stdout());
+            for function in cfg.functions.values() {
+                cfg.write_function_dot(function, &mut out).map_err(|err| err.to_string())?;
+            }
+            cfg.write_call_graph_dot(&mut out).and_then(|_| out.flush()).map_err(|err| err.to_string())
+        },
+    }
+}
@@ -0,0 +1,26 @@
+use std::{fs, io::{self, Write}};
+
+use chipmunk::engine::{asm, octo};
+
+use super::{Args, is_asm_source};
+
+/// Compile Octo or assembly source into ROM, and write its symbols if requested.
+pub fn execute(mut args: Args) -> Result<(), String> {
+    let out_path = args.value("-o")?;
+    let symbols_path = args.value("--symbols")?;
+    let source_path = args.positional(1)?.remove(0);
+
+    let program = if is_asm_source(&source_path) {
+        asm::assemble_file(&source_path)?
+    } else {
+        octo::compile_file(&source_path)?
+    };
+    let out_path = out_path.unwrap_or_else(|| format!("{}.ch8", source_path.trim_end_matches(".8o").trim_end_matches(".asm")));
+    fs::write(&out_path, &program.rom).map_err(|err| format!("{} : {}", out_path, err))?;
+    if let Some(path) = symbols_path {
+        let mut out = io::BufWriter::new(fs::File::create(&path).map_err(|err| format!("{} : {}", path, err))?);
+        program.symbols.write(&mut out).and_then(|_| out.flush()).map_err(|err| format!("{} : {}", path, err))?;
+    }
+    println!("{} byte(s) written into {}", program.rom.len(), out_path);
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::{fs, io::{self, Write}};
+
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::coverage::Coverage;
+use chipmunk::engine::symbols::SymbolTable;
+
+use super::{Args, parse_number, quirks_option, read_program, symbols_option};
+
+/// Provides output options of coverage, shared by `coverage` and `run` command.
+pub struct CoverageOptions {
+    asm_path: Option<String>,
+    lcov_path: Option<String>,
+}
+
+impl CoverageOptions {
+    /// Take out `--coverage-asm` and `--coverage-lcov` options.
+    /// Returns None if no coverage output is requested.
+    pub fn from_args(args: &mut Args) -> Result<Option<CoverageOptions>, String> {
+        let asm_path = args.value("--coverage-asm")?;
+        let lcov_path = args.value("--coverage-lcov")?;
+
+        if asm_path.is_none() && lcov_path.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(CoverageOptions { asm_path, lcov_path }))
+    }
+
+    /// Write requested outputs, and print warnings of self-modifying code.
+    pub fn write(&self, coverage: &Coverage, rom: &[u8], rom_path: &str, symbols: &SymbolTable) -> Result<(), String> {
+        for smc in coverage.self_modifications() {
+            println!("Warning : runtime-written code executed at {} (cycle {})", symbols.describe(smc.pc), smc.cycle);
+        }
+
+        // lcov refers lines of disassembly when no listing is given.
+        let asm_name = self.asm_path.clone().unwrap_or(format!("{}.asm", rom_path));
+        if let Some(path) = &self.asm_path {
+            let mut out = create(path)?;
+            coverage.write_disassembly(&mut out, rom, symbols).map_err(|err| err.to_string())?;
+            out.flush().map_err(|err| err.to_string())?;
+        }
+        if let Some(path) = &self.lcov_path {
+            let mut out = create(path)?;
+            coverage.write_lcov(&mut out, rom, symbols, &asm_name).map_err(|err| err.to_string())?;
+            out.flush().map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+fn create(path: &str) -> Result<io::BufWriter<fs::File>, String> {
+    fs::File::create(path)
+        .map(io::BufWriter::new)
+        .map_err(|err| format!("{} : {}", path, err))
+}
+
+/// Run given ROM headlessly and write coverage.
+pub fn execute(mut args: Args) -> Result<(), String> {
+    let profile = quirks_option(&mut args)?;
+    let frames: u64 = parse_number(&args.value("--frames")?.unwrap_or_else(|| "600".to_string()))?;
+    let cycles: u64 = parse_number(&args.value("--cycles")?.unwrap_or_else(|| "15".to_string()))?;
+    let options = CoverageOptions::from_args(&mut args)?
+        .ok_or("--coverage-asm or --coverage-lcov must be given")?;
+    let symbols = symbols_option(&mut args)?;
+    let rom_path = args.positional(1)?.remove(0);
+
+    let (rom, compiled_symbols) = read_program(&rom_path)?;
+    let symbols = symbols.unwrap_or(compiled_symbols);
+    let mut machine = Machine::new(&rom, profile.quirks());
+    let mut coverage = Coverage::new();
+    'frames: for _ in 0..frames {
+        for _ in 0..cycles {
+            coverage.observe(&machine);
+            if let Err(fault) = machine.step() {
+                println!("{}", fault);
+                break 'frames;
+            }
+        }
+        machine.tick_timers();
+    }
+
+    options.write(&coverage, &rom, &rom_path, &symbols)
+}
@@ -0,0 +1,21 @@
+use std::fs;
+
+use chipmunk::engine::decompile;
+
+use super::{Args, read_rom, symbols_option};
+
+/// Decompile given ROM into Octo-like source.
+pub fn execute(mut args: Args) -> Result<(), String> {
+    let out_path = args.value("-o")?;
+    let symbols = symbols_option(&mut args)?.unwrap_or_default();
+    let rom_path = args.positional(1)?.remove(0);
+
+    let source = decompile::decompile_with_symbols(&read_rom(&rom_path)?, &symbols);
+    match out_path {
+        Some(path) => fs::write(&path, source).map_err(|err| format!("{} : {}", path, err)),
+        None => {
+            print!("{}", source);
+            Ok(())
+        },
+    }
+}
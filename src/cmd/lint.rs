@@ -0,0 +1,46 @@
+use std::{fs, io::{self, Write}};
+
+use chipmunk::engine::lint::{self, Rule};
+
+use super::{Args, read_rom};
+
+/// Parse comma separated rule names.
+fn parse_rules(names: &str) -> Result<Vec<Rule>, String> {
+    names.split(',')
+        .map(|name| Rule::from_name(name).ok_or(format!("Unknown lint rule {}", name)))
+        .collect()
+}
+
+/// Check given ROM statically, and write warnings.
+/// `--enable` gives the only rules to check, and `--disable` removes rules from them.
+pub fn execute(mut args: Args) -> Result<(), String> {
+    let mut rules = match args.value("--enable")? {
+        Some(names) => parse_rules(&names)?,
+        None => Rule::ALL.to_vec(),
+    };
+    if let Some(names) = args.value("--disable")? {
+        let disabled = parse_rules(&names)?;
+        rules.retain(|rule| !disabled.contains(rule));
+    }
+    let format = args.value("--format")?;
+    let out_path = args.value("-o")?;
+    let rom_path = args.positional(1)?.remove(0);
+
+    let warnings = lint::lint(&read_rom(&rom_path)?, &rules);
+    let mut out: Box<dyn Write> = match out_path {
+        Some(path) => Box::new(io::BufWriter::new(fs::File::create(&path).map_err(|err| format!("{} : {}", path, err))?)),
+        None => Box::new(io::BufWriter::new(io::stdout())),
+    };
+    match format.as_deref() {
+        Some("text") | None => {
+            for warning in &warnings {
+                writeln!(out, "{}", warning).map_err(|err| err.to_string())?;
+            }
+            writeln!(out, "Lint : {} warning(s) found", warnings.len())
+        },
+        Some("csv") => lint::write_csv(&warnings, &mut out),
+        Some("json") => lint::write_json(&warnings, &mut out),
+        Some(other) => return Err(format!("Unknown format {}", other)),
+    }.map_err(|err| err.to_string())?;
+    out.flush().map_err(|err| err.to_string())
+}
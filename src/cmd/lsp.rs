@@ -0,0 +1,12 @@
+use std::io;
+
+use chipmunk::engine::lsp;
+
+use super::Args;
+
+/// Serve language server protocol over standard input and output.
+pub fn execute(args: Args) -> Result<(), String> {
+    args.positional(0)?;
+    let stdin = io::stdin();
+    lsp::serve(&mut stdin.lock(), &mut io::stdout()).map_err(|err| err.to_string())
+}
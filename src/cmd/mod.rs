@@ -0,0 +1,161 @@
+pub mod run;
+pub mod trace;
+pub mod profile;
+pub mod coverage;
+pub mod sanitize;
+pub mod recompile;
+pub mod batch;
+pub mod search;
+pub mod why;
+pub mod cfg;
+pub mod decompile;
+pub mod lint;
+pub mod compile;
+pub mod lsp;
+
+use std::{fs, convert::TryFrom};
+
+use chipmunk::engine::{asm, octo};
+use chipmunk::engine::quirks::QuirksProfile;
+use chipmunk::engine::symbols::SymbolTable;
+
+pub const USAGE: &str = "\
+Valid usage :
+  chipmunk <rom.ch8 | game.8o | game.asm>
+  chipmunk run <rom.ch8 | game.8o | game.asm> [--quirks chipmunk|vip|chip48|schip]
+      [--trace FILE] [--trace-format text|binary] [--trace-addr FROM-TO] [--trace-frames FROM-TO]
+      [--coverage-asm FILE] [--coverage-lcov FILE] [--symbols FILE] [--sanitize] [--stack-limit N]
+      [--frontend terminal|headless] [--frames N] [--cycles N] [--record FILE] [--replay FILE]
+      [--break ADDR | LABEL,..]
+  chipmunk trace-text <trace> [-o FILE] [--symbols FILE]
+  chipmunk trace-diff <left trace> <right trace> [--align cycle|index] [--ignore-timers] [--symbols FILE]
+  chipmunk profile <rom.ch8> [--quirks NAME] [--frames N] [--cycles N] [--top N] [-o FILE] [--folded FILE]
+      [--symbols FILE]
+  chipmunk coverage <rom.ch8> [--quirks NAME] [--frames N] [--cycles N]
+      [--coverage-asm FILE] [--coverage-lcov FILE] [--symbols FILE]
+  chipmunk sanitize <rom.ch8> [--quirks NAME] [--frames N] [--cycles N] [--stack-limit N]
+  chipmunk recompile <rom.ch8> [--quirks NAME] [-o FILE]
+  chipmunk batch <rom.ch8 | dir> [--quirks NAME,..|all] [--seeds FROM-TO] [--frames N] [--cycles N]
+      [--threads N] [--format csv|json] [-o FILE]
+  chipmunk search <rom.ch8> (--pc ADDR | --memory ADDR=VALUE) [--strategy bfs|beam] [--width N]
+      [--keys K,..] [--quirks NAME] [--seed N] [--frames N] [--cycles N] [--max-states N] [-o FILE]
+  chipmunk why <rom.ch8> <V0..VF | I | ADDR>.. [--quirks NAME] [--frames N] [--cycles N] [--replay FILE]
+      [--depth N]
+  chipmunk cfg <rom.ch8> [-o DIR]
+  chipmunk decompile <rom.ch8> [-o FILE] [--symbols FILE]
+  chipmunk lint <rom.ch8> [--enable RULE,..] [--disable RULE,..] [--format text|csv|json] [-o FILE]
+  chipmunk compile <game.8o | game.asm> [-o FILE] [--symbols FILE]
+  chipmunk lsp";
+
+/// Provides command line arguments of one command.
+/// Options are taken out first, and then remaining positional arguments are taken.
+pub struct Args {
+    items: Vec<String>,
+}
+
+impl Args {
+    pub fn new(items: Vec<String>) -> Args {
+        Args { items }
+    }
+
+    /// Take out given flag option. Return true if it was given.
+    pub fn flag(&mut self, name: &str) -> bool {
+        match self.items.iter().position(|item| item == name) {
+            Some(index) => { self.items.remove(index); true },
+            None => false,
+        }
+    }
+
+    /// Take out given option and its value.
+    pub fn value(&mut self, name: &str) -> Result<Option<String>, String> {
+        match self.items.iter().position(|item| item == name) {
+            Some(index) if index + 1 < self.items.len() => {
+                self.items.remove(index);
+                Ok(Some(self.items.remove(index)))
+            },
+            Some(_) => Err(format!("Option {} needs value", name)),
+            None => Ok(None),
+        }
+    }
+
+    /// Take out all remaining arguments, which must be positional.
+    pub fn positional(self, count: usize) -> Result<Vec<String>, String> {
+        if let Some(option) = self.items.iter().find(|item| item.starts_with("--")) {
+            return Err(format!("Unknown option {}", option));
+        }
+        if self.items.len() != count {
+            return Err(format!("Expected {} argument(s), but got {}", count, self.items.len()));
+        }
+        Ok(self.items)
+    }
+
+    /// Take out all remaining arguments, which must be positional and at least `count`.
+    pub fn positional_at_least(self, count: usize) -> Result<Vec<String>, String> {
+        if self.items.len() < count {
+            return Err(format!("Expected at least {} argument(s), but got {}", count, self.items.len()));
+        }
+        let len = self.items.len();
+        self.positional(len)
+    }
+}
+
+/// Parse decimal or `0x` (or `0X`) prefixed hexadecimal number.
+pub fn parse_number<T: TryFrom<u64>>(token: &str) -> Result<T, String> {
+    let parsed = match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => token.parse::<u64>().ok(),
+    };
+    parsed
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| format!("Invalid number {}", token))
+}
+
+/// Parse inclusive range written as `FROM-TO`.
+pub fn parse_range<T: TryFrom<u64> + PartialOrd>(token: &str) -> Result<(T, T), String> {
+    let (from, to) = token.split_once('-').ok_or_else(|| format!("Invalid range {}", token))?;
+    let (from, to) = (parse_number(from)?, parse_number(to)?);
+    if from > to {
+        return Err(format!("Invalid range {}", token));
+    }
+    Ok((from, to))
+}
+
+/// Take out `--quirks` option. Default profile is `chipmunk`.
+pub fn quirks_option(args: &mut Args) -> Result<QuirksProfile, String> {
+    match args.value("--quirks")? {
+        Some(name) => QuirksProfile::from_name(&name).ok_or(format!("Unknown quirks profile {}", name)),
+        None => Ok(QuirksProfile::Chipmunk),
+    }
+}
+
+/// Take out `--symbols` option, and load the symbol file.
+pub fn symbols_option(args: &mut Args) -> Result<Option<SymbolTable>, String> {
+    args.value("--symbols")?.map(|path| SymbolTable::load(&path)).transpose()
+}
+
+/// Read whole ROM file.
+pub fn read_rom(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|err| format!("{} : {}", path, err))
+}
+
+/// Check whether given file is Octo source.
+pub fn is_octo_source(path: &str) -> bool {
+    path.ends_with(".8o")
+}
+
+/// Check whether given file is assembly source of canonical mnemonics.
+pub fn is_asm_source(path: &str) -> bool {
+    path.ends_with(".asm")
+}
+
+/// Read ROM, or compile Octo or assembly source into ROM with its symbols.
+pub fn read_program(path: &str) -> Result<(Vec<u8>, SymbolTable), String> {
+    let program = if is_octo_source(path) {
+        octo::compile_file(path)?
+    } else if is_asm_source(path) {
+        asm::assemble_file(path)?
+    } else {
+        return Ok((read_rom(path)?, SymbolTable::new()));
+    };
+    Ok((program.rom, program.symbols))
+}
@@ -0,0 +1,50 @@
+use std::{fs, io::{self, Write}};
+
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::profile::Profiler;
+
+use super::{Args, parse_number, quirks_option, read_program, symbols_option};
+
+/// Run given ROM headlessly and write profile report.
+pub fn execute(mut args: Args) -> Result<(), String> {
+    let profile = quirks_option(&mut args)?;
+    let frames: u64 = parse_number(&args.value("--frames")?.unwrap_or_else(|| "600".to_string()))?;
+    let cycles: u64 = parse_number(&args.value("--cycles")?.unwrap_or_else(|| "15".to_string()))?;
+    let top: usize = parse_number(&args.value("--top")?.unwrap_or_else(|| "20".to_string()))?;
+    let out_path = args.value("-o")?;
+    let folded_path = args.value("--folded")?;
+    let symbols = symbols_option(&mut args)?;
+    let rom_path = args.positional(1)?.remove(0);
+
+    let (rom, compiled_symbols) = read_program(&rom_path)?;
+    let mut machine = Machine::new(&rom, profile.quirks());
+    let mut profiler = Profiler::new();
+    profiler.set_symbols(symbols.unwrap_or(compiled_symbols));
+    'frames: for _ in 0..frames {
+        for _ in 0..cycles {
+            profiler.observe(&machine);
+            if let Err(fault) = machine.step() {
+                println!("{}", fault);
+                profiler.end_frame();
+                break 'frames;
+            }
+        }
+        profiler.end_frame();
+        machine.tick_timers();
+    }
+
+    let mut out: Box<dyn Write> = match out_path {
+        Some(path) => Box::new(io::BufWriter::new(fs::File::create(&path).map_err(|err| err.to_string())?)),
+        None => Box::new(io::BufWriter::new(io::stdout())),
+    };
+    writeln!(out, "Budget : {} cycles per frame", cycles).map_err(|err| err.to_string())?;
+    profiler.write_report(&mut out, &machine, top).map_err(|err| err.to_string())?;
+    out.flush().map_err(|err| err.to_string())?;
+
+    if let Some(path) = folded_path {
+        let mut folded = io::BufWriter::new(fs::File::create(&path).map_err(|err| err.to_string())?);
+        profiler.write_folded(&mut folded).map_err(|err| err.to_string())?;
+        folded.flush().map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use std::{fs, path::Path};
+
+use chipmunk::engine::recompile;
+
+use super::{Args, quirks_option, read_rom};
+
+/// Translate given ROM into Rust source.
+pub fn execute(mut args: Args) -> Result<(), String> {
+    let profile = quirks_option(&mut args)?;
+    let out_path = args.value("-o")?;
+    let rom_path = args.positional(1)?.remove(0);
+
+    let rom = read_rom(&rom_path)?;
+    let name = Path::new(&rom_path).file_name().map_or(rom_path.clone(), |name| name.to_string_lossy().into_owned());
+    let source = recompile::recompile(&rom, profile.quirks(), &name);
+    match out_path {
+        Some(path) => fs::write(&path, source).map_err(|err| format!("{} : {}", path, err)),
+        None => {
+            print!("{}", source);
+            Ok(())
+        },
+    }
+}
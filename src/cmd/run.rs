@@ -0,0 +1,242 @@
+use std::{cell::Cell, fs, io, time};
+
+use chipmunk::engine::machine::{Machine, Output};
+use chipmunk::engine::check::is_file_valid_ch8;
+use chipmunk::engine::trace::{TraceWriter, TraceFormat, TraceFilter};
+use chipmunk::engine::coverage::Coverage;
+use chipmunk::engine::sanitizer::Sanitizer;
+use chipmunk::engine::device;
+use chipmunk::engine::frontend::{self, Event, Frontend, Headless, HostKey, Recording, Pacing, Exit};
+use chipmunk::engine::peripheral::{Display, Timers};
+use chipmunk::engine::replay::Replay;
+
+use super::{Args, is_asm_source, is_octo_source, parse_number, parse_range, quirks_option, read_program, symbols_option};
+use super::coverage::CoverageOptions;
+use super::sanitize::{stack_limit_option, sanitized_machine, print_reports};
+
+/// Instruction clock of terminal front end.
+const REAL_TIME: Pacing = Pacing::RealTime{ clock_hz: 1_760_000.0 };
+
+/// Provides optional tools which observe machine right before every instruction.
+struct Observers {
+    tracer: Option<TraceWriter>,
+    coverage: Option<Coverage>,
+    sanitizer: Option<Sanitizer>,
+}
+
+impl Observers {
+    fn is_empty(&self) -> bool {
+        self.tracer.is_none() && self.coverage.is_none() && self.sanitizer.is_none()
+    }
+
+    fn observe(&mut self, machine: &Machine) -> io::Result<()> {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.trace(machine)?;
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.observe(machine);
+        }
+        if let Some(sanitizer) = self.sanitizer.as_mut() {
+            sanitizer.observe(machine);
+        }
+        Ok(())
+    }
+}
+
+/// Provides front end which requests to quit once machine reaches a breakpoint.
+struct Breaking<'a, F: Frontend> {
+    inner: &'a mut F,
+    /// Address of the reached breakpoint.
+    hit: &'a Cell<Option<u16>>,
+}
+
+impl<F: Frontend> Frontend for Breaking<'_, F> {
+    fn present(&mut self, output: &Output, display: &dyn Display) -> io::Result<()> {
+        self.inner.present(output, display)
+    }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        self.inner.end_frame()
+    }
+
+    fn poll_event(&mut self) -> io::Result<Option<Event>> {
+        match self.hit.get() {
+            Some(_) => Ok(Some(Event::Host(HostKey::Quit))),
+            None => self.inner.poll_event(),
+        }
+    }
+
+    fn wait_event(&mut self, timeout: time::Duration) -> io::Result<Option<Event>> {
+        match self.hit.get() {
+            Some(_) => Ok(Some(Event::Host(HostKey::Quit))),
+            None => self.inner.wait_event(timeout),
+        }
+    }
+
+    fn set_beep(&mut self, on: bool) {
+        self.inner.set_beep(on);
+    }
+}
+
+/// Run machine on given front end. If `record_path` is given, frames are recorded into the file.
+/// Emulation stops right before instruction of any breakpoint, and its address is returned.
+/// Idle loops are skipped unless any observer or breakpoint has to see every instruction.
+fn emulate<F: Frontend>(machine: &mut Machine, frontend: F, pacing: Pacing, record_path: Option<&str>, observers: &mut Observers, breakpoints: &[u16]) -> Result<(Exit, Option<u16>), String> {
+    let skip_idle = observers.is_empty() && breakpoints.is_empty();
+    // Breakpoint is checked after each step, so that front end quits before the next step
+    // while the frame of the last instruction is processed as usual.
+    let breakpoint = |machine: &Machine| Some(machine.registers().get_pc()).filter(|pc| breakpoints.contains(pc));
+    let hit = Cell::new(breakpoint(machine));
+    let step = |machine: &mut Machine, budget| {
+        let output = if skip_idle { machine.step_fast(budget) } else { machine.step() };
+        if hit.get().is_none() {
+            hit.set(breakpoint(machine));
+        }
+        output
+    };
+    let observe = |machine: &Machine| observers.observe(machine);
+    let exit = match record_path {
+        Some(path) => {
+            let mut recording = Recording::new(frontend);
+            let mut breaking = Breaking { inner: &mut recording, hit: &hit };
+            let exit = frontend::run_with(machine, &mut breaking, pacing, observe, step).map_err(|err| err.to_string())?;
+            let file = fs::File::create(path).map_err(|err| format!("{} : {}", path, err))?;
+            recording.write(&mut io::BufWriter::new(file)).map_err(|err| format!("{} : {}", path, err))?;
+            exit
+        },
+        None => {
+            let mut frontend = frontend;
+            let mut breaking = Breaking { inner: &mut frontend, hit: &hit };
+            frontend::run_with(machine, &mut breaking, pacing, observe, step).map_err(|err| err.to_string())?
+        },
+    };
+    Ok((exit, hit.get()))
+}
+
+/// Run given ROM in terminal, or other front end.
+pub fn execute(mut args: Args) -> Result<(), String> {
+    let mut profile = quirks_option(&mut args)?;
+    let trace_path = args.value("--trace")?;
+    let trace_format = match args.value("--trace-format")?.as_deref() {
+        Some("binary") => TraceFormat::Binary,
+        Some("text") | None => TraceFormat::Text,
+        Some(other) => return Err(format!("Unknown trace format {}", other)),
+    };
+    let trace_filter = TraceFilter {
+        addr: args.value("--trace-addr")?.map(|range| parse_range(&range)).transpose()?,
+        frames: args.value("--trace-frames")?.map(|range| parse_range(&range)).transpose()?,
+    };
+    let coverage_options = CoverageOptions::from_args(&mut args)?;
+    let symbols = symbols_option(&mut args)?;
+    let breakpoints = args.value("--break")?;
+    let sanitize = args.flag("--sanitize");
+    let stack_limit = stack_limit_option(&mut args)?;
+    let frontend_name = args.value("--frontend")?;
+    let frames = args.value("--frames")?.map(|value| parse_number(&value)).transpose()?;
+    let cycles = args.value("--cycles")?.map(|value| parse_number(&value)).transpose()?;
+    let record_path = args.value("--record")?;
+    let replay = args.value("--replay")?.map(|path| Replay::load(&path)).transpose()?;
+    let file_path = args.positional(1)?.remove(0);
+
+    // Interpret file and check validation. Octo and assembly sources are checked by compiling them.
+    if !is_octo_source(&file_path) && !is_asm_source(&file_path) && !is_file_valid_ch8(&file_path) {
+        return Err(format!("{} is not valid ch8 file", file_path));
+    }
+
+    // Symbols of compiled source are used unless given, and breakpoints may be their labels.
+    let (rom, compiled_symbols) = read_program(&file_path)?;
+    let symbols = symbols.unwrap_or(compiled_symbols);
+    let breakpoints = match breakpoints {
+        Some(names) => names.split(',').map(|name| symbols.resolve(name)).collect::<Result<Vec<u16>, String>>()?,
+        None => Vec::new(),
+    };
+
+    // Trace is never written into stdout, which is used by alternative screen.
+    let tracer = match trace_path {
+        Some(path) => {
+            let file = fs::File::create(&path).map_err(|err| format!("{} : {}", path, err))?;
+            let out = Box::new(io::BufWriter::new(file));
+            let mut tracer = TraceWriter::new(out, trace_format, trace_filter).map_err(|err| err.to_string())?;
+            tracer.set_symbols(symbols.clone());
+            Some(tracer)
+        },
+        None => None,
+    };
+
+    // Replay decides quirks, seed and cycles, and is played on headless front end.
+    let (frontend_name, frames, cycles) = match replay.as_ref() {
+        Some(replay) => {
+            if frontend_name.as_deref().is_some_and(|name| name != "headless") {
+                return Err("Replay needs headless front end".to_string());
+            }
+            profile = replay.profile;
+            (Some("headless".to_string()), Some(frames.unwrap_or(replay.keys.len() as u64)), Some(replay.cycles))
+        },
+        None => (frontend_name, frames, cycles),
+    };
+
+    // Set devices of CHIP-8 simulator.
+    let (mut machine, sanitizer) = if sanitize {
+        let (machine, sanitizer) = sanitized_machine(&rom, profile.quirks(), stack_limit);
+        (machine, Some(sanitizer))
+    } else {
+        (Machine::new(&rom, profile.quirks()), None)
+    };
+    if let Some(replay) = replay.as_ref() {
+        machine.seed_random(replay.seed);
+    }
+    let coverage = coverage_options.as_ref().map(|_| Coverage::new());
+    let mut observers = Observers{ tracer, coverage, sanitizer };
+
+    let result = match frontend_name.as_deref() {
+        Some("terminal") | None => {
+            // Device is moved into emulation, and leaves alternative screen when emulation ends,
+            // before coverage warnings and sanitizer reports are printed.
+            let mut device = device::Device::new().map_err(|err| format!("Error : {:?}", err))?;
+            let _ = device.clear();
+            let pacing = cycles.map_or(REAL_TIME, |cycles_per_frame| Pacing::Fixed{ cycles_per_frame });
+            emulate(&mut machine, device, pacing, record_path.as_deref(), &mut observers, &breakpoints)
+        },
+        Some("headless") => {
+            let frames = frames.ok_or_else(|| "Headless front end needs --frames".to_string())?;
+            let mut headless = Headless::with_frame_limit(frames);
+            if let Some(replay) = replay.as_ref() {
+                replay.schedule(&mut headless);
+            }
+            let pacing = Pacing::Fixed{ cycles_per_frame: cycles.unwrap_or(15) };
+            emulate(&mut machine, headless, pacing, record_path.as_deref(), &mut observers, &breakpoints)
+        },
+        Some(other) => return Err(format!("Unknown front end {}", other)),
+    };
+    match result? {
+        (Exit::Fault(fault), _) => {
+            println!("{}", fault);
+            println!("  in {}", symbols.describe(fault.pc()));
+            print_registers(&machine);
+        },
+        (_, Some(pc)) => {
+            println!("Breakpoint at {} (cycle {})", symbols.describe(pc), machine.cycles());
+            print_registers(&machine);
+        },
+        _ => (),
+    }
+    let Observers{ mut tracer, coverage, sanitizer } = observers;
+
+    if let Some(tracer) = tracer.as_mut() {
+        tracer.flush().map_err(|err| err.to_string())?;
+    }
+
+    if let (Some(options), Some(coverage)) = (coverage_options, coverage) {
+        options.write(&coverage, &rom, &file_path, &symbols)?;
+    }
+    if let Some(sanitizer) = sanitizer {
+        print_reports(&sanitizer);
+    }
+    Ok(())
+}
+
+/// Print registers with timers, which often explain why ROM stopped.
+fn print_registers(machine: &Machine) {
+    let timers = machine.timers();
+    println!("Register dump : {},DT:{:4},ST:{:4}", machine.registers(), timers.delay(), timers.sound());
+}
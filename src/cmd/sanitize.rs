@@ -0,0 +1,55 @@
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::memory::Memory;
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::sanitizer::Sanitizer;
+use chipmunk::engine::register::STACK_POINTER_CNT;
+
+use super::{Args, parse_number, quirks_option, read_rom};
+
+/// Take out `--stack-limit` option. Default limit is stack size of this interpreter.
+pub fn stack_limit_option(args: &mut Args) -> Result<usize, String> {
+    match args.value("--stack-limit")? {
+        Some(limit) => parse_number(&limit),
+        None => Ok(STACK_POINTER_CNT),
+    }
+}
+
+/// Create machine whose free memory is randomized as power-on state, and its sanitizer.
+pub fn sanitized_machine(rom: &[u8], quirks: Quirks, stack_limit: usize) -> (Machine, Sanitizer) {
+    let mut memory = Memory::from_rom(rom);
+    memory.randomize_free(rom.len());
+    (Machine::from_memory(memory, quirks), Sanitizer::new(rom.len(), stack_limit))
+}
+
+/// Print all reports of sanitizer.
+pub fn print_reports(sanitizer: &Sanitizer) {
+    for report in sanitizer.reports() {
+        println!("{}", report);
+    }
+    println!("Sanitizer : {} issue(s) found", sanitizer.reports().len());
+}
+
+/// Run given ROM headlessly with sanitizer.
+pub fn execute(mut args: Args) -> Result<(), String> {
+    let profile = quirks_option(&mut args)?;
+    let frames: u64 = parse_number(&args.value("--frames")?.unwrap_or_else(|| "600".to_string()))?;
+    let cycles: u64 = parse_number(&args.value("--cycles")?.unwrap_or_else(|| "15".to_string()))?;
+    let stack_limit = stack_limit_option(&mut args)?;
+    let rom_path = args.positional(1)?.remove(0);
+
+    let rom = read_rom(&rom_path)?;
+    let (mut machine, mut sanitizer) = sanitized_machine(&rom, profile.quirks(), stack_limit);
+    'frames: for _ in 0..frames {
+        for _ in 0..cycles {
+            sanitizer.observe(&machine);
+            if let Err(fault) = machine.step() {
+                println!("{}", fault);
+                break 'frames;
+            }
+        }
+        machine.tick_timers();
+    }
+
+    print_reports(&sanitizer);
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use std::{fs, io::{self, Write}};
+
+use chipmunk::engine::search::{self, SearchOptions, SearchResult, Strategy, Target};
+
+use super::{Args, parse_number, quirks_option, read_rom};
+
+/// Take out `--pc ADDR` or `--memory ADDR=VALUE` option.
+fn target_option(args: &mut Args) -> Result<Target, String> {
+    match (args.value("--pc")?, args.value("--memory")?) {
+        (Some(addr), None) => Ok(Target::Pc(parse_number(&addr)?)),
+        (None, Some(condition)) => {
+            let (addr, value) = condition.split_once('=').ok_or_else(|| format!("Invalid condition {}", condition))?;
+            Ok(Target::Memory{ addr: parse_number(addr)?, value: parse_number(value)? })
+        },
+        _ => Err("Search needs either --pc or --memory".to_string()),
+    }
+}
+
+/// Search key input which reaches target, and write it as replay file.
+pub fn execute(mut args: Args) -> Result<(), String> {
+    let mut options = SearchOptions::new(target_option(&mut args)?);
+    options.profile = quirks_option(&mut args)?;
+    let width = args.value("--width")?.map(|width| parse_number(&width)).transpose()?;
+    options.strategy = match args.value("--strategy")?.as_deref() {
+        Some("bfs") | None => Strategy::BreadthFirst,
+        Some("beam") => Strategy::Beam{ width: width.unwrap_or(1000) },
+        Some(other) => return Err(format!("Unknown strategy {}", other)),
+    };
+    if let Some(keys) = args.value("--keys")? {
+        options.keys = keys.split(',')
+            .map(|key| match key {
+                "-" => Ok(None),
+                _ => u8::from_str_radix(key, 16).ok().filter(|&key| key < 16).map(Some)
+                    .ok_or_else(|| format!("Invalid key {}", key)),
+            })
+            .collect::<Result<_, String>>()?;
+    }
+    if let Some(seed) = args.value("--seed")? { options.seed = parse_number(&seed)?; }
+    if let Some(frames) = args.value("--frames")? { options.max_frames = parse_number(&frames)?; }
+    if let Some(cycles) = args.value("--cycles")? { options.cycles = parse_number(&cycles)?; }
+    if let Some(states) = args.value("--max-states")? { options.max_states = parse_number(&states)?; }
+    let out_path = args.value("-o")?;
+    let rom = read_rom(&args.positional(1)?.remove(0))?;
+
+    let SearchResult{ replay, states, frames } = search::search(&rom, &options);
+    let replay = replay.ok_or_else(|| format!("{} is not reached in {} frames ({} states)", options.target, frames, states))?;
+
+    // Replay is written into stdout if output file is not given, so summary is printed only with file.
+    match out_path {
+        Some(path) => {
+            let mut out = io::BufWriter::new(fs::File::create(&path).map_err(|err| format!("{} : {}", path, err))?);
+            replay.write(&mut out).and_then(|_| out.flush()).map_err(|err| format!("{} : {}", path, err))?;
+            println!("{} is reached in {} frames ({} states)", options.target, replay.keys.len(), states);
+            Ok(())
+        },
+        None => replay.write(&mut io::stdout()).map_err(|err| err.to_string()),
+    }
+}
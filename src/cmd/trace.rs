@@ -0,0 +1,59 @@
+use std::{fs, process, io::{self, Write}};
+
+use chipmunk::engine::trace::{self, TraceRecord, Alignment, Divergence};
+
+use super::{Args, symbols_option};
+
+fn load(path: &str) -> Result<Vec<TraceRecord>, String> {
+    let file = fs::File::open(path).map_err(|err| format!("{} : {}", path, err))?;
+    trace::read_trace(io::BufReader::new(file)).map_err(|err| format!("{} : {}", path, err))
+}
+
+/// Convert binary (or text) trace into text trace.
+pub fn execute_text(mut args: Args) -> Result<(), String> {
+    let out_path = args.value("-o")?;
+    let symbols = symbols_option(&mut args)?.unwrap_or_default();
+    let in_path = args.positional(1)?.remove(0);
+    let records = load(&in_path)?;
+
+    let mut out: Box<dyn Write> = match out_path {
+        Some(path) => Box::new(io::BufWriter::new(fs::File::create(&path).map_err(|err| err.to_string())?)),
+        None => Box::new(io::BufWriter::new(io::stdout())),
+    };
+    for record in &records {
+        writeln!(out, "{}", record.to_text(&symbols)).map_err(|err| err.to_string())?;
+    }
+    out.flush().map_err(|err| err.to_string())
+}
+
+/// Align two traces and report the first divergent instruction.
+/// Exits with failure when traces diverge, so that it can be used from scripts.
+pub fn execute_diff(mut args: Args) -> Result<(), String> {
+    let alignment = match args.value("--align")?.as_deref() {
+        Some("cycle") | None => Alignment::Cycle,
+        Some("index") => Alignment::Index,
+        Some(other) => return Err(format!("Unknown alignment {}", other)),
+    };
+    let timers = !args.flag("--ignore-timers");
+    let symbols = symbols_option(&mut args)?.unwrap_or_default();
+    let paths = args.positional(2)?;
+    let (left, right) = (load(&paths[0])?, load(&paths[1])?);
+
+    match trace::first_divergence(&left, &right, alignment, timers) {
+        None => {
+            println!("Traces are identical. ({} / {} records)", left.len(), right.len());
+            Ok(())
+        },
+        Some(Divergence::Record{ index, left, right, fields }) => {
+            println!("First divergence at aligned instruction #{} : {}", index, fields.join(", "));
+            println!("< {}", left.to_text(&symbols));
+            println!("> {}", right.to_text(&symbols));
+            process::exit(1);
+        },
+        Some(Divergence::Length{ index, left_len, right_len }) => {
+            println!("Traces are identical for {} instructions, but lengths differ. ({} / {} records)",
+                index, left_len, right_len);
+            process::exit(1);
+        },
+    }
+}
@@ -0,0 +1,38 @@
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::provenance::{Location, Provenance};
+use chipmunk::engine::replay::{Replay, play_frame};
+
+use super::{Args, parse_number, quirks_option, read_rom};
+
+/// Run given ROM headlessly with provenance tracking, and explain where values of given locations came from.
+pub fn execute(mut args: Args) -> Result<(), String> {
+    let profile = quirks_option(&mut args)?;
+    let replay = args.value("--replay")?.map(|path| Replay::load(&path)).transpose()?;
+    let frames = args.value("--frames")?.map(|frames| parse_number(&frames)).transpose()?;
+    let cycles = args.value("--cycles")?.map(|cycles| parse_number(&cycles)).transpose()?;
+    let depth: usize = parse_number(&args.value("--depth")?.unwrap_or_else(|| "8".to_string()))?;
+    let mut positional = args.positional_at_least(2)?;
+    let rom_path = positional.remove(0);
+    let locations = positional.iter()
+        .map(|text| Location::parse(text).ok_or_else(|| format!("Invalid location {}", text)))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    // Replay decides quirks, seed and cycles, and keys of each frame.
+    let rom = read_rom(&rom_path)?;
+    let replay = replay.unwrap_or_else(|| Replay::new(profile, 0, cycles.unwrap_or(15)));
+    let frames = frames.unwrap_or(if replay.keys.is_empty() { 600 } else { replay.keys.len() as u64 });
+    let mut machine: Machine = replay.machine(&rom);
+    let mut provenance = Provenance::new();
+    for frame in 0..frames {
+        let key = replay.keys.get(frame as usize).copied().flatten();
+        if let Err(fault) = play_frame(&mut machine, key, replay.cycles, &mut |machine| provenance.observe(machine), &mut |_| false) {
+            println!("{}", fault);
+            break;
+        }
+    }
+
+    for location in locations {
+        print!("{}", provenance.explain(&machine, location, depth));
+    }
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+};
+
+use super::isa;
+use super::memory::{MEMORY_SIZE, PROGRAM_START};
+use super::octo::Program;
+use super::symbols::SymbolTable;
+
+/// Operands which are not names of labels.
+const RESERVED: [&str; 7] = ["I", "[I]", "DT", "ST", "K", "F", "B"];
+
+/// Provides one source line, split into label, mnemonic and operands.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Line<'a> {
+    pub label: Option<&'a str>,
+    pub mnemonic: Option<&'a str>,
+    pub operands: Vec<&'a str>,
+    /// Comment after `;`, without `;`.
+    pub comment: Option<&'a str>,
+}
+
+/// Split source line such as `loop: JP loop ; comment`.
+pub fn split_line(line: &str) -> Line<'_> {
+    let (code, comment) = match line.split_once(';') {
+        Some((code, comment)) => (code, Some(comment)),
+        None => (line, None),
+    };
+    let mut code = code.trim();
+    let mut label = None;
+    if let Some((name, rest)) = code.split_once(':') {
+        if is_name(name.trim()) {
+            label = Some(name.trim());
+            code = rest.trim();
+        }
+    }
+    let (mnemonic, operands) = match code.split_once(char::is_whitespace) {
+        Some((mnemonic, rest)) => (Some(mnemonic), rest.split(',').map(str::trim).collect()),
+        None if code.is_empty() => (None, Vec::new()),
+        None => (Some(code), Vec::new()),
+    };
+    Line { label, mnemonic, operands, comment }
+}
+
+/// Check whether token is name of label, which starts with letter or `_`.
+pub fn is_name(token: &str) -> bool {
+    let mut chars = token.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        && !is_register(token)
+        && !RESERVED.contains(&token.to_uppercase().as_str())
+}
+
+fn is_register(token: &str) -> bool {
+    token.len() == 2 && token[..1].eq_ignore_ascii_case("v") && token.as_bytes()[1].is_ascii_hexdigit()
+}
+
+fn parse_number(token: &str) -> Option<u16> {
+    match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
+
+/// Get size in bytes of statement.
+fn size_of(line: &Line) -> usize {
+    match line.mnemonic.map(str::to_uppercase).as_deref() {
+        None => 0,
+        Some("DB") => line.operands.len(),
+        Some("DW") => line.operands.len() * 2,
+        Some(_) => 2,
+    }
+}
+
+/// Assemble source of canonical mnemonics, which `isa::parse_mnemonic` reads.
+///
+/// Each line has optional `label:`, then mnemonic or `DB`/`DW` with comma separated operands.
+/// `;` starts a comment. Labels may be used as operands before they are defined.
+pub fn assemble(source: &str, file_name: &str) -> Result<Program, String> {
+    let lines: Vec<Line> = source.lines().map(split_line).collect();
+
+    // Addresses of labels are known before any instruction is assembled.
+    let mut labels = HashMap::new();
+    let mut symbols = SymbolTable::new();
+    let mut addr = PROGRAM_START;
+    for (line_no, line) in lines.iter().enumerate() {
+        if let Some(label) = line.label {
+            if labels.insert(label, addr as u16).is_some() {
+                return Err(format!("line {} : label {} is defined more than once", line_no + 1, label));
+            }
+            symbols.add_label(addr as u16, label);
+        }
+        addr += size_of(line);
+        if addr > MEMORY_SIZE {
+            return Err(format!("line {} : program is larger than memory", line_no + 1));
+        }
+    }
+
+    let mut rom = Vec::new();
+    for (line_no, line) in lines.iter().enumerate() {
+        let mnemonic = match line.mnemonic {
+            Some(mnemonic) => mnemonic,
+            None => continue,
+        };
+        let mut operands = Vec::with_capacity(line.operands.len());
+        for &operand in &line.operands {
+            if is_name(operand) {
+                match labels.get(operand) {
+                    Some(addr) => operands.push(format!("0x{:03X}", addr)),
+                    None => return Err(format!("line {} : undefined name {}", line_no + 1, operand)),
+                }
+            } else {
+                operands.push(operand.to_string());
+            }
+        }
+
+        let addr = (PROGRAM_START + rom.len()) as u16;
+        match mnemonic.to_uppercase().as_str() {
+            "DB" | "DW" => {
+                let is_word = mnemonic.eq_ignore_ascii_case("DW");
+                for operand in &operands {
+                    let value = parse_number(operand)
+                        .filter(|&value| is_word || value <= 0xFF)
+                        .ok_or_else(|| format!("line {} : invalid value {}", line_no + 1, operand))?;
+                    if is_word {
+                        rom.extend_from_slice(&value.to_be_bytes());
+                    } else {
+                        rom.push(value as u8);
+                    }
+                }
+            },
+            _ => {
+                let text = format!("{} {}", mnemonic, operands.join(", "));
+                let instruction = isa::parse_mnemonic(&text).map_err(|err| format!("line {} : {}", line_no + 1, err))?;
+                symbols.add_line(addr, file_name, line_no + 1);
+                rom.extend_from_slice(&instruction.encode());
+            },
+        }
+    }
+    Ok(Program { rom, symbols })
+}
+
+/// Assemble source file.
+pub fn assemble_file(path: &str) -> Result<Program, String> {
+    let source = fs::read_to_string(path).map_err(|err| format!("{} : {}", path, err))?;
+    let file_name = Path::new(path).file_name().map_or(path.to_string(), |name| name.to_string_lossy().into_owned());
+    assemble(&source, &file_name).map_err(|err| format!("{} : {}", path, err))
+}
@@ -0,0 +1,162 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}},
+    thread,
+};
+
+use super::isa::Instruction;
+use super::json::{csv_field, json_string};
+use super::machine::{Machine, Fault};
+use super::quirks::QuirksProfile;
+use super::screen::{Screen, SCREEN_HEIGHT};
+use super::state::MachineState;
+
+/// Provides one headless run of batch.
+#[derive(Debug, Clone)]
+pub struct Job {
+    /// Name of ROM written in report.
+    pub name: String,
+    pub rom: Arc<[u8]>,
+    pub profile: QuirksProfile,
+    /// Seed of random number generator.
+    pub seed: u64,
+    /// Maximum count of 60Hz frames.
+    pub frames: u64,
+    /// Count of instructions per frame.
+    pub cycles: usize,
+}
+
+/// Provides the reason why run ended.
+#[derive(Debug, PartialEq)]
+pub enum Halt {
+    /// All frames are processed.
+    FrameLimit,
+    /// Machine jumps to itself forever, which is how programs usually end.
+    Spin,
+    /// Machine waits for key press, which never comes in batch.
+    KeyWait,
+    Fault(Fault),
+}
+
+impl fmt::Display for Halt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Halt::FrameLimit => write!(f, "frame-limit"),
+            Halt::Spin => write!(f, "spin"),
+            Halt::KeyWait => write!(f, "key-wait"),
+            Halt::Fault(_) => write!(f, "fault"),
+        }
+    }
+}
+
+/// Provides result of one job.
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub name: String,
+    pub profile: QuirksProfile,
+    pub seed: u64,
+    /// Count of processed frames.
+    pub frames: u64,
+    /// Count of processed instructions.
+    pub cycles: u64,
+    /// FNV-1a hash of the final screen. See `screen_hash`.
+    pub screen_hash: u64,
+    pub halt: Halt,
+}
+
+/// Get FNV-1a hash of screen rows, from top to bottom in big endian.
+pub fn screen_hash(screen: &Screen) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for y in 0..SCREEN_HEIGHT as u8 {
+        for &byte in screen.row(y).to_be_bytes().iter() {
+            hash = (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+    hash
+}
+
+/// Process one job until it halts.
+pub fn run_job(job: &Job) -> Report {
+    let mut machine = Machine::new(&job.rom, job.profile.quirks());
+    machine.seed_random(job.seed);
+
+    let mut halt = Halt::FrameLimit;
+    for _ in 0..job.frames {
+        if let Err(fault) = machine.run_frame(job.cycles) {
+            halt = Halt::Fault(fault);
+            break;
+        }
+        let pc = machine.registers().get_pc();
+        if let MachineState::WaitKeyPress{ .. } = machine.state() {
+            halt = Halt::KeyWait;
+            break;
+        }
+        if machine.memory().parse_instruction(pc) == Some(Instruction::JmpAddr(pc)) {
+            halt = Halt::Spin;
+            break;
+        }
+    }
+
+    Report {
+        name: job.name.clone(),
+        profile: job.profile,
+        seed: job.seed,
+        frames: machine.frames(),
+        cycles: machine.cycles(),
+        screen_hash: screen_hash(machine.screen()),
+        halt,
+    }
+}
+
+/// Process jobs on given count of worker threads. Reports are in the same order as jobs.
+pub fn run(jobs: &[Job], threads: usize) -> Vec<Report> {
+    let next = AtomicUsize::new(0);
+    let reports: Mutex<Vec<Option<Report>>> = Mutex::new(jobs.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, jobs.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let job = match jobs.get(index) {
+                    Some(job) => job,
+                    None => break,
+                };
+                let report = run_job(job);
+                reports.lock().unwrap()[index] = Some(report);
+            });
+        }
+    });
+    reports.into_inner().unwrap().into_iter().map(|report| report.unwrap()).collect()
+}
+
+/// Write reports as CSV with header line. Fault column is empty if there is no fault.
+pub fn write_csv(reports: &[Report], out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "rom,profile,seed,frames,cycles,screen_hash,halt,fault")?;
+    for report in reports {
+        let fault = match &report.halt {
+            Halt::Fault(fault) => fault.to_string(),
+            _ => String::new(),
+        };
+        writeln!(out, "{},{},{},{},{},{:016x},{},{}",
+            csv_field(&report.name), report.profile, report.seed, report.frames, report.cycles,
+            report.screen_hash, report.halt, csv_field(&fault))?;
+    }
+    Ok(())
+}
+
+/// Write reports as JSON array of objects. Fault is null if there is no fault.
+pub fn write_json(reports: &[Report], out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "[")?;
+    for (index, report) in reports.iter().enumerate() {
+        let fault = match &report.halt {
+            Halt::Fault(fault) => json_string(&fault.to_string()),
+            _ => "null".to_string(),
+        };
+        writeln!(out,
+            "  {{\"rom\": {}, \"profile\": \"{}\", \"seed\": {}, \"frames\": {}, \"cycles\": {}, \"screen_hash\": \"{:016x}\", \"halt\": \"{}\", \"fault\": {}}}{}",
+            json_string(&report.name), report.profile, report.seed, report.frames, report.cycles,
+            report.screen_hash, report.halt, fault, if index + 1 < reports.len() { "," } else { "" })?;
+    }
+    writeln!(out, "]")
+}
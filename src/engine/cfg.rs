@@ -0,0 +1,227 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+};
+
+use super::isa::{Flow, Instruction};
+use super::memory::{Memory, MEMORY_SIZE};
+use super::register::INIT_PROGRAM_COUNTER_VAL;
+
+/// Provides kind of control flow edge between basic blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Edge {
+    /// Falls through into the next block, or skip condition is false.
+    Next,
+    /// 0x1nnn jumps.
+    Jump,
+    /// Skip condition is true, so the next instruction is skipped.
+    Skip,
+}
+
+impl Edge {
+    fn label(self) -> &'static str {
+        match self {
+            Edge::Next => "next",
+            Edge::Jump => "jump",
+            Edge::Skip => "skip",
+        }
+    }
+}
+
+/// Provides straight sequence of instructions which is entered only from the first one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    pub start: u16,
+    /// Address and instruction, in order.
+    pub instructions: Vec<(u16, Instruction)>,
+    pub successors: Vec<(u16, Edge)>,
+    /// Block ends with 0xBnnn, whose target is known only at runtime.
+    pub indirect_jump: bool,
+    /// Block ends at bytes which can not be parsed or are out of ROM.
+    pub invalid_end: bool,
+}
+
+impl BasicBlock {
+    /// Get address right after the last instruction.
+    pub fn end(&self) -> u16 {
+        self.instructions.last().map_or(self.start, |&(pc, _)| pc + 2)
+    }
+}
+
+/// Provides blocks reachable from entry point, or from target of 0x2nnn, without entering calls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub entry: u16,
+    pub blocks: BTreeMap<u16, BasicBlock>,
+    /// Entry points of called functions.
+    pub calls: BTreeSet<u16>,
+}
+
+impl Function {
+    /// Get name of function, which is `main` for the entry point of program.
+    pub fn name(&self) -> String {
+        if self.entry == INIT_PROGRAM_COUNTER_VAL {
+            "main".to_string()
+        } else {
+            format!("sub_{:03X}", self.entry)
+        }
+    }
+
+    /// Check whether any block ends with 0xBnnn.
+    pub fn has_indirect_jump(&self) -> bool {
+        self.blocks.values().any(|block| block.indirect_jump)
+    }
+}
+
+/// Provides control flow graph of ROM, split into functions at call targets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub functions: BTreeMap<u16, Function>,
+}
+
+/// Get successors of instruction inside function, and whether it ends basic block.
+/// Calls return to the next instruction, so they do not end block.
+fn successors(pc: u16, instruction: &Instruction) -> (Vec<(u16, Edge)>, bool) {
+    match instruction.flow() {
+        Flow::Jump(addr) => (vec![(addr, Edge::Jump)], true),
+        Flow::IndirectJump | Flow::Return => (vec![], true),
+        Flow::Skip => (vec![(pc + 2, Edge::Next), (pc + 4, Edge::Skip)], true),
+        Flow::Next | Flow::Call(_) => (vec![(pc + 2, Edge::Next)], false),
+    }
+}
+
+impl Cfg {
+    /// Build control flow graph of ROM loaded at 0x200.
+    pub fn build(rom: &[u8]) -> Cfg {
+        let memory = Memory::from_rom(rom);
+        let rom_end = INIT_PROGRAM_COUNTER_VAL as usize + rom.len().min(MEMORY_SIZE - INIT_PROGRAM_COUNTER_VAL as usize);
+        let parse = |pc: u16| {
+            if pc < INIT_PROGRAM_COUNTER_VAL || pc as usize + 2 > rom_end {
+                None
+            } else {
+                memory.parse_instruction(pc)
+            }
+        };
+
+        let mut functions = BTreeMap::new();
+        let mut pending = vec![INIT_PROGRAM_COUNTER_VAL];
+        while let Some(entry) = pending.pop() {
+            if functions.contains_key(&entry) {
+                continue;
+            }
+            let function = Cfg::build_function(entry, &parse);
+            pending.extend(function.calls.iter().copied());
+            functions.insert(entry, function);
+        }
+        Cfg { functions }
+    }
+
+    fn build_function(entry: u16, parse: &impl Fn(u16) -> Option<Instruction>) -> Function {
+        // Find reachable instructions and block leaders.
+        let mut leaders = BTreeSet::new();
+        let mut visited = BTreeSet::new();
+        let mut calls = BTreeSet::new();
+        let mut pending = vec![entry];
+        leaders.insert(entry);
+        while let Some(pc) = pending.pop() {
+            if !visited.insert(pc) {
+                continue;
+            }
+            let instruction = match parse(pc) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            if let Instruction::CallSub(addr) = instruction {
+                calls.insert(addr);
+            }
+            let (next, is_end) = successors(pc, &instruction);
+            if is_end {
+                leaders.extend(next.iter().map(|&(addr, _)| addr));
+            }
+            pending.extend(next.iter().map(|&(addr, _)| addr));
+        }
+
+        // Split instructions into blocks at leaders.
+        let mut blocks = BTreeMap::new();
+        for &start in &leaders {
+            let mut block = BasicBlock {
+                start,
+                instructions: Vec::new(),
+                successors: Vec::new(),
+                indirect_jump: false,
+                invalid_end: false,
+            };
+            let mut pc = start;
+            loop {
+                let instruction = match parse(pc) {
+                    Some(instruction) => instruction,
+                    None => {
+                        block.invalid_end = true;
+                        break;
+                    },
+                };
+                block.instructions.push((pc, instruction));
+                let (next, is_end) = successors(pc, &instruction);
+                block.indirect_jump = matches!(instruction, Instruction::JmpAddrOffReg0(_));
+                if is_end || leaders.contains(&(pc + 2)) {
+                    block.successors = next;
+                    break;
+                }
+                pc += 2;
+            }
+            blocks.insert(start, block);
+        }
+        Function { entry, blocks, calls }
+    }
+
+    /// Write DOT graph of given function, whose blocks have disassembly.
+    pub fn write_function_dot(&self, function: &Function, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "digraph \"{}\" {{", function.name())?;
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];")?;
+        for block in function.blocks.values() {
+            let mut label = String::new();
+            for &(pc, instruction) in &block.instructions {
+                let text = match instruction {
+                    Instruction::CallSub(addr) => match self.functions.get(&addr) {
+                        Some(callee) => format!("CALL {}", callee.name()),
+                        None => instruction.to_string(),
+                    },
+                    _ => instruction.to_string(),
+                };
+                label.push_str(&format!("0x{:03X}  {}\\l", pc, dot_escape(&text)));
+            }
+            if block.invalid_end {
+                label.push_str(&format!("0x{:03X}  (invalid)\\l", block.end()));
+            }
+            writeln!(out, "    \"0x{:03X}\" [label=\"{}\"];", block.start, label)?;
+            for &(target, edge) in &block.successors {
+                writeln!(out, "    \"0x{:03X}\" -> \"0x{:03X}\" [label=\"{}\"];", block.start, target, edge.label())?;
+            }
+            if block.indirect_jump {
+                writeln!(out, "    \"0x{:03X}?\" [label=\"unresolved\", shape=octagon];", block.start)?;
+                writeln!(out, "    \"0x{:03X}\" -> \"0x{:03X}?\" [label=\"indirect\", style=dashed];", block.start, block.start)?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+
+    /// Write DOT graph of calls between functions. Functions with unresolved indirect jumps are marked.
+    pub fn write_call_graph_dot(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "digraph calls {{")?;
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];")?;
+        for function in self.functions.values() {
+            let mark = if function.has_indirect_jump() { ", style=dashed" } else { "" };
+            writeln!(out, "    \"{}\" [label=\"{}\\n0x{:03X}\"{}];", function.name(), function.name(), function.entry, mark)?;
+            for callee in &function.calls {
+                if let Some(callee) = self.functions.get(callee) {
+                    writeln!(out, "    \"{}\" -> \"{}\";", function.name(), callee.name())?;
+                }
+            }
+        }
+        writeln!(out, "}}")
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -4,7 +4,8 @@ use std::{
     io::Read
 };
 
-fn is_file_valid_ch8(path: &str) -> bool {
+#[allow(clippy::unbuffered_bytes, clippy::redundant_pattern_matching)]
+pub fn is_file_valid_ch8(path: &str) -> bool {
     use std::path::Path;
 
     let path = Path::new(&path);
@@ -55,6 +56,7 @@ fn is_file_valid_ch8(path: &str) -> bool {
     true
 }
 
+#[allow(clippy::bool_comparison, clippy::needless_return)]
 pub fn get_ch8_file_path(args: &mut env::Args) -> Result<String, String> {
     // Check given arguments are valid.
     if args.len() != 2 {
@@ -0,0 +1,204 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use super::isa::{self, Instruction};
+use super::machine::Machine;
+use super::peripheral::{Bus, Display, Input, Timers};
+use super::hook::Hooks;
+use super::state::MachineState;
+use super::symbols::SymbolTable;
+use super::register::INIT_PROGRAM_COUNTER_VAL;
+use super::memory::MEMORY_SIZE;
+
+/// Provides how one byte of memory was accessed.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Access {
+    /// Count of instructions executed from this byte.
+    pub executed: u64,
+    /// Read as data by 0xDxyn or 0xFx65.
+    pub read: bool,
+    /// Written by 0xFx33 or 0xFx55.
+    pub written: bool,
+}
+
+impl Access {
+    /// Get one character mark of access.
+    /// `X` executed, `W` written, `D` read as data, `.` never touched.
+    /// Execution has priority over write, and write has priority over read.
+    pub fn mark(&self) -> char {
+        if self.executed > 0 { 'X' }
+        else if self.written { 'W' }
+        else if self.read { 'D' }
+        else { '.' }
+    }
+}
+
+/// Provides execution of code which was written at runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfModification {
+    pub pc: u16,
+    pub cycle: u64,
+}
+
+/// Provides ROM code coverage tracker.
+/// `observe` must be called right before every `Machine::step`.
+pub struct Coverage {
+    access: Vec<Access>,
+    /// Byte is written since it was executed last, or ever.
+    stale: Vec<bool>,
+    self_modifications: Vec<SelfModification>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Coverage::new()
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage {
+            access: vec![Access::default(); MEMORY_SIZE],
+            stale: vec![false; MEMORY_SIZE],
+            self_modifications: Vec::new(),
+        }
+    }
+
+    /// Get access of given address.
+    pub fn access(&self, addr: u16) -> Access {
+        self.access.get(addr as usize).copied().unwrap_or_default()
+    }
+
+    /// Get every first execution of runtime-written code, per address.
+    pub fn self_modifications(&self) -> &[SelfModification] { &self.self_modifications }
+
+    fn mark_range(&mut self, from: u16, count: usize, mark: fn(&mut Access)) {
+        for addr in (from as usize..).take(count).filter(|&addr| addr < MEMORY_SIZE) {
+            mark(&mut self.access[addr]);
+        }
+    }
+
+    fn mark_written(&mut self, from: u16, count: usize) {
+        self.mark_range(from, count, |access| access.written = true);
+        for addr in (from as usize..).take(count).filter(|&addr| addr < MEMORY_SIZE) {
+            self.stale[addr] = true;
+        }
+    }
+
+    /// Record the instruction that machine is about to process, and memory it will access.
+    pub fn observe<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&mut self, machine: &Machine<B, D, I, T, H>) {
+        if *machine.state() != MachineState::Normal {
+            return;
+        }
+
+        let registers = machine.registers();
+        let pc = registers.get_pc();
+        let instruction = match machine.bus().fetch(pc) {
+            Some(instruction) => instruction,
+            None => return,
+        };
+
+        // Code may be executed, written and executed again. Warn only once per address.
+        let range = pc as usize..(pc as usize + 2).min(MEMORY_SIZE);
+        if self.stale[range.clone()].contains(&true) && self.self_modifications.iter().all(|smc| smc.pc != pc) {
+            self.self_modifications.push(SelfModification{ pc, cycle: machine.cycles() });
+        }
+        self.stale[range].fill(false);
+        self.mark_range(pc, 2, |access| access.executed += 1);
+
+        let l = registers.get_l();
+        match instruction {
+            Instruction::DispSpr{ n, .. } => self.mark_range(l, n as usize, |access| access.read = true),
+            Instruction::MemRead{ endr } => self.mark_range(l, endr as usize + 1, |access| access.read = true),
+            Instruction::MemDump{ endr } => self.mark_written(l, endr as usize + 1),
+            Instruction::MemDumpBcdFromReg{ .. } => self.mark_written(l, 3),
+            _ => (),
+        }
+    }
+
+    /// Make annotated disassembly lines of ROM. Each line has address of its word if exists.
+    /// Labels of `symbols` are written as their own lines.
+    pub fn disassembly(&self, rom: &[u8], symbols: &SymbolTable) -> Vec<(Option<u16>, String)> {
+        let mut lines = vec![
+            (None, "; X executed, W written at runtime, D read as data, . never touched".to_string()),
+        ];
+        for smc in &self.self_modifications {
+            lines.push((None, format!("; WARNING : runtime-written code executed at 0x{:03X} (cycle {})", smc.pc, smc.cycle)));
+        }
+
+        for (i, word) in rom.chunks(2).enumerate() {
+            let addr = INIT_PROGRAM_COUNTER_VAL + (i as u16) * 2;
+            if let Some(label) = symbols.label(addr) {
+                lines.push((None, format!("{}:", label)));
+            }
+
+            let (first, second) = (self.access(addr), self.access(addr + 1));
+            let marks: String = [first.mark(), second.mark()].iter().collect();
+            let text = match word {
+                [hi, lo] if first.executed > 0 || (first.mark() == '.' && second.mark() == '.') => {
+                    match isa::parse_instruction(&[*hi, *lo]) {
+                        Some(instruction) => instruction.to_string(),
+                        None => format!("db 0x{:02X}, 0x{:02X}", hi, lo),
+                    }
+                },
+                [hi, lo] => format!("db 0x{:02X}, 0x{:02X}", hi, lo),
+                [hi] => format!("db 0x{:02X}", hi),
+                _ => unreachable!(),
+            };
+            let hex: String = word.iter().map(|byte| format!("{:02X}", byte)).collect();
+            lines.push((Some(addr), format!("0x{:03X}  {:<4}  {}  {:>8}  {}", addr, hex, marks, first.executed, text)));
+        }
+        lines
+    }
+
+    /// Write annotated disassembly of ROM.
+    pub fn write_disassembly(&self, out: &mut dyn Write, rom: &[u8], symbols: &SymbolTable) -> io::Result<()> {
+        for (_, line) in self.disassembly(rom, symbols) {
+            writeln!(out, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Write lcov tracefile.
+    /// If `symbols` has source lines, hits are reported against source files.
+    /// Otherwise hits are reported against lines of annotated disassembly named `disassembly_name`.
+    /// Words only read or written as data are not reported as lines.
+    pub fn write_lcov(&self, out: &mut dyn Write, rom: &[u8], symbols: &SymbolTable, disassembly_name: &str) -> io::Result<()> {
+        let is_code = |addr: u16| {
+            let access = self.access(addr);
+            access.executed > 0 || access.mark() == '.'
+        };
+
+        // File name -> (line -> hits)
+        let mut files = BTreeMap::<String, BTreeMap<usize, u64>>::new();
+        if symbols.has_lines() {
+            for addr in (INIT_PROGRAM_COUNTER_VAL..).take(rom.len()) {
+                if let (Some((file, line)), true) = (symbols.line(addr), is_code(addr)) {
+                    let hits = files.entry(file.to_string()).or_default().entry(line).or_insert(0);
+                    *hits = (*hits).max(self.access(addr).executed);
+                }
+            }
+        } else {
+            let lines = files.entry(disassembly_name.to_string()).or_default();
+            for (line_no, (addr, _)) in self.disassembly(rom, symbols).iter().enumerate() {
+                if let Some(addr) = addr.filter(|&addr| is_code(addr)) {
+                    lines.insert(line_no + 1, self.access(addr).executed);
+                }
+            }
+        }
+
+        writeln!(out, "TN:chipmunk")?;
+        for (file, lines) in &files {
+            writeln!(out, "SF:{}", file)?;
+            for (line, hits) in lines {
+                writeln!(out, "DA:{},{}", line, hits)?;
+            }
+            writeln!(out, "LH:{}", lines.values().filter(|&&hits| hits > 0).count())?;
+            writeln!(out, "LF:{}", lines.len())?;
+            writeln!(out, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::cfg::Cfg;
+use super::isa::Instruction;
+use super::register::INIT_PROGRAM_COUNTER_VAL;
+use super::symbols::SymbolTable;
+
+/// Provides one line of decompiled source. Address is of the instruction which the line starts with.
+struct Line {
+    addr: Option<u16>,
+    depth: usize,
+    text: String,
+}
+
+/// Get condition under which given instruction skips the next one, as Octo condition.
+fn skip_condition(instruction: &Instruction, name: &impl Fn(u8) -> String) -> Option<String> {
+    type Inst = Instruction;
+    let condition = match *instruction {
+        Inst::SkipEq{ r, val } => format!("{} == {}", name(r), val),
+        Inst::SkipNeq{ r, val } => format!("{} != {}", name(r), val),
+        Inst::SkipRegEq{ r, f } => format!("{} == {}", name(r), name(f)),
+        Inst::SkipRegNeq{ r, f } => format!("{} != {}", name(r), name(f)),
+        Inst::SkipKeyPressed{ r } => format!("{} key", name(r)),
+        Inst::SkipKeyReleased{ r } => format!("{} -key", name(r)),
+        _ => return None,
+    };
+    Some(condition)
+}
+
+/// Negate Octo condition made by `skip_condition`.
+fn negate(condition: &str) -> String {
+    if let Some(reg) = condition.strip_suffix(" -key") {
+        format!("{} key", reg)
+    } else if let Some(reg) = condition.strip_suffix(" key") {
+        format!("{} -key", reg)
+    } else if condition.contains(" == ") {
+        condition.replace(" == ", " != ")
+    } else {
+        condition.replace(" != ", " == ")
+    }
+}
+
+/// Render sprite row as bitmap, `#` for set pixel.
+fn bitmap(byte: u8) -> String {
+    (0..8).rev().map(|i| if byte & (1 << i) != 0 { '#' } else { '.' }).collect()
+}
+
+/// Provides structure recovery of one ROM into Octo-flavored pseudo-source.
+struct Decompiler<'a> {
+    rom: &'a [u8],
+    cfg: Cfg,
+    /// Instructions of the function being decompiled.
+    code: BTreeMap<u16, Instruction>,
+    aliases: BTreeMap<u8, String>,
+    /// Sprite address and the largest height drawn from it.
+    sprites: BTreeMap<u16, u8>,
+    /// Jump targets which need labels.
+    labels: BTreeSet<u16>,
+    lines: Vec<Line>,
+    /// Labels which name functions, jump targets and sprites instead of generated names.
+    symbols: &'a SymbolTable,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(rom: &'a [u8], symbols: &'a SymbolTable) -> Decompiler<'a> {
+        let cfg = Cfg::build(rom);
+        let mut code = BTreeMap::new();
+        let mut sprites = BTreeMap::new();
+        for function in cfg.functions.values() {
+            for block in function.blocks.values() {
+                code.extend(block.instructions.iter().copied());
+                // Sprite is drawn from I set by 0xAnnn in the same block.
+                let mut l = None;
+                for &(_, instruction) in &block.instructions {
+                    match instruction {
+                        Instruction::SetRegL(addr) => l = Some(addr),
+                        Instruction::AddRegL{ .. } | Instruction::SetRegLFontAddrFromReg{ .. }
+                        | Instruction::MemDump{ .. } | Instruction::MemRead{ .. } => l = None,
+                        Instruction::DispSpr{ n, .. } if n > 0 => if let Some(addr) = l {
+                            let height = sprites.entry(addr).or_insert(0);
+                            *height = n.max(*height);
+                        },
+                        _ => (),
+                    }
+                }
+            }
+        }
+
+        let aliases = Decompiler::find_aliases(&code);
+        Decompiler { rom, cfg, code: BTreeMap::new(), aliases, sprites, labels: BTreeSet::new(), lines: Vec::new(), symbols }
+    }
+
+    /// Name registers by the role they are used for most, such as sprite coordinates and key.
+    fn find_aliases(code: &BTreeMap<u16, Instruction>) -> BTreeMap<u8, String> {
+        type Inst = Instruction;
+        const ROLES: [&str; 6] = ["x", "y", "key", "time", "rand", "digit"];
+        let mut counts = [[0usize; ROLES.len()]; 15];
+        for instruction in code.values() {
+            let uses: Vec<(u8, usize)> = match *instruction {
+                Inst::DispSpr{ rp, .. } if rp.0 != rp.1 => vec![(rp.0, 0), (rp.1, 1)],
+                Inst::WaitKeyPress{ r } | Inst::SkipKeyPressed{ r } | Inst::SkipKeyReleased{ r } => vec![(r, 2)],
+                Inst::SetDelayToReg{ r } => vec![(r, 3)],
+                Inst::RndAnd{ r, .. } => vec![(r, 4)],
+                Inst::SetRegLFontAddrFromReg{ r } | Inst::MemDumpBcdFromReg{ r } => vec![(r, 5)],
+                _ => vec![],
+            };
+            // VF is the flag register, and keeps its own name.
+            for (r, role) in uses.into_iter().filter(|&(r, _)| r < 0xF) {
+                counts[r as usize][role] += 1;
+            }
+        }
+
+        let mut aliases = BTreeMap::new();
+        let mut taken: BTreeMap<&str, usize> = BTreeMap::new();
+        for (r, count) in counts.iter().enumerate() {
+            let best = (0..ROLES.len()).filter(|&role| count[role] > 0).max_by_key(|&role| (count[role], ROLES.len() - role));
+            if let Some(role) = best {
+                let index = taken.entry(ROLES[role]).or_insert(0);
+                *index += 1;
+                let name = if *index == 1 { ROLES[role].to_string() } else { format!("{}{}", ROLES[role], index) };
+                aliases.insert(r as u8, name);
+            }
+        }
+        aliases
+    }
+
+    fn reg(&self, r: u8) -> String {
+        self.aliases.get(&r).cloned().unwrap_or_else(|| format!("v{:x}", r))
+    }
+
+    /// Get name of code address, which is function name for call targets. Labels of symbols come first.
+    fn label(&self, addr: u16) -> String {
+        match (self.symbols.label(addr), self.cfg.functions.get(&addr)) {
+            (Some(name), _) => name.to_string(),
+            (None, Some(function)) => function.name(),
+            (None, None) => format!("label_{:03X}", addr),
+        }
+    }
+
+    /// Get name of jump target, which is placed right before its instruction.
+    fn target_label(&self, addr: u16) -> String {
+        self.symbols.label(addr).map_or_else(|| format!("label_{:03X}", addr), str::to_string)
+    }
+
+    fn sprite_label(&self, addr: u16) -> String {
+        self.symbols.label(addr).map_or_else(|| format!("sprite_{:03X}", addr), str::to_string)
+    }
+
+    fn emit(&mut self, addr: Option<u16>, depth: usize, text: String) {
+        self.lines.push(Line { addr, depth, text });
+    }
+
+    /// Render instruction as one statement.
+    fn statement(&mut self, instruction: &Instruction) -> String {
+        type Inst = Instruction;
+        let reg = |r: u8| self.reg(r);
+        match *instruction {
+            Inst::Ignore(_) => "# 0nnn is ignored".to_string(),
+            Inst::ClearDisplay => "clear".to_string(),
+            Inst::ReturnSubroutine => "return".to_string(),
+            Inst::JmpAddr(addr) => {
+                if !self.cfg.functions.contains_key(&addr) {
+                    self.labels.insert(addr);
+                }
+                format!("jump {}", self.label(addr))
+            },
+            Inst::CallSub(addr) => self.label(addr),
+            Inst::SetByte{ r, val } => format!("{} := {}", reg(r), val),
+            Inst::AddByte{ r, val } => format!("{} += {}", reg(r), val),
+            Inst::SetRegV{ r, f } => format!("{} := {}", reg(r), reg(f)),
+            Inst::OrRegV{ r, f } => format!("{} |= {}", reg(r), reg(f)),
+            Inst::AndRegV{ r, f } => format!("{} &= {}", reg(r), reg(f)),
+            Inst::XorRegV{ r, f } => format!("{} ^= {}", reg(r), reg(f)),
+            Inst::AddRegV{ r, f } => format!("{} += {}", reg(r), reg(f)),
+            Inst::SubRegV{ r, f } => format!("{} -= {}", reg(r), reg(f)),
+            Inst::ShrRegV{ r, f } => format!("{} >>= {}", reg(r), reg(f)),
+            Inst::SubNRegV{ r, f } => format!("{} =- {}", reg(r), reg(f)),
+            Inst::ShlRegV{ r, f } => format!("{} <<= {}", reg(r), reg(f)),
+            Inst::SetRegL(addr) if self.sprites.contains_key(&addr) => format!("i := {}", self.sprite_label(addr)),
+            Inst::SetRegL(addr) => format!("i := 0x{:03X}", addr),
+            Inst::JmpAddrOffReg0(addr) => format!("jump0 0x{:03X}  # unresolved", addr),
+            Inst::RndAnd{ r, val } => format!("{} := random 0x{:02X}", reg(r), val),
+            Inst::DispSpr{ rp, n } => format!("sprite {} {} {}", reg(rp.0), reg(rp.1), n),
+            Inst::SetDelayToReg{ r } => format!("{} := delay", reg(r)),
+            Inst::WaitKeyPress{ r } => format!("{} := key", reg(r)),
+            Inst::SetDelayFromReg{ r } => format!("delay := {}", reg(r)),
+            Inst::SetSoundFromReg{ r } => format!("buzzer := {}", reg(r)),
+            Inst::AddRegL{ r } => format!("i += {}", reg(r)),
+            Inst::SetRegLFontAddrFromReg{ r } => format!("i := hex {}", reg(r)),
+            Inst::MemDumpBcdFromReg{ r } => format!("bcd {}", reg(r)),
+            Inst::MemDump{ endr } => format!("save {}", reg(endr)),
+            Inst::MemRead{ endr } => format!("load {}", reg(endr)),
+            Inst::SkipEq{ .. } | Inst::SkipNeq{ .. } | Inst::SkipRegEq{ .. } | Inst::SkipRegNeq{ .. }
+            | Inst::SkipKeyPressed{ .. } | Inst::SkipKeyReleased{ .. } => {
+                let condition = skip_condition(instruction, &reg).unwrap();
+                format!("if {} then  # skips out of structure", negate(&condition))
+            },
+        }
+    }
+
+    /// Find the last backward jump to `head` in [head, to), which closes a loop.
+    fn loop_end(&self, head: u16, to: u16) -> Option<u16> {
+        self.code.range(head..to).rev()
+            .find(|&(_, instruction)| *instruction == Instruction::JmpAddr(head))
+            .map(|(&pc, _)| pc)
+    }
+
+    /// Emit instructions of [from, to) with recovered structure.
+    /// `exit` is the address right after the innermost loop, which `while` leaves to.
+    fn region(&mut self, from: u16, to: u16, depth: usize, exit: Option<u16>) {
+        let mut pc = from;
+        while let Some((&at, &instruction)) = self.code.range(pc..to).next() {
+            pc = at;
+            // Loop whose head is here.
+            if let Some(end) = self.loop_end(pc, to).filter(|_| exit.is_none() || pc != from) {
+                self.emit(Some(pc), depth, "loop".to_string());
+                self.region(pc, end, depth + 1, Some(end + 2));
+                self.emit(None, depth, "again".to_string());
+                pc = end + 2;
+                continue;
+            }
+
+            let reg = |r: u8| self.reg(r);
+            let condition = match skip_condition(&instruction, &reg) {
+                Some(condition) => condition,
+                None => {
+                    let text = self.statement(&instruction);
+                    self.emit(Some(pc), depth, text);
+                    pc += 2;
+                    continue;
+                },
+            };
+            let next = self.code.get(&(pc + 2)).copied().filter(|_| pc + 2 < to);
+            match next {
+                // Skip over jump out of loop.
+                Some(Instruction::JmpAddr(target)) if Some(target) == exit => {
+                    self.emit(Some(pc), depth, format!("while {}", condition));
+                    pc += 4;
+                },
+                // Skip over forward jump, which jumps over body.
+                Some(Instruction::JmpAddr(target)) if target > pc + 4 && target <= to => {
+                    self.emit(Some(pc), depth, format!("if {} begin", condition));
+                    let else_end = match self.code.get(&(target - 2)) {
+                        Some(&Instruction::JmpAddr(end)) if target - 2 > pc + 4 && end > target && end <= to => Some(end),
+                        _ => None,
+                    };
+                    match else_end {
+                        Some(end) => {
+                            self.region(pc + 4, target - 2, depth + 1, exit);
+                            self.emit(None, depth, "else".to_string());
+                            self.region(target, end, depth + 1, exit);
+                            pc = end;
+                        },
+                        None => {
+                            self.region(pc + 4, target, depth + 1, exit);
+                            pc = target;
+                        },
+                    }
+                    self.emit(None, depth, "end".to_string());
+                },
+                Some(next) if skip_condition(&next, &reg).is_none() => {
+                    let text = self.statement(&next);
+                    self.emit(Some(pc), depth, format!("if {} then {}", negate(&condition), text));
+                    pc += 4;
+                },
+                _ => {
+                    let text = self.statement(&instruction);
+                    self.emit(Some(pc), depth, text);
+                    pc += 2;
+                },
+            }
+        }
+    }
+
+    fn decompile(mut self) -> String {
+        let functions: Vec<(u16, String, u16, BTreeMap<u16, Instruction>)> = self.cfg.functions.values()
+            .map(|function| {
+                let end = function.blocks.values().map(|block| block.end()).max().unwrap_or(function.entry);
+                let code = function.blocks.values().flat_map(|block| block.instructions.iter().copied()).collect();
+                (function.entry, self.label(function.entry), end, code)
+            })
+            .collect();
+        for (entry, name, end, code) in functions {
+            // Other functions placed between blocks of this one are not part of it.
+            self.code = code;
+            self.emit(None, 0, String::new());
+            self.emit(None, 0, format!(": {}", name));
+            self.region(entry, end, 1, None);
+        }
+
+        let mut source = String::from("# Decompiled by chipmunk. Structure is recovered from control flow.\n");
+        for (r, name) in &self.aliases {
+            source.push_str(&format!(":alias {} v{:x}\n", name, r));
+        }
+        let placed: BTreeSet<u16> = self.labels.iter().copied()
+            .filter(|&addr| self.lines.iter().any(|line| line.addr == Some(addr)))
+            .collect();
+        for addr in self.labels.difference(&placed) {
+            source.push_str(&format!("# {} is inside a recovered structure\n", self.target_label(*addr)));
+        }
+
+        let mut labeled = BTreeSet::new();
+        for line in &self.lines {
+            if let Some(addr) = line.addr.filter(|addr| placed.contains(addr) && labeled.insert(*addr)) {
+                // Function already has the label of symbols.
+                if self.symbols.label(addr).is_none() || !self.cfg.functions.contains_key(&addr) {
+                    source.push_str(&format!(": {}\n", self.target_label(addr)));
+                }
+            }
+            source.push_str(&"\t".repeat(line.depth));
+            source.push_str(&line.text);
+            source.push('\n');
+        }
+
+        for (&addr, &height) in &self.sprites {
+            source.push_str(&format!("\n: {}\n", self.sprite_label(addr)));
+            for offset in 0..height as u16 {
+                let index = (addr + offset) as usize;
+                match index.checked_sub(INIT_PROGRAM_COUNTER_VAL as usize).and_then(|index| self.rom.get(index)) {
+                    Some(&byte) => source.push_str(&format!("\t0x{:02X}  # {}\n", byte, bitmap(byte))),
+                    None => source.push_str(&format!("\t# 0x{:03X} is out of ROM\n", addr + offset)),
+                }
+            }
+        }
+        source
+    }
+}
+
+/// Decompile ROM into Octo-flavored pseudo-source.
+///
+/// Functions are found from control flow graph. Skip over forward jump becomes `if .. begin .. end`
+/// (with `else` if the body ends with forward jump), backward jump becomes `loop .. again`, and skip over
+/// jump out of loop becomes `while`. Registers are aliased by their use, and sprites drawn from `i := addr`
+/// are written as data with bitmap comments.
+pub fn decompile(rom: &[u8]) -> String {
+    decompile_with_symbols(rom, &SymbolTable::new())
+}
+
+/// Same to `decompile`, but functions, labels and sprites are named by labels of given symbols.
+pub fn decompile_with_symbols(rom: &[u8], symbols: &SymbolTable) -> String {
+    Decompiler::new(rom, symbols).decompile()
+}
@@ -1,17 +1,34 @@
 use std::{
     io::{self, Write},
     ops::{Drop},
-    error::Error
+    error::Error,
+    time,
 };
 
 extern crate crossterm;
 use crossterm::{
     cursor, style,
+    event::{self, KeyEvent, KeyCode},
     terminal::{self, ClearType},
 };
 
-/// Provides rendering device.
+use super::frontend::{Frontend, Event, HostKey};
+use super::keypad::Keypad;
+use super::machine::Output;
+use super::peripheral::Display;
+use super::screen::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+/// Convert crossterm error into io error.
+fn io_error(err: crossterm::ErrorKind) -> io::Error {
+    match err {
+        crossterm::ErrorKind::IoError(err) => err,
+        err => io::Error::other(format!("{:?}", err)),
+    }
+}
+
+/// Provides rendering device, which is the terminal front end.
 /// To use device, valid terminal or console must be provided from OS.
+/// `Esc` quits and `p` pauses emulation.
 pub struct Device {
     stdout: io::Stdout
 }
@@ -62,3 +79,52 @@ impl Device {
         Ok(())
     }
 }
+
+impl Frontend for Device {
+    fn present(&mut self, output: &Output, display: &dyn Display) -> io::Result<()> {
+        match output {
+            Output::Cleared => self.clear().map_err(io_error),
+            Output::Drawn(dirty_rows) => {
+                // Update window buffer row by row.
+                for y in (0..SCREEN_HEIGHT as u8).filter(|y| dirty_rows & (1 << y) != 0) {
+                    let line: String = (0..SCREEN_WIDTH as u8)
+                        .map(|x| if display.is_drawn((x, y)) { '\u{2588}' } else { ' ' })
+                        .collect();
+                    self.mv_print((0, y), &line).map_err(io_error)?;
+                }
+                Ok(())
+            },
+            Output::None => Ok(()),
+        }
+    }
+
+    fn poll_event(&mut self) -> io::Result<Option<Event>> {
+        self.wait_event(time::Duration::from_secs(0))
+    }
+
+    fn wait_event(&mut self, timeout: time::Duration) -> io::Result<Option<Event>> {
+        if !event::poll(timeout).map_err(io_error)? {
+            return Ok(None);
+        }
+
+        // calling read() will be unblocked because some input is already polled.
+        // Terminal does not report key release, so only key press is reported.
+        match event::read().map_err(io_error)? {
+            // If Escape key is pressed, terminate program.
+            event::Event::Key(KeyEvent{ code: KeyCode::Esc, modifiers: _ }) => Ok(Some(Event::Host(HostKey::Quit))),
+            event::Event::Key(KeyEvent{ code: KeyCode::Char('p'), modifiers: _ }) => Ok(Some(Event::Host(HostKey::Pause))),
+            // If read value has KeyCode::Char(), try to update keypad state.
+            event::Event::Key(KeyEvent{ code: KeyCode::Char(val), modifiers: _ }) => {
+                Ok(Keypad::key_from_char(val).map(Event::KeyDown))
+            },
+            _ => Ok(None),
+        }
+    }
+
+    fn set_beep(&mut self, on: bool) {
+        // Terminal can only ring bell once.
+        if on {
+            let _ = crossterm::execute!(&mut self.stdout, style::Print("\x07"));
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    fs,
+    sync::Arc,
+};
+
+use super::isa::Instruction;
+use super::machine::Machine;
+use super::memory::MEMORY_SIZE;
+use super::quirks::{Quirks, QuirksProfile};
+use super::state::MachineState;
+
+/// Count of actions. Action 0 presses no key, and action `k + 1` holds key `k`.
+pub const ACTION_COUNT: usize = 17;
+
+/// Provides location of value which game keeps, such as score or lives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    /// `len` bytes from `addr` as big endian unsigned number.
+    Memory{ addr: u16, len: u8 },
+    /// `len` bytes from `addr` as decimal digits, one digit per byte as `Fx33` stores.
+    Bcd{ addr: u16, len: u8 },
+    /// General register Vx.
+    Register(u8),
+}
+
+impl Value {
+    /// Read value from given machine.
+    pub fn read(&self, machine: &Machine) -> u64 {
+        match *self {
+            Value::Memory{ addr, len } => machine.memory().get_data_bytes(addr as usize, len as usize)
+                .iter().fold(0, |value, &byte| value << 8 | byte as u64),
+            Value::Bcd{ addr, len } => machine.memory().get_data_bytes(addr as usize, len as usize)
+                .iter().fold(0, |value, &byte| value * 10 + byte as u64),
+            Value::Register(r) => machine.registers().general_register(r) as u64,
+        }
+    }
+}
+
+/// Provides what environment observes after each step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Observation {
+    /// Screen rows from top to bottom, packed 8 pixels per byte with the leftmost pixel in MSB.
+    Screen,
+    /// Whole 4KiB memory.
+    Ram,
+}
+
+/// Provides per-ROM definition of environment.
+///
+/// Spec file is a text file which consists of lines below. `#` starts a comment.
+///
+/// ``` text
+/// score 0x2F0 2       # Score is 2 bytes big endian number from 0x2F0. (`score V3` reads a register)
+/// score-bcd 0x2F0 3   # Or, score is 3 decimal digits from 0x2F0.
+/// lives 0x2F4 1       # Lives is 1 byte from 0x2F4, and episode ends when it becomes `game-over`.
+/// game-over 0
+/// frame-skip 4        # Action is held for 4 frames per step.
+/// cycles 15           # Instructions per frame.
+/// max-frames 18000    # Episode ends after this count of frames.
+/// observation screen  # Or `ram`.
+/// quirks vip
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameSpec {
+    pub score: Option<Value>,
+    pub lives: Option<Value>,
+    /// Lives value which ends episode.
+    pub game_over: u64,
+    /// Count of frames per step.
+    pub frame_skip: u64,
+    /// Count of instructions per frame.
+    pub cycles: usize,
+    /// Maximum count of frames of one episode.
+    pub max_frames: u64,
+    pub observation: Observation,
+    pub quirks: Quirks,
+}
+
+impl Default for GameSpec {
+    fn default() -> Self {
+        GameSpec::new()
+    }
+}
+
+impl GameSpec {
+    pub fn new() -> GameSpec {
+        GameSpec {
+            score: None,
+            lives: None,
+            game_over: 0,
+            frame_skip: 4,
+            cycles: 15,
+            max_frames: 18000,
+            observation: Observation::Screen,
+            quirks: Quirks::default(),
+        }
+    }
+
+    /// Load spec file from given path.
+    pub fn load(path: &str) -> Result<GameSpec, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{} : {}", path, err))?;
+        GameSpec::parse(&text).map_err(|err| format!("{} : {}", path, err))
+    }
+
+    /// Parse text of spec file.
+    pub fn parse(text: &str) -> Result<GameSpec, String> {
+        let mut spec = GameSpec::new();
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() { continue; }
+
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let invalid = |token: &str| format!("line {} : invalid value {}", line_no + 1, token);
+            let parse_number = |token: &str| {
+                match token.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16),
+                    None => token.parse(),
+                }.map_err(|_| invalid(token))
+            };
+            let parse_memory = |addr: &str, len: Option<&&str>| -> Result<(u16, u8), String> {
+                let len = len.map_or(Ok(1), |len| parse_number(len))?;
+                let addr = parse_number(addr)?;
+                let end = addr.checked_add(len).ok_or_else(|| invalid(line))?;
+                if len == 0 || len > 8 || end > MEMORY_SIZE as u64 {
+                    return Err(invalid(line));
+                }
+                Ok((addr as u16, len as u8))
+            };
+            let parse_value = |args: &[&str]| -> Result<Value, String> {
+                match args {
+                    [reg] if reg.starts_with('V') || reg.starts_with('v') => {
+                        match u8::from_str_radix(&reg[1..], 16) {
+                            Ok(r) if r < 16 => Ok(Value::Register(r)),
+                            _ => Err(invalid(reg)),
+                        }
+                    },
+                    [addr] | [addr, _] => {
+                        let (addr, len) = parse_memory(addr, args.get(1))?;
+                        Ok(Value::Memory{ addr, len })
+                    },
+                    _ => Err(invalid(line)),
+                }
+            };
+            match tokens[..] {
+                ["score", ref args @ ..] => spec.score = Some(parse_value(args)?),
+                ["score-bcd", addr, ref len @ ..] if len.len() <= 1 => {
+                    let (addr, len) = parse_memory(addr, len.first())?;
+                    spec.score = Some(Value::Bcd{ addr, len });
+                },
+                ["lives", ref args @ ..] => spec.lives = Some(parse_value(args)?),
+                ["game-over", value] => spec.game_over = parse_number(value)?,
+                ["frame-skip", value] => {
+                    spec.frame_skip = parse_number(value)?;
+                    if spec.frame_skip == 0 { return Err(invalid(value)); }
+                },
+                ["cycles", value] => spec.cycles = parse_number(value)? as usize,
+                ["max-frames", value] => spec.max_frames = parse_number(value)?,
+                ["observation", "screen"] => spec.observation = Observation::Screen,
+                ["observation", "ram"] => spec.observation = Observation::Ram,
+                ["quirks", name] => {
+                    spec.quirks = QuirksProfile::from_name(name).ok_or_else(|| invalid(name))?.quirks();
+                },
+                _ => return Err(format!("line {} : unknown entry {}", line_no + 1, line)),
+            }
+        }
+
+        Ok(spec)
+    }
+}
+
+/// Provides gym-style reinforcement learning environment of one ROM.
+///
+/// Every step holds the key of action for `frame_skip` frames, then releases it.
+/// Reward is the increase of score, and episode is done when lives reaches `game_over`,
+/// machine faults or spins on itself, or `max_frames` frames are processed.
+/// Environment is deterministic for given seed and actions, and clone of it proceeds
+/// independently, so it can be used for tree search.
+#[derive(Clone)]
+pub struct Env {
+    rom: Arc<[u8]>,
+    spec: GameSpec,
+    machine: Machine,
+    score: u64,
+    done: bool,
+}
+
+impl Env {
+    /// Create new environment. `reset` is called with seed 0.
+    pub fn new(rom: &[u8], spec: GameSpec) -> Env {
+        let machine = Machine::new(rom, spec.quirks);
+        let mut env = Env { rom: rom.into(), spec, machine, score: 0, done: false };
+        env.reset(0);
+        env
+    }
+
+    /// Restart episode with given seed of random number generator, and return the first observation.
+    pub fn reset(&mut self, seed: u64) -> Vec<u8> {
+        self.machine = Machine::new(&self.rom, self.spec.quirks);
+        self.machine.seed_random(seed);
+        self.score = self.read_score();
+        self.done = false;
+        self.observe()
+    }
+
+    /// Process given action, and return observation, reward and whether episode is done.
+    /// Step after episode is done does nothing and returns zero reward.
+    pub fn step(&mut self, action: usize) -> (Vec<u8>, i64, bool) {
+        assert!(action < ACTION_COUNT, "Invalid action {}", action);
+        if self.done {
+            return (self.observe(), 0, true);
+        }
+
+        let key = action.checked_sub(1).map(|key| key as u8);
+        if let Some(key) = key {
+            self.machine.press_key(key);
+        }
+        for _ in 0..self.spec.frame_skip {
+            self.done = self.machine.run_frame(self.spec.cycles).is_err()
+                || self.is_spinning()
+                || self.lives() == Some(self.spec.game_over)
+                || self.machine.frames() >= self.spec.max_frames;
+            if self.done { break; }
+        }
+        if let Some(key) = key {
+            self.machine.release_key(key);
+        }
+
+        let score = self.read_score();
+        let reward = score as i64 - self.score as i64;
+        self.score = score;
+        (self.observe(), reward, self.done)
+    }
+
+    /// Get current observation.
+    pub fn observe(&self) -> Vec<u8> {
+        match self.spec.observation {
+            Observation::Screen => {
+                let screen = self.machine.screen();
+                let bytes = screen.width() / 8;
+                (0..screen.height() as u8)
+                    .flat_map(|y| screen.row(y).to_be_bytes()[..bytes].to_vec())
+                    .collect()
+            },
+            Observation::Ram => self.machine.memory().get_data_bytes(0, MEMORY_SIZE).to_vec(),
+        }
+    }
+
+    pub fn spec(&self) -> &GameSpec { &self.spec }
+
+    pub fn machine(&self) -> &Machine { &self.machine }
+
+    /// Get current score, or 0 if score is not defined.
+    pub fn score(&self) -> u64 { self.score }
+
+    /// Get current lives, if lives is defined.
+    pub fn lives(&self) -> Option<u64> {
+        self.spec.lives.map(|lives| lives.read(&self.machine))
+    }
+
+    pub fn is_done(&self) -> bool { self.done }
+
+    fn read_score(&self) -> u64 {
+        self.spec.score.map_or(0, |score| score.read(&self.machine))
+    }
+
+    /// Check whether machine jumps to itself, which never ends unless a key wait is pending.
+    fn is_spinning(&self) -> bool {
+        let pc = self.machine.registers().get_pc();
+        *self.machine.state() == MachineState::Normal
+            && self.machine.memory().parse_instruction(pc) == Some(Instruction::JmpAddr(pc))
+    }
+}
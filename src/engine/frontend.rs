@@ -0,0 +1,371 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    thread, time,
+};
+
+use super::machine::{Machine, Output, Fault};
+use super::peripheral::{Bus, Display, Input, Timers};
+use super::hook::Hooks;
+use super::register::TimerSideEffect;
+use super::screen::{SCREEN_WIDTH, SCREEN_HEIGHT};
+use super::timer;
+
+/// Provides keys of host which control emulator itself, not CHIP-8 program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HostKey {
+    /// Terminate emulation.
+    Quit,
+    /// Pause or resume emulation.
+    Pause,
+}
+
+/// Provides input event polled from front end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    /// CHIP-8 key is pressed.
+    KeyDown(u8),
+    /// CHIP-8 key is released.
+    KeyUp(u8),
+    /// Host key is pressed.
+    Host(HostKey),
+}
+
+/// Provides rendering, input and audio of emulator.
+/// Emulation loop (`run`) only talks to this trait, so new front end can be added without touching it.
+pub trait Frontend {
+    /// Present visible output of one processed instruction. Changed rows are read from `display`.
+    fn present(&mut self, output: &Output, display: &dyn Display) -> io::Result<()>;
+
+    /// Called at every 60Hz frame boundary, after timers are processed.
+    fn end_frame(&mut self) -> io::Result<()> { Ok(()) }
+
+    /// Poll one pending input event without blocking.
+    fn poll_event(&mut self) -> io::Result<Option<Event>>;
+
+    /// Wait for one input event at most `timeout`. Default implementation sleeps, and then polls.
+    fn wait_event(&mut self, timeout: time::Duration) -> io::Result<Option<Event>> {
+        thread::sleep(timeout);
+        self.poll_event()
+    }
+
+    /// Start (`true`) or stop (`false`) beep.
+    fn set_beep(&mut self, on: bool);
+}
+
+/// Provides how fast emulation loop processes instructions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+    /// Process instructions in given clock, and timers in 60Hz of wall clock.
+    RealTime{ clock_hz: f64 },
+    /// Process given count of instructions per frame without waiting.
+    Fixed{ cycles_per_frame: usize },
+    /// Process given count of instructions per frame, and frames in 60Hz of wall clock.
+    Frames{ cycles_per_frame: usize },
+}
+
+/// Provides the reason why emulation loop ended.
+#[derive(Debug, PartialEq)]
+pub enum Exit {
+    /// Front end requested to quit.
+    Quit,
+    /// Machine could not proceed.
+    Fault(Fault),
+}
+
+/// Run machine on given front end until front end requests to quit or machine faults.
+/// `observe` is called right before every `Machine::step`.
+/// Keys pressed by `Event::KeyDown` are released after each instruction, unless `Event::KeyUp` is
+/// ever polled. It is because terminal can not report key release.
+pub fn run<B, D, I, T, H, F, O>(machine: &mut Machine<B, D, I, T, H>, frontend: &mut F, pacing: Pacing, observe: O) -> io::Result<Exit>
+where
+    B: Bus, D: Display, I: Input, T: Timers, H: Hooks,
+    F: Frontend + ?Sized,
+    O: FnMut(&Machine<B, D, I, T, H>) -> io::Result<()>,
+{
+    run_with(machine, frontend, pacing, observe, |machine, _| machine.step())
+}
+
+/// Same to `run`, but instructions are processed by `step` instead of `Machine::step`.
+/// `step` is given the count of instructions which still fit in the current frame, and may process
+/// several of them at once. Processed count is taken from `Machine::cycles`.
+pub fn run_with<B, D, I, T, H, F, O, S>(machine: &mut Machine<B, D, I, T, H>, frontend: &mut F, pacing: Pacing, mut observe: O, mut step: S) -> io::Result<Exit>
+where
+    B: Bus, D: Display, I: Input, T: Timers, H: Hooks,
+    F: Frontend + ?Sized,
+    O: FnMut(&Machine<B, D, I, T, H>) -> io::Result<()>,
+    S: FnMut(&mut Machine<B, D, I, T, H>, u64) -> Result<Output, Fault>,
+{
+    let (mut clock, mut timer_60hz) = match pacing {
+        Pacing::RealTime{ clock_hz } => (
+            Some(timer::Timer::from_second(1.0 / clock_hz)),
+            Some(timer::Timer::from_second(1.0 / 60.0)),
+        ),
+        Pacing::Fixed{ .. } => (None, None),
+        Pacing::Frames{ .. } => (None, Some(timer::Timer::from_second(1.0 / 60.0))),
+    };
+    let mut frame_cycles = 0;
+    let mut paused = false;
+    let mut key_up_reported = false;
+    let mut beeping = false;
+
+    loop {
+        if let Some(clock) = clock.as_mut() {
+            if !clock.tick() {
+                continue;
+            }
+        }
+
+        // Idle machine can not change until input or the next frame, so block on input instead of spinning.
+        let mut event = match (pacing, timer_60hz.as_ref()) {
+            (Pacing::RealTime{ .. }, Some(timer_60hz)) if !paused && machine.is_idle() => frontend.wait_event(timer_60hz.remaining())?,
+            _ => None,
+        };
+        if event.is_none() {
+            event = frontend.poll_event()?;
+        }
+        while let Some(polled) = event {
+            match polled {
+                Event::KeyDown(key) => machine.press_key(key),
+                Event::KeyUp(key) => {
+                    key_up_reported = true;
+                    machine.release_key(key);
+                },
+                Event::Host(HostKey::Quit) => {
+                    frontend.set_beep(false);
+                    return Ok(Exit::Quit);
+                },
+                Event::Host(HostKey::Pause) => paused = !paused,
+            }
+            event = frontend.poll_event()?;
+        }
+
+        let budget = match pacing {
+            Pacing::RealTime{ .. } => 1,
+            Pacing::Fixed{ cycles_per_frame } | Pacing::Frames{ cycles_per_frame } =>
+                cycles_per_frame.saturating_sub(frame_cycles) as u64,
+        };
+        if !paused && budget > 0 {
+            observe(machine)?;
+            let cycles = machine.cycles();
+            match step(machine, budget) {
+                Ok(output) => frontend.present(&output, machine.display())?,
+                Err(fault) => {
+                    frontend.set_beep(false);
+                    return Ok(Exit::Fault(fault));
+                },
+            }
+            frame_cycles += (machine.cycles() - cycles).max(1) as usize;
+        }
+
+        // Timer is processed independently of instructions, even if machine is waiting for key input.
+        let is_frame_end = match (timer_60hz.as_mut(), pacing) {
+            (Some(timer_60hz), Pacing::Frames{ .. }) => {
+                // Instructions of frame are done, so wait for wall clock.
+                let is_done = paused || budget == 0;
+                if is_done {
+                    thread::sleep(time::Duration::from_micros(100));
+                }
+                is_done && timer_60hz.tick()
+            },
+            (Some(timer_60hz), _) => timer_60hz.tick(),
+            (None, Pacing::Fixed{ cycles_per_frame }) => paused || frame_cycles >= cycles_per_frame,
+            (None, _) => unreachable!(),
+        };
+        if is_frame_end {
+            if !paused {
+                let is_beep = machine.tick_timers() == TimerSideEffect::Beep;
+                if is_beep != beeping {
+                    beeping = is_beep;
+                    frontend.set_beep(beeping);
+                }
+            }
+            frontend.end_frame()?;
+            frame_cycles = 0;
+
+            // Paused loop only polls events, so do not spin.
+            if paused && clock.is_none() {
+                thread::sleep(time::Duration::from_millis(1));
+            }
+        }
+
+        if !key_up_reported {
+            machine.release_all_keys();
+        }
+    }
+}
+
+/// Provides front end which presents nothing.
+/// Input can be scripted per frame, and quit is requested after the frame limit.
+#[derive(Debug, Default)]
+pub struct Headless {
+    frames: u64,
+    frame_limit: Option<u64>,
+    script: VecDeque<(u64, Event)>,
+}
+
+impl Headless {
+    pub fn new() -> Headless {
+        Headless::default()
+    }
+
+    /// Request quit after given count of frames.
+    pub fn with_frame_limit(frame_limit: u64) -> Headless {
+        Headless { frame_limit: Some(frame_limit), ..Headless::default() }
+    }
+
+    /// Report given event at the start of given frame. Events must be scheduled in frame order.
+    pub fn schedule(&mut self, frame: u64, event: Event) {
+        self.script.push_back((frame, event));
+    }
+
+    /// Get count of ended frames.
+    pub fn frames(&self) -> u64 { self.frames }
+}
+
+impl Frontend for Headless {
+    fn present(&mut self, _output: &Output, _display: &dyn Display) -> io::Result<()> { Ok(()) }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn poll_event(&mut self) -> io::Result<Option<Event>> {
+        if self.frame_limit.is_some_and(|limit| self.frames >= limit) {
+            return Ok(Some(Event::Host(HostKey::Quit)));
+        }
+        match self.script.front() {
+            Some(&(frame, event)) if frame <= self.frames => {
+                self.script.pop_front();
+                Ok(Some(event))
+            },
+            _ => Ok(None),
+        }
+    }
+
+    /// Scripted input never arrives while waiting, so it does not sleep.
+    fn wait_event(&mut self, _timeout: time::Duration) -> io::Result<Option<Event>> {
+        self.poll_event()
+    }
+
+    fn set_beep(&mut self, _on: bool) {}
+}
+
+/// Provides one frame recorded by `Recording`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedFrame {
+    /// Index of frame.
+    pub frame: u64,
+    /// Screen pixels at the end of frame, in row-major order.
+    pub pixels: Vec<bool>,
+    /// Whether beep is on at the end of frame.
+    pub beep: bool,
+    /// Events polled in frame.
+    pub events: Vec<Event>,
+}
+
+/// Provides front end which records every frame, event and beep of wrapped front end.
+pub struct Recording<F: Frontend> {
+    inner: F,
+    pixels: Vec<bool>,
+    beep: bool,
+    events: Vec<Event>,
+    frames: Vec<RecordedFrame>,
+}
+
+impl<F: Frontend> Recording<F> {
+    pub fn new(inner: F) -> Recording<F> {
+        Recording {
+            inner,
+            pixels: vec![false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            beep: false,
+            events: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Get recorded frames.
+    pub fn frames(&self) -> &[RecordedFrame] { &self.frames }
+
+    /// Get wrapped front end.
+    pub fn into_inner(self) -> F { self.inner }
+
+    /// Write recorded frames as text.
+    /// Each frame is a header line (`frame 12 beep key-down 5`), followed by screen lines
+    /// of `#` and `.` only if screen was changed from previous frame.
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut previous: Option<&[bool]> = None;
+        for recorded in &self.frames {
+            write!(out, "frame {}", recorded.frame)?;
+            if recorded.beep {
+                write!(out, " beep")?;
+            }
+            for event in &recorded.events {
+                match event {
+                    Event::KeyDown(key) => write!(out, " key-down {:X}", key)?,
+                    Event::KeyUp(key) => write!(out, " key-up {:X}", key)?,
+                    Event::Host(host) => write!(out, " host {:?}", host)?,
+                }
+            }
+            writeln!(out)?;
+
+            if previous != Some(&recorded.pixels[..]) {
+                for row in recorded.pixels.chunks(SCREEN_WIDTH) {
+                    let line: String = row.iter().map(|&drawn| if drawn { '#' } else { '.' }).collect();
+                    writeln!(out, "{}", line)?;
+                }
+                previous = Some(&recorded.pixels);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<F: Frontend> Frontend for Recording<F> {
+    fn present(&mut self, output: &Output, display: &dyn Display) -> io::Result<()> {
+        match output {
+            Output::Cleared => self.pixels.iter_mut().for_each(|pixel| *pixel = false),
+            Output::Drawn(dirty_rows) => {
+                for y in (0..SCREEN_HEIGHT).filter(|y| dirty_rows & (1 << y) != 0) {
+                    for x in 0..SCREEN_WIDTH {
+                        self.pixels[y * SCREEN_WIDTH + x] = display.is_drawn((x as u8, y as u8));
+                    }
+                }
+            },
+            Output::None => (),
+        }
+        self.inner.present(output, display)
+    }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        self.frames.push(RecordedFrame {
+            frame: self.frames.len() as u64,
+            pixels: self.pixels.clone(),
+            beep: self.beep,
+            events: std::mem::take(&mut self.events),
+        });
+        self.inner.end_frame()
+    }
+
+    fn poll_event(&mut self) -> io::Result<Option<Event>> {
+        let event = self.inner.poll_event()?;
+        if let Some(event) = event {
+            self.events.push(event);
+        }
+        Ok(event)
+    }
+
+    fn wait_event(&mut self, timeout: time::Duration) -> io::Result<Option<Event>> {
+        let event = self.inner.wait_event(timeout)?;
+        if let Some(event) = event {
+            self.events.push(event);
+        }
+        Ok(event)
+    }
+
+    fn set_beep(&mut self, on: bool) {
+        self.beep = on;
+        self.inner.set_beep(on);
+    }
+}
@@ -0,0 +1,111 @@
+use super::isa::Instruction;
+use super::register::Registers;
+
+/// Provides callbacks of machine for tooling, such as tracers, achievements, overlays and test assertions.
+/// Every callback does nothing by default, so only needed callbacks have to be implemented.
+///
+/// Hooks are a type parameter of `Machine`, and `()` which is used by default has no callback.
+/// Calls into `()` are removed by compiler, so machine without hooks has no cost.
+pub trait Hooks {
+    /// Whether any callback is implemented. If false, machine skips preparing callback arguments.
+    fn is_active(&self) -> bool { true }
+
+    /// Called right before the instruction of `pc` is processed.
+    fn before_instruction(&mut self, _pc: u16, _instruction: &Instruction, _registers: &Registers) {}
+
+    /// Called right after the instruction of `pc` is processed.
+    fn after_instruction(&mut self, _pc: u16, _instruction: &Instruction, _registers: &Registers) {}
+
+    /// Called when sprite is drawn at `pos`. `collision` is true if any pixel is erased.
+    fn on_draw(&mut self, _pos: (u8, u8), _sprite: &[u8], _collision: bool) {}
+
+    /// Called for each byte written into memory by instruction.
+    fn on_memory_write(&mut self, _addr: u16, _old: u8, _new: u8) {}
+
+    /// Called when machine starts waiting for key press, which will be stored into Vr.
+    fn on_key_wait(&mut self, _r: u8) {}
+
+    /// Called at every 60Hz frame boundary with count of frames processed so far.
+    fn on_frame(&mut self, _frame: u64) {}
+
+    /// Called when beep starts (`true`) or stops (`false`).
+    fn on_beep(&mut self, _on: bool) {}
+}
+
+/// No hooks.
+impl Hooks for () {
+    fn is_active(&self) -> bool { false }
+}
+
+/// Two hooks, called in order.
+impl<A: Hooks, B: Hooks> Hooks for (A, B) {
+    fn is_active(&self) -> bool { self.0.is_active() || self.1.is_active() }
+
+    fn before_instruction(&mut self, pc: u16, instruction: &Instruction, registers: &Registers) {
+        self.0.before_instruction(pc, instruction, registers);
+        self.1.before_instruction(pc, instruction, registers);
+    }
+
+    fn after_instruction(&mut self, pc: u16, instruction: &Instruction, registers: &Registers) {
+        self.0.after_instruction(pc, instruction, registers);
+        self.1.after_instruction(pc, instruction, registers);
+    }
+
+    fn on_draw(&mut self, pos: (u8, u8), sprite: &[u8], collision: bool) {
+        self.0.on_draw(pos, sprite, collision);
+        self.1.on_draw(pos, sprite, collision);
+    }
+
+    fn on_memory_write(&mut self, addr: u16, old: u8, new: u8) {
+        self.0.on_memory_write(addr, old, new);
+        self.1.on_memory_write(addr, old, new);
+    }
+
+    fn on_key_wait(&mut self, r: u8) {
+        self.0.on_key_wait(r);
+        self.1.on_key_wait(r);
+    }
+
+    fn on_frame(&mut self, frame: u64) {
+        self.0.on_frame(frame);
+        self.1.on_frame(frame);
+    }
+
+    fn on_beep(&mut self, on: bool) {
+        self.0.on_beep(on);
+        self.1.on_beep(on);
+    }
+}
+
+/// Hooks registered at runtime, called in order of registration.
+impl Hooks for Vec<Box<dyn Hooks>> {
+    fn is_active(&self) -> bool { self.iter().any(|hooks| hooks.is_active()) }
+
+    fn before_instruction(&mut self, pc: u16, instruction: &Instruction, registers: &Registers) {
+        self.iter_mut().for_each(|hooks| hooks.before_instruction(pc, instruction, registers));
+    }
+
+    fn after_instruction(&mut self, pc: u16, instruction: &Instruction, registers: &Registers) {
+        self.iter_mut().for_each(|hooks| hooks.after_instruction(pc, instruction, registers));
+    }
+
+    fn on_draw(&mut self, pos: (u8, u8), sprite: &[u8], collision: bool) {
+        self.iter_mut().for_each(|hooks| hooks.on_draw(pos, sprite, collision));
+    }
+
+    fn on_memory_write(&mut self, addr: u16, old: u8, new: u8) {
+        self.iter_mut().for_each(|hooks| hooks.on_memory_write(addr, old, new));
+    }
+
+    fn on_key_wait(&mut self, r: u8) {
+        self.iter_mut().for_each(|hooks| hooks.on_key_wait(r));
+    }
+
+    fn on_frame(&mut self, frame: u64) {
+        self.iter_mut().for_each(|hooks| hooks.on_frame(frame));
+    }
+
+    fn on_beep(&mut self, on: bool) {
+        self.iter_mut().for_each(|hooks| hooks.on_beep(on));
+    }
+}
@@ -0,0 +1,78 @@
+use super::isa::Instruction;
+use super::register::{Registers, GENERAL_REGISTERS_CNT};
+
+/// Maximum count of instructions in one iteration of idle loop.
+const MAX_IDLE_LEN: u64 = 16;
+
+/// Check whether instruction may be part of idle loop.
+/// It must not write memory, screen, timers or stack, and must not use random numbers,
+/// so that its result depends only on registers, memory, delay timer and keypad.
+fn is_idle_instruction(instruction: &Instruction) -> bool {
+    type Inst = Instruction;
+    !matches!(instruction,
+        Inst::ClearDisplay | Inst::ReturnSubroutine | Inst::CallSub(_) | Inst::RndAnd{ .. }
+        | Inst::DispSpr{ .. } | Inst::WaitKeyPress{ .. } | Inst::SetDelayFromReg{ .. }
+        | Inst::SetSoundFromReg{ .. } | Inst::MemDumpBcdFromReg{ .. } | Inst::MemDump{ .. })
+}
+
+/// Provides detection of idle loop, such as polling delay timer by `Fx07 / 3x00 / 1nnn`.
+///
+/// Loop is idle if one iteration consists of idle instructions only, and PC, V and I are the same
+/// at the start and the end of the iteration. Until delay timer or keypad changes, every next
+/// iteration is the same, so it can be skipped without changing the machine except cycle count.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct IdleLoop {
+    /// PC, V and I at the start of current iteration.
+    start: Option<(u16, [u8; GENERAL_REGISTERS_CNT], u16)>,
+    /// Count of instructions processed since `start`.
+    len: u64,
+    /// Count of instructions of one iteration, if idle loop is detected.
+    period: Option<u64>,
+}
+
+impl IdleLoop {
+    /// Forget detected loop, because something which loop may depend on is changed.
+    pub(crate) fn reset(&mut self) {
+        *self = IdleLoop::default();
+    }
+
+    /// Get count of instructions of one iteration, if machine is in idle loop.
+    pub(crate) fn period(&self) -> Option<u64> { self.period }
+
+    /// Called before given instruction is processed. Return false if it breaks idle loop.
+    pub(crate) fn before(&mut self, instruction: &Instruction, registers: &Registers) -> bool {
+        if !is_idle_instruction(instruction) {
+            self.reset();
+            return false;
+        }
+        if self.start.is_none() {
+            self.start = Some(Self::snapshot(registers));
+            self.len = 0;
+        }
+        true
+    }
+
+    /// Called after idle instruction is processed.
+    pub(crate) fn after(&mut self, registers: &Registers) {
+        self.len += 1;
+        let (pc, v, l) = match self.start {
+            Some(start) => start,
+            None => return,
+        };
+        if registers.get_pc() == pc {
+            let now = Self::snapshot(registers);
+            self.period = if now == (pc, v, l) { Some(self.len) } else { None };
+            self.start = Some(now);
+            self.len = 0;
+        } else if self.len >= MAX_IDLE_LEN {
+            // Loop is too long, so start again from here.
+            self.start = Some(Self::snapshot(registers));
+            self.len = 0;
+            self.period = None;
+        }
+    }
+
+    fn snapshot(registers: &Registers) -> (u16, [u8; GENERAL_REGISTERS_CNT], u16) {
+        (registers.get_pc(), *registers.general_registers(), registers.get_l())
+    }
+}
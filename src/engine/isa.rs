@@ -1,8 +1,71 @@
-use std::mem;
+use std::{fmt, mem};
 
-#[derive(Debug)]
+use super::quirks::Quirks;
+
+/// Provides how instruction changes control flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Continues to the next instruction.
+    Next,
+    /// Continues to the next instruction, or skips it.
+    Skip,
+    Jump(u16),
+    /// Jumps to address, and returns to the next instruction.
+    Call(u16),
+    Return,
+    /// Target is known only at runtime (0xBnnn).
+    IndirectJump,
+}
+
+/// Provides the first platform on which instruction has its behavior.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Chip8,
+    /// 0x0nnn calls machine code routine of COSMAC VIP, which is ignored here.
+    CosmacVip,
+    /// 0xDxy0 draws 16x16 sprite on SUPER-CHIP, and nothing on CHIP-8.
+    SuperChip,
+}
+
+/// Provides storage which instruction reads or writes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    /// Bit x is set for Vx.
+    pub registers: u16,
+    /// I, which is L of this interpreter.
+    pub index: bool,
+    /// Count of memory bytes from I.
+    pub memory: u8,
+    pub delay_timer: bool,
+    pub sound_timer: bool,
+    pub stack: bool,
+    pub screen: bool,
+    pub keypad: bool,
+}
+
+impl Access {
+    /// Check whether Vx is accessed.
+    pub fn register(&self, r: u8) -> bool {
+        self.registers & (1 << r) != 0
+    }
+
+    fn with(mut self, registers: &[u8]) -> Access {
+        for &r in registers {
+            self.registers |= 1 << r;
+        }
+        self
+    }
+
+    /// Access V0 to Vx.
+    fn with_range(mut self, endr: u8) -> Access {
+        self.registers |= ((1u32 << (endr + 1)) - 1) as u16;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Instruction {
-    Ignore,                         // 0x0nnn SYS addr (IGNORED)
+    Ignore(u16),                    // 0x0nnn SYS addr (IGNORED)
     ClearDisplay,                   // 0x00E0 CLS
     ReturnSubroutine,               // 0x00EE RET
     JmpAddr(u16),                   // 0x1nnn JP Addr Jump to location nnn (program counter).
@@ -39,6 +102,281 @@ pub enum Instruction {
     MemRead{ endr: u8 },            // 0xFx65 LD Vx, [l]. Read value from [l, l+(x-0)] to [V0, Vx].
 }
 
+impl Instruction {
+    /// Get mnemonic without operands, such as `LD` and `DRW`.
+    pub fn mnemonic(&self) -> &'static str {
+        type Inst = Instruction;
+        match self {
+            Inst::Ignore(_) => "SYS",
+            Inst::ClearDisplay => "CLS",
+            Inst::ReturnSubroutine => "RET",
+            Inst::JmpAddr(_) | Inst::JmpAddrOffReg0(_) => "JP",
+            Inst::CallSub(_) => "CALL",
+            Inst::SkipEq{ .. } | Inst::SkipRegEq{ .. } => "SE",
+            Inst::SkipNeq{ .. } | Inst::SkipRegNeq{ .. } => "SNE",
+            Inst::SetByte{ .. } | Inst::SetRegV{ .. } | Inst::SetRegL(_) | Inst::SetDelayToReg{ .. }
+            | Inst::WaitKeyPress{ .. } | Inst::SetDelayFromReg{ .. } | Inst::SetSoundFromReg{ .. }
+            | Inst::SetRegLFontAddrFromReg{ .. } | Inst::MemDumpBcdFromReg{ .. } | Inst::MemDump{ .. }
+            | Inst::MemRead{ .. } => "LD",
+            Inst::AddByte{ .. } | Inst::AddRegV{ .. } | Inst::AddRegL{ .. } => "ADD",
+            Inst::OrRegV{ .. } => "OR",
+            Inst::AndRegV{ .. } => "AND",
+            Inst::XorRegV{ .. } => "XOR",
+            Inst::SubRegV{ .. } => "SUB",
+            Inst::ShrRegV{ .. } => "SHR",
+            Inst::SubNRegV{ .. } => "SUBN",
+            Inst::ShlRegV{ .. } => "SHL",
+            Inst::RndAnd{ .. } => "RND",
+            Inst::DispSpr{ .. } => "DRW",
+            Inst::SkipKeyPressed{ .. } => "SKP",
+            Inst::SkipKeyReleased{ .. } => "SKNP",
+        }
+    }
+
+    /// Get operand-less form, which tells variants of the same mnemonic apart, such as `LD I, addr`.
+    pub fn form(&self) -> &'static str {
+        type Inst = Instruction;
+        match self {
+            Inst::Ignore(_) => "SYS addr",
+            Inst::ClearDisplay => "CLS",
+            Inst::ReturnSubroutine => "RET",
+            Inst::JmpAddr(_) => "JP addr",
+            Inst::CallSub(_) => "CALL addr",
+            Inst::SkipEq{ .. } => "SE Vx, byte",
+            Inst::SkipNeq{ .. } => "SNE Vx, byte",
+            Inst::SkipRegEq{ .. } => "SE Vx, Vy",
+            Inst::SetByte{ .. } => "LD Vx, byte",
+            Inst::AddByte{ .. } => "ADD Vx, byte",
+            Inst::SetRegV{ .. } => "LD Vx, Vy",
+            Inst::OrRegV{ .. } => "OR Vx, Vy",
+            Inst::AndRegV{ .. } => "AND Vx, Vy",
+            Inst::XorRegV{ .. } => "XOR Vx, Vy",
+            Inst::AddRegV{ .. } => "ADD Vx, Vy",
+            Inst::SubRegV{ .. } => "SUB Vx, Vy",
+            Inst::ShrRegV{ .. } => "SHR Vx, Vy",
+            Inst::SubNRegV{ .. } => "SUBN Vx, Vy",
+            Inst::ShlRegV{ .. } => "SHL Vx, Vy",
+            Inst::SkipRegNeq{ .. } => "SNE Vx, Vy",
+            Inst::SetRegL(_) => "LD I, addr",
+            Inst::JmpAddrOffReg0(_) => "JP V0, addr",
+            Inst::RndAnd{ .. } => "RND Vx, byte",
+            Inst::DispSpr{ .. } => "DRW Vx, Vy, nibble",
+            Inst::SkipKeyPressed{ .. } => "SKP Vx",
+            Inst::SkipKeyReleased{ .. } => "SKNP Vx",
+            Inst::SetDelayToReg{ .. } => "LD Vx, DT",
+            Inst::WaitKeyPress{ .. } => "LD Vx, K",
+            Inst::SetDelayFromReg{ .. } => "LD DT, Vx",
+            Inst::SetSoundFromReg{ .. } => "LD ST, Vx",
+            Inst::AddRegL{ .. } => "ADD I, Vx",
+            Inst::SetRegLFontAddrFromReg{ .. } => "LD F, Vx",
+            Inst::MemDumpBcdFromReg{ .. } => "LD B, Vx",
+            Inst::MemDump{ .. } => "LD [I], Vx",
+            Inst::MemRead{ .. } => "LD Vx, [I]",
+        }
+    }
+
+    /// Encode instruction into opcode.
+    /// Encoding decoded instruction gives the same opcode again, and decoding the opcode gives the same
+    /// instruction again. Operands are masked into their fields.
+    pub fn encode(&self) -> [u8; 2] {
+        type Inst = Instruction;
+        let nnn = |op: u16, addr: u16| op << 12 | addr & 0x0FFF;
+        let xkk = |op: u16, r: u8, val: u8| op << 12 | ((r & 0xF) as u16) << 8 | val as u16;
+        let xyn = |op: u16, r: u8, f: u8, n: u8| op << 12 | ((r & 0xF) as u16) << 8 | ((f & 0xF) as u16) << 4 | (n & 0xF) as u16;
+        let word = match *self {
+            Inst::Ignore(addr) => nnn(0x0, addr),
+            Inst::ClearDisplay => 0x00E0,
+            Inst::ReturnSubroutine => 0x00EE,
+            Inst::JmpAddr(addr) => nnn(0x1, addr),
+            Inst::CallSub(addr) => nnn(0x2, addr),
+            Inst::SkipEq{ r, val } => xkk(0x3, r, val),
+            Inst::SkipNeq{ r, val } => xkk(0x4, r, val),
+            Inst::SkipRegEq{ r, f } => xyn(0x5, r, f, 0x0),
+            Inst::SetByte{ r, val } => xkk(0x6, r, val),
+            Inst::AddByte{ r, val } => xkk(0x7, r, val),
+            Inst::SetRegV{ r, f } => xyn(0x8, r, f, 0x0),
+            Inst::OrRegV{ r, f } => xyn(0x8, r, f, 0x1),
+            Inst::AndRegV{ r, f } => xyn(0x8, r, f, 0x2),
+            Inst::XorRegV{ r, f } => xyn(0x8, r, f, 0x3),
+            Inst::AddRegV{ r, f } => xyn(0x8, r, f, 0x4),
+            Inst::SubRegV{ r, f } => xyn(0x8, r, f, 0x5),
+            Inst::ShrRegV{ r, f } => xyn(0x8, r, f, 0x6),
+            Inst::SubNRegV{ r, f } => xyn(0x8, r, f, 0x7),
+            Inst::ShlRegV{ r, f } => xyn(0x8, r, f, 0xE),
+            Inst::SkipRegNeq{ r, f } => xyn(0x9, r, f, 0x0),
+            Inst::SetRegL(addr) => nnn(0xA, addr),
+            Inst::JmpAddrOffReg0(addr) => nnn(0xB, addr),
+            Inst::RndAnd{ r, val } => xkk(0xC, r, val),
+            Inst::DispSpr{ rp, n } => xyn(0xD, rp.0, rp.1, n),
+            Inst::SkipKeyPressed{ r } => xkk(0xE, r, 0x9E),
+            Inst::SkipKeyReleased{ r } => xkk(0xE, r, 0xA1),
+            Inst::SetDelayToReg{ r } => xkk(0xF, r, 0x07),
+            Inst::WaitKeyPress{ r } => xkk(0xF, r, 0x0A),
+            Inst::SetDelayFromReg{ r } => xkk(0xF, r, 0x15),
+            Inst::SetSoundFromReg{ r } => xkk(0xF, r, 0x18),
+            Inst::AddRegL{ r } => xkk(0xF, r, 0x1E),
+            Inst::SetRegLFontAddrFromReg{ r } => xkk(0xF, r, 0x29),
+            Inst::MemDumpBcdFromReg{ r } => xkk(0xF, r, 0x33),
+            Inst::MemDump{ endr } => xkk(0xF, endr, 0x55),
+            Inst::MemRead{ endr } => xkk(0xF, endr, 0x65),
+        };
+        word.to_be_bytes()
+    }
+
+    /// Get how instruction changes control flow.
+    pub fn flow(&self) -> Flow {
+        type Inst = Instruction;
+        match *self {
+            Inst::JmpAddr(addr) => Flow::Jump(addr),
+            Inst::CallSub(addr) => Flow::Call(addr),
+            Inst::ReturnSubroutine => Flow::Return,
+            Inst::JmpAddrOffReg0(_) => Flow::IndirectJump,
+            Inst::SkipEq{ .. } | Inst::SkipNeq{ .. } | Inst::SkipRegEq{ .. } | Inst::SkipRegNeq{ .. }
+            | Inst::SkipKeyPressed{ .. } | Inst::SkipKeyReleased{ .. } => Flow::Skip,
+            _ => Flow::Next,
+        }
+    }
+
+    /// Get the first platform on which instruction has its behavior.
+    pub fn platform(&self) -> Platform {
+        match *self {
+            Instruction::Ignore(_) => Platform::CosmacVip,
+            Instruction::DispSpr{ n: 0, .. } => Platform::SuperChip,
+            _ => Platform::Chip8,
+        }
+    }
+
+    /// Get how behavior of instruction differs between platforms.
+    pub fn caveats(&self) -> Vec<&'static str> {
+        type Inst = Instruction;
+        match *self {
+            Inst::Ignore(_) => vec!["calls machine code routine on COSMAC VIP, and is ignored here"],
+            Inst::OrRegV{ .. } | Inst::AndRegV{ .. } | Inst::XorRegV{ .. } => vec!["resets VF to 0 on COSMAC VIP"],
+            Inst::ShrRegV{ r, f } | Inst::ShlRegV{ r, f } if r != f => {
+                vec!["shifts Vy into Vx on COSMAC VIP, and Vx in place on CHIP-48 and SUPER-CHIP"]
+            },
+            Inst::JmpAddrOffReg0(_) => vec!["jumps to xnn + Vx instead of nnn + V0 on CHIP-48 and SUPER-CHIP"],
+            Inst::DispSpr{ n, .. } => {
+                let mut caveats = vec![
+                    "clips sprites at the screen edges on most platforms, instead of wrapping around",
+                    "waits for the next frame on COSMAC VIP",
+                ];
+                if n == 0 {
+                    caveats.push("draws 16x16 sprite on SUPER-CHIP, and nothing on CHIP-8");
+                }
+                caveats
+            },
+            Inst::MemDump{ .. } | Inst::MemRead{ .. } => vec!["advances I past the last register except on SUPER-CHIP"],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get storage which instruction reads under given quirks.
+    pub fn reads(&self, quirks: &Quirks) -> Access {
+        self.access(quirks).0
+    }
+
+    /// Get storage which instruction writes under given quirks.
+    pub fn writes(&self, quirks: &Quirks) -> Access {
+        self.access(quirks).1
+    }
+
+    fn access(&self, quirks: &Quirks) -> (Access, Access) {
+        type Inst = Instruction;
+        const VF: u8 = 0xF;
+        let none = Access::default();
+        match *self {
+            Inst::Ignore(_) | Inst::JmpAddr(_) => (none, none),
+            Inst::ClearDisplay => (none, Access { screen: true, ..none }),
+            Inst::ReturnSubroutine => (Access { stack: true, ..none }, none),
+            Inst::CallSub(_) => (none, Access { stack: true, ..none }),
+            Inst::SkipEq{ r, .. } | Inst::SkipNeq{ r, .. } => (none.with(&[r]), none),
+            Inst::SkipRegEq{ r, f } | Inst::SkipRegNeq{ r, f } => (none.with(&[r, f]), none),
+            Inst::SetByte{ r, .. } | Inst::RndAnd{ r, .. } => (none, none.with(&[r])),
+            Inst::AddByte{ r, .. } => (none.with(&[r]), none.with(&[r])),
+            Inst::SetRegV{ r, f } => (none.with(&[f]), none.with(&[r])),
+            Inst::OrRegV{ r, f } | Inst::AndRegV{ r, f } | Inst::XorRegV{ r, f } => {
+                let writes = if quirks.vf_reset { none.with(&[r, VF]) } else { none.with(&[r]) };
+                (none.with(&[r, f]), writes)
+            },
+            Inst::AddRegV{ r, f } | Inst::SubRegV{ r, f } | Inst::SubNRegV{ r, f } => (none.with(&[r, f]), none.with(&[r, VF])),
+            Inst::ShrRegV{ r, f } | Inst::ShlRegV{ r, f } => {
+                let src = if quirks.shift_vy { f } else { r };
+                (none.with(&[src]), none.with(&[r, VF]))
+            },
+            Inst::SetRegL(_) => (none, Access { index: true, ..none }),
+            Inst::JmpAddrOffReg0(addr) => {
+                let r = if quirks.jump_vx { (addr >> 8) as u8 } else { 0 };
+                (none.with(&[r]), none)
+            },
+            Inst::DispSpr{ rp, n } => (
+                Access { index: true, memory: n, screen: true, ..none }.with(&[rp.0, rp.1]),
+                Access { screen: true, ..none }.with(&[VF]),
+            ),
+            Inst::SkipKeyPressed{ r } | Inst::SkipKeyReleased{ r } => (Access { keypad: true, ..none }.with(&[r]), none),
+            Inst::SetDelayToReg{ r } => (Access { delay_timer: true, ..none }, none.with(&[r])),
+            Inst::WaitKeyPress{ r } => (Access { keypad: true, ..none }, none.with(&[r])),
+            Inst::SetDelayFromReg{ r } => (none.with(&[r]), Access { delay_timer: true, ..none }),
+            Inst::SetSoundFromReg{ r } => (none.with(&[r]), Access { sound_timer: true, ..none }),
+            Inst::AddRegL{ r } => (Access { index: true, ..none }.with(&[r]), Access { index: true, ..none }),
+            Inst::SetRegLFontAddrFromReg{ r } => (none.with(&[r]), Access { index: true, ..none }),
+            Inst::MemDumpBcdFromReg{ r } => (Access { index: true, ..none }.with(&[r]), Access { memory: 3, ..none }),
+            Inst::MemDump{ endr } => (
+                Access { index: true, ..none }.with_range(endr),
+                Access { index: quirks.memory_increment, memory: endr + 1, ..none },
+            ),
+            Inst::MemRead{ endr } => (
+                Access { index: true, memory: endr + 1, ..none },
+                Access { index: quirks.memory_increment, ..none }.with_range(endr),
+            ),
+        }
+    }
+}
+
+/// Write canonical mnemonic such as `LD V1, 0x05` and `DRW V0, V1, 5`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        type Inst = Instruction;
+        match *self {
+            Inst::Ignore(addr) => write!(f, "SYS 0x{:03X}", addr),
+            Inst::ClearDisplay => write!(f, "CLS"),
+            Inst::ReturnSubroutine => write!(f, "RET"),
+            Inst::JmpAddr(addr) => write!(f, "JP 0x{:03X}", addr),
+            Inst::CallSub(addr) => write!(f, "CALL 0x{:03X}", addr),
+            Inst::SkipEq{ r, val } => write!(f, "SE V{:X}, 0x{:02X}", r, val),
+            Inst::SkipNeq{ r, val } => write!(f, "SNE V{:X}, 0x{:02X}", r, val),
+            Inst::SkipRegEq{ r, f: y } => write!(f, "SE V{:X}, V{:X}", r, y),
+            Inst::SetByte{ r, val } => write!(f, "LD V{:X}, 0x{:02X}", r, val),
+            Inst::AddByte{ r, val } => write!(f, "ADD V{:X}, 0x{:02X}", r, val),
+            Inst::SetRegV{ r, f: y } => write!(f, "LD V{:X}, V{:X}", r, y),
+            Inst::OrRegV{ r, f: y } => write!(f, "OR V{:X}, V{:X}", r, y),
+            Inst::AndRegV{ r, f: y } => write!(f, "AND V{:X}, V{:X}", r, y),
+            Inst::XorRegV{ r, f: y } => write!(f, "XOR V{:X}, V{:X}", r, y),
+            Inst::AddRegV{ r, f: y } => write!(f, "ADD V{:X}, V{:X}", r, y),
+            Inst::SubRegV{ r, f: y } => write!(f, "SUB V{:X}, V{:X}", r, y),
+            Inst::ShrRegV{ r, f: y } => write!(f, "SHR V{:X}, V{:X}", r, y),
+            Inst::SubNRegV{ r, f: y } => write!(f, "SUBN V{:X}, V{:X}", r, y),
+            Inst::ShlRegV{ r, f: y } => write!(f, "SHL V{:X}, V{:X}", r, y),
+            Inst::SkipRegNeq{ r, f: y } => write!(f, "SNE V{:X}, V{:X}", r, y),
+            Inst::SetRegL(addr) => write!(f, "LD I, 0x{:03X}", addr),
+            Inst::JmpAddrOffReg0(addr) => write!(f, "JP V0, 0x{:03X}", addr),
+            Inst::RndAnd{ r, val } => write!(f, "RND V{:X}, 0x{:02X}", r, val),
+            Inst::DispSpr{ rp, n } => write!(f, "DRW V{:X}, V{:X}, {}", rp.0, rp.1, n),
+            Inst::SkipKeyPressed{ r } => write!(f, "SKP V{:X}", r),
+            Inst::SkipKeyReleased{ r } => write!(f, "SKNP V{:X}", r),
+            Inst::SetDelayToReg{ r } => write!(f, "LD V{:X}, DT", r),
+            Inst::WaitKeyPress{ r } => write!(f, "LD V{:X}, K", r),
+            Inst::SetDelayFromReg{ r } => write!(f, "LD DT, V{:X}", r),
+            Inst::SetSoundFromReg{ r } => write!(f, "LD ST, V{:X}", r),
+            Inst::AddRegL{ r } => write!(f, "ADD I, V{:X}", r),
+            Inst::SetRegLFontAddrFromReg{ r } => write!(f, "LD F, V{:X}", r),
+            Inst::MemDumpBcdFromReg{ r } => write!(f, "LD B, V{:X}", r),
+            Inst::MemDump{ endr } => write!(f, "LD [I], V{:X}", endr),
+            Inst::MemRead{ endr } => write!(f, "LD V{:X}, [I]", endr),
+        }
+    }
+}
+
 fn get_12bit_from(bytes: &[u8; 2]) -> u16 {
     (((bytes[0] & 0x0F) as u16) << 8) + bytes[1] as u16
 }
@@ -53,14 +391,14 @@ pub fn parse_instruction(bytes: &[u8; 2]) -> Option<Instruction> {
             match r {
                 0 if bytes[1] == 0xE0 => Some(Instruction::ClearDisplay),       // 0x00E0
                 0 if bytes[1] == 0xEE => Some(Instruction::ReturnSubroutine),   // 0x00EE
-                _ => Some(Instruction::Ignore),
+                _ => Some(Instruction::Ignore(get_12bit_from(bytes))),
             }
         },
         0x1 => Some(Instruction::JmpAddr(get_12bit_from(bytes))),               // 0x1NNN
         0x2 => Some(Instruction::CallSub(get_12bit_from(bytes))),               // 0x2NNN
         0x3 => Some(Instruction::SkipEq{ r, val }),                             // 0x3XNN
         0x4 => Some(Instruction::SkipNeq{ r, val }),                            // 0x4XNN
-        0x5 => Some(Instruction::SkipRegEq{ r, f: bytes[1] >> 4 }),             // 0x5XY0
+        0x5 if val & 0x0F == 0 => Some(Instruction::SkipRegEq{ r, f: val >> 4 }),  // 0x5XY0
         0x6 => Some(Instruction::SetByte{ r, val: bytes[1] }),                  // 0x6XNN
         0x7 => Some(Instruction::AddByte{ r, val: bytes[1] }),                  // 0x7XNN
         0x8 => {
@@ -78,7 +416,7 @@ pub fn parse_instruction(bytes: &[u8; 2]) -> Option<Instruction> {
                 _ => None, 
             }
         },
-        0x9 => Some(Instruction::SkipRegNeq{ r, f: bytes[1] >> 4 }),            // 0x9XY0
+        0x9 if val & 0x0F == 0 => Some(Instruction::SkipRegNeq{ r, f: val >> 4 }), // 0x9XY0
         0xA => Some(Instruction::SetRegL(get_12bit_from(bytes))),               // 0xANNN
         0xB => Some(Instruction::JmpAddrOffReg0(get_12bit_from(bytes))),        // 0xBNNN
         0xC => Some(Instruction::RndAnd{ r, val }),                             // 0xCXNN
@@ -112,6 +450,82 @@ pub fn parse_instruction(bytes: &[u8; 2]) -> Option<Instruction> {
     }
 }
 
+/// Parse canonical mnemonic such as `LD V1, 0x05`, which `Display` writes.
+/// Numbers are decimal or `0x` prefixed hexadecimal. Mnemonics and registers are case insensitive.
+pub fn parse_mnemonic(text: &str) -> Result<Instruction, String> {
+    type Inst = Instruction;
+    let text = text.trim();
+    let (name, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let operands: Vec<String> = match rest.trim() {
+        "" => Vec::new(),
+        rest => rest.split(',').map(|operand| operand.trim().to_uppercase()).collect(),
+    };
+    let operands: Vec<&str> = operands.iter().map(String::as_str).collect();
+
+    let reg = |operand: &str| {
+        operand.strip_prefix('V')
+            .filter(|digit| digit.len() == 1)
+            .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+    };
+    let num = |operand: &str, max: u16| {
+        match operand.strip_prefix("0X") {
+            Some(hex) => u16::from_str_radix(hex, 16).ok(),
+            None => operand.parse::<u16>().ok(),
+        }.filter(|&value| value <= max)
+    };
+    let addr = |operand: &str| num(operand, 0xFFF);
+    let byte = |operand: &str| num(operand, 0xFF).map(|value| value as u8);
+    // Second operand is either register or byte.
+    let reg_or_byte = |x: &str, y: &str, with_reg: fn(u8, u8) -> Inst, with_byte: fn(u8, u8) -> Inst| {
+        match (reg(x), reg(y), byte(y)) {
+            (Some(r), Some(f), _) => Some(with_reg(r, f)),
+            (Some(r), None, Some(val)) => Some(with_byte(r, val)),
+            _ => None,
+        }
+    };
+    let regs = |x: &str, y: &str, with_regs: fn(u8, u8) -> Inst| Some(with_regs(reg(x)?, reg(y)?));
+
+    let instruction = match (name.to_uppercase().as_str(), operands.as_slice()) {
+        ("SYS", [target]) => addr(target).map(Inst::Ignore),
+        ("CLS", []) => Some(Inst::ClearDisplay),
+        ("RET", []) => Some(Inst::ReturnSubroutine),
+        ("JP", ["V0", target]) => addr(target).map(Inst::JmpAddrOffReg0),
+        ("JP", [target]) => addr(target).map(Inst::JmpAddr),
+        ("CALL", [target]) => addr(target).map(Inst::CallSub),
+        ("SE", [x, y]) => reg_or_byte(x, y, |r, f| Inst::SkipRegEq{ r, f }, |r, val| Inst::SkipEq{ r, val }),
+        ("SNE", [x, y]) => reg_or_byte(x, y, |r, f| Inst::SkipRegNeq{ r, f }, |r, val| Inst::SkipNeq{ r, val }),
+        ("LD", ["I", target]) => addr(target).map(Inst::SetRegL),
+        ("LD", ["DT", x]) => reg(x).map(|r| Inst::SetDelayFromReg{ r }),
+        ("LD", ["ST", x]) => reg(x).map(|r| Inst::SetSoundFromReg{ r }),
+        ("LD", ["F", x]) => reg(x).map(|r| Inst::SetRegLFontAddrFromReg{ r }),
+        ("LD", ["B", x]) => reg(x).map(|r| Inst::MemDumpBcdFromReg{ r }),
+        ("LD", ["[I]", x]) => reg(x).map(|endr| Inst::MemDump{ endr }),
+        ("LD", [x, "DT"]) => reg(x).map(|r| Inst::SetDelayToReg{ r }),
+        ("LD", [x, "K"]) => reg(x).map(|r| Inst::WaitKeyPress{ r }),
+        ("LD", [x, "[I]"]) => reg(x).map(|endr| Inst::MemRead{ endr }),
+        ("LD", [x, y]) => reg_or_byte(x, y, |r, f| Inst::SetRegV{ r, f }, |r, val| Inst::SetByte{ r, val }),
+        ("ADD", ["I", x]) => reg(x).map(|r| Inst::AddRegL{ r }),
+        ("ADD", [x, y]) => reg_or_byte(x, y, |r, f| Inst::AddRegV{ r, f }, |r, val| Inst::AddByte{ r, val }),
+        ("OR", [x, y]) => regs(x, y, |r, f| Inst::OrRegV{ r, f }),
+        ("AND", [x, y]) => regs(x, y, |r, f| Inst::AndRegV{ r, f }),
+        ("XOR", [x, y]) => regs(x, y, |r, f| Inst::XorRegV{ r, f }),
+        ("SUB", [x, y]) => regs(x, y, |r, f| Inst::SubRegV{ r, f }),
+        ("SHR", [x, y]) => regs(x, y, |r, f| Inst::ShrRegV{ r, f }),
+        ("SUBN", [x, y]) => regs(x, y, |r, f| Inst::SubNRegV{ r, f }),
+        ("SHL", [x, y]) => regs(x, y, |r, f| Inst::ShlRegV{ r, f }),
+        ("RND", [x, k]) => reg(x).zip(byte(k)).map(|(r, val)| Inst::RndAnd{ r, val }),
+        ("DRW", [x, y, n]) => match (reg(x), reg(y), num(n, 0xF)) {
+            (Some(rx), Some(ry), Some(n)) => Some(Inst::DispSpr{ rp: (rx, ry), n: n as u8 }),
+            _ => None,
+        },
+        ("SKP", [x]) => reg(x).map(|r| Inst::SkipKeyPressed{ r }),
+        ("SKNP", [x]) => reg(x).map(|r| Inst::SkipKeyReleased{ r }),
+        _ => None,
+    };
+    instruction.ok_or_else(|| format!("invalid instruction {}", text))
+}
+
+#[allow(clippy::unnecessary_cast)]
 pub fn to_bitfield_string(bytes: &[u8; 2], true_char: char, false_char: char) -> String {
     static LEN: usize = mem::size_of::<u8>() * 8 * 2;
 
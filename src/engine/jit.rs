@@ -0,0 +1,477 @@
+use std::{io, ptr};
+
+extern crate libc;
+
+use super::isa::Instruction;
+use super::machine::{Machine, Fault};
+use super::memory::{Memory, MEMORY_SIZE};
+use super::peripheral::{Bus, Display, Input, Timers};
+use super::hook::Hooks;
+use super::quirks::Quirks;
+use super::register::TimerSideEffect;
+use super::screen::Screen;
+use super::state::MachineState;
+
+/// Maximum count of instructions translated into one block.
+const MAX_BLOCK_LEN: usize = 64;
+
+/// Registers which native code reads and writes. Emitted code depends on the field offsets.
+#[repr(C)]
+struct NativeState {
+    v: [u8; 16],
+    l: u16,
+    pc: u16,
+}
+
+const OFFSET_VF: u8 = 15;
+const OFFSET_L: u8 = 16;
+const OFFSET_PC: u8 = 18;
+
+/// Provides executable memory holding one translated block.
+struct CodeBuffer {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl CodeBuffer {
+    /// Copy given machine code into new executable pages.
+    fn new(code: &[u8]) -> io::Result<CodeBuffer> {
+        let len = code.len().max(1);
+        unsafe {
+            let ptr = libc::mmap(
+                ptr::null_mut(), len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0);
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            ptr::copy_nonoverlapping(code.as_ptr(), ptr as *mut u8, code.len());
+
+            // Pages are never writable and executable at once.
+            if libc::mprotect(ptr, len, libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                let err = io::Error::last_os_error();
+                libc::munmap(ptr, len);
+                return Err(err);
+            }
+            Ok(CodeBuffer { ptr, len })
+        }
+    }
+
+    fn call(&self, state: &mut NativeState) {
+        unsafe {
+            let function: extern "sysv64" fn(*mut NativeState) = std::mem::transmute(self.ptr);
+            function(state);
+        }
+    }
+}
+
+impl Drop for CodeBuffer {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr, self.len); }
+    }
+}
+
+/// Provides x86-64 machine code emitter. State pointer is given in `rdi`.
+#[derive(Default)]
+struct Assembler {
+    code: Vec<u8>,
+}
+
+/// x86-64 8bit register numbers.
+const AL: u8 = 0;
+const CL: u8 = 1;
+
+impl Assembler {
+    /// Emit opcode with ModRM operand `[rdi + disp]`.
+    fn mem(&mut self, opcode: &[u8], reg: u8, disp: u8) {
+        self.code.extend_from_slice(opcode);
+        self.code.extend_from_slice(&[0x40 | (reg << 3) | 0x07, disp]);
+    }
+
+    fn load(&mut self, reg: u8, r: u8) { self.mem(&[0x8A], reg, r); }               // mov reg, [v + r]
+
+    fn store(&mut self, r: u8, reg: u8) { self.mem(&[0x88], reg, r); }              // mov [v + r], reg
+
+    fn store_imm(&mut self, r: u8, val: u8) {                                       // mov byte [v + r], imm8
+        self.mem(&[0xC6], 0, r);
+        self.code.push(val);
+    }
+
+    fn set_pc(&mut self, pc: u16) {                                                 // mov word [pc], imm16
+        self.mem(&[0x66, 0xC7], 0, OFFSET_PC);
+        self.code.extend_from_slice(&pc.to_le_bytes());
+    }
+
+    fn ret(&mut self) { self.code.push(0xC3); }
+
+    /// Emit `Vx = Vx op Vy` and VF = flag in `cl`, in this order.
+    fn arithmetic(&mut self, opcode: u8, flag: u8, x: u8, y: u8) {
+        self.load(AL, x);
+        self.mem(&[opcode], AL, y);                                                 // add / sub al, [v + y]
+        self.code.extend_from_slice(&[0x0F, flag, 0xC1]);                           // setc / setnc cl
+        self.store(x, AL);
+        self.store(OFFSET_VF, CL);
+    }
+
+    /// Emit skip of next instruction. `compare` sets flags, and `skip_if_equal` selects condition.
+    fn skip(&mut self, pc: u16, skip_if_equal: bool, compare: impl FnOnce(&mut Assembler)) {
+        self.set_pc(pc + 2);
+        compare(self);
+        self.code.extend_from_slice(&[if skip_if_equal { 0x75 } else { 0x74 }, 6]);  // jne / je over next mov
+        self.set_pc(pc + 4);
+        self.ret();
+    }
+
+    /// Emit instruction which does not change control flow. Return false if it can not be translated.
+    fn straight(&mut self, instruction: Instruction, quirks: &Quirks) -> bool {
+        type Inst = Instruction;
+        match instruction {
+            Inst::Ignore(_) => (),
+            Inst::SetByte{ r, val } => self.store_imm(r, val),
+            Inst::AddByte{ r, val } => {                                            // add byte [v + r], imm8
+                self.mem(&[0x80], 0, r);
+                self.code.push(val);
+            },
+            Inst::SetRegV{ r, f } => {
+                self.load(AL, f);
+                self.store(r, AL);
+            },
+            Inst::OrRegV{ r, f } | Inst::AndRegV{ r, f } | Inst::XorRegV{ r, f } => {
+                let opcode = match instruction {
+                    Inst::OrRegV{ .. } => 0x08,
+                    Inst::AndRegV{ .. } => 0x20,
+                    _ => 0x30,
+                };
+                self.load(AL, f);
+                self.mem(&[opcode], AL, r);                                         // op [v + r], al
+                if quirks.vf_reset { self.store_imm(OFFSET_VF, 0); }
+            },
+            Inst::AddRegV{ r, f } => self.arithmetic(0x02, 0x92, r, f),
+            Inst::SubRegV{ r, f } => self.arithmetic(0x2A, 0x93, r, f),
+            Inst::SubNRegV{ r, f } => {
+                self.load(AL, f);
+                self.mem(&[0x2A], AL, r);                                           // sub al, [v + r]
+                self.code.extend_from_slice(&[0x0F, 0x93, 0xC1]);                   // setnc cl
+                self.store(r, AL);
+                self.store(OFFSET_VF, CL);
+            },
+            Inst::ShrRegV{ r, f } | Inst::ShlRegV{ r, f } => {
+                self.load(AL, if quirks.shift_vy { f } else { r });
+                self.code.extend_from_slice(&[0x88, 0xC1]);                         // mov cl, al
+                if let Inst::ShrRegV{ .. } = instruction {
+                    self.code.extend_from_slice(&[0x80, 0xE1, 0x01]);               // and cl, 1
+                    self.code.extend_from_slice(&[0xD0, 0xE8]);                     // shr al, 1
+                } else {
+                    self.code.extend_from_slice(&[0xC0, 0xE9, 0x07]);               // shr cl, 7
+                    self.code.extend_from_slice(&[0xD0, 0xE0]);                     // shl al, 1
+                }
+                // Flag is stored first, so result wins when Vx is VF.
+                self.store(OFFSET_VF, CL);
+                self.store(r, AL);
+            },
+            Inst::SetRegL(addr) => {                                                // mov word [l], imm16
+                self.mem(&[0x66, 0xC7], 0, OFFSET_L);
+                self.code.extend_from_slice(&addr.to_le_bytes());
+            },
+            Inst::AddRegL{ r } => {
+                self.mem(&[0x0F, 0xB6], AL, r);                                     // movzx eax, byte [v + r]
+                self.mem(&[0x66, 0x01], AL, OFFSET_L);                              // add [l], ax
+            },
+            Inst::SetRegLFontAddrFromReg{ r } => {
+                self.mem(&[0x0F, 0xB6], AL, r);                                     // movzx eax, byte [v + r]
+                self.code.extend_from_slice(&[0x8D, 0x04, 0x80]);                   // lea eax, [rax + rax * 4]
+                self.mem(&[0x66, 0x89], AL, OFFSET_L);                              // mov [l], ax
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// Emit instruction which ends block. Return false if it can not be translated.
+    fn terminator(&mut self, pc: u16, instruction: Instruction, quirks: &Quirks) -> bool {
+        type Inst = Instruction;
+        match instruction {
+            Inst::JmpAddr(addr) => {
+                self.set_pc(addr);
+                self.ret();
+            },
+            Inst::JmpAddrOffReg0(addr) => {
+                let r = if quirks.jump_vx { (addr >> 8) as u8 } else { 0 };
+                self.mem(&[0x0F, 0xB6], AL, r);                                     // movzx eax, byte [v + r]
+                self.code.extend_from_slice(&[0x66, 0x05]);                         // add ax, imm16
+                self.code.extend_from_slice(&addr.to_le_bytes());
+                self.mem(&[0x66, 0x89], AL, OFFSET_PC);                             // mov [pc], ax
+                self.ret();
+            },
+            Inst::SkipEq{ r, val } | Inst::SkipNeq{ r, val } => {
+                let skip_if_equal = matches!(instruction, Inst::SkipEq{ .. });
+                self.skip(pc, skip_if_equal, |asm| {
+                    asm.mem(&[0x80], 7, r);                                         // cmp byte [v + r], imm8
+                    asm.code.push(val);
+                });
+            },
+            Inst::SkipRegEq{ r, f } | Inst::SkipRegNeq{ r, f } => {
+                let skip_if_equal = matches!(instruction, Inst::SkipRegEq{ .. });
+                self.skip(pc, skip_if_equal, |asm| {
+                    asm.load(AL, r);
+                    asm.mem(&[0x3A], AL, f);                                        // cmp al, [v + f]
+                });
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Provides one translated basic block.
+struct Block {
+    /// Byte range [start, end) of instructions translated into block.
+    start: usize,
+    end: usize,
+    /// Count of instructions processed by one execution.
+    cycles: u64,
+    code: CodeBuffer,
+}
+
+/// Provides translation state of one address.
+enum Slot {
+    /// Not translated yet, or invalidated.
+    Unknown,
+    /// The first instruction is processed by interpreter.
+    Interpreted,
+    Translated(Block),
+}
+
+/// Translate basic block starting from `pc`. Return None if the first instruction can not be translated.
+fn compile<B: Bus>(bus: &B, pc: u16, quirks: &Quirks) -> io::Result<Option<Block>> {
+    let mut asm = Assembler::default();
+    let mut addr = pc;
+    let mut cycles = 0;
+
+    loop {
+        let instruction = bus.fetch(addr);
+        match instruction {
+            Some(instruction) if cycles < MAX_BLOCK_LEN && asm.straight(instruction, quirks) => {
+                cycles += 1;
+                addr += 2;
+            },
+            Some(instruction) if cycles < MAX_BLOCK_LEN && asm.terminator(addr, instruction, quirks) => {
+                cycles += 1;
+                addr += 2;
+                break;
+            },
+            // Instruction which can not be translated is processed by interpreter.
+            _ => {
+                asm.set_pc(addr);
+                asm.ret();
+                break;
+            },
+        }
+    }
+
+    if cycles == 0 {
+        return Ok(None);
+    }
+    Ok(Some(Block {
+        start: pc as usize,
+        end: addr as usize,
+        cycles: cycles as u64,
+        code: CodeBuffer::new(&asm.code)?,
+    }))
+}
+
+/// Provides native x86-64 code backend of machine.
+/// Basic blocks of register-only instructions are translated into native code, and other instructions
+/// (draw, key, timer, memory, stack and random) are processed by interpreter of the machine.
+///
+/// Blocks are invalidated when the interpreter writes over them by 0xFx33 and 0xFx55.
+/// If memory is written from outside of machine, `invalidate_all` must be called.
+/// Machine with active hooks is always interpreted, so that every callback is called.
+pub struct Jit {
+    /// Translation state of each address.
+    blocks: Vec<Slot>,
+    /// Count of blocks covering each byte.
+    covered: Vec<u16>,
+    quirks: Option<Quirks>,
+}
+
+impl Default for Jit {
+    fn default() -> Self {
+        Jit::new()
+    }
+}
+
+impl Jit {
+    pub fn new() -> Jit {
+        Jit {
+            blocks: (0..MEMORY_SIZE).map(|_| Slot::Unknown).collect(),
+            covered: vec![0; MEMORY_SIZE],
+            quirks: None,
+        }
+    }
+
+    /// Get count of translated blocks.
+    pub fn block_count(&self) -> usize {
+        self.blocks.iter().filter(|slot| matches!(slot, Slot::Translated(_))).count()
+    }
+
+    /// Remove every translated block.
+    pub fn invalidate_all(&mut self) {
+        self.blocks.iter_mut().for_each(|slot| *slot = Slot::Unknown);
+        self.covered.iter_mut().for_each(|count| *count = 0);
+    }
+
+    /// Remove blocks overlapping with memory range [from, from + count).
+    pub fn invalidate(&mut self, from: usize, count: usize) {
+        let end = (from + count).min(MEMORY_SIZE);
+        let from = from.min(end);
+        // Address whose instruction overlaps with the range may become translatable.
+        for slot in &mut self.blocks[from.saturating_sub(1)..end] {
+            if let Slot::Interpreted = slot {
+                *slot = Slot::Unknown;
+            }
+        }
+        if self.covered[from..end].iter().all(|&count| count == 0) {
+            return;
+        }
+
+        for slot in &mut self.blocks {
+            match slot {
+                Slot::Translated(block) if block.start < end && from < block.end => {
+                    self.covered[block.start..block.end].iter_mut().for_each(|count| *count -= 1);
+                    *slot = Slot::Unknown;
+                },
+                _ => (),
+            }
+        }
+    }
+
+    /// Get translation of given address, translating it if not yet.
+    fn slot<B: Bus>(&mut self, bus: &B, pc: u16, quirks: &Quirks) -> &Slot {
+        let index = (pc as usize).min(MEMORY_SIZE - 1);
+        if let Slot::Unknown = self.blocks[index] {
+            // Failure of executable memory allocation only disables translation of this address.
+            self.blocks[index] = match compile(bus, pc, quirks) {
+                Ok(Some(block)) => {
+                    self.covered[block.start..block.end].iter_mut().for_each(|count| *count += 1);
+                    Slot::Translated(block)
+                },
+                _ => Slot::Interpreted,
+            };
+        }
+        &self.blocks[index]
+    }
+
+    /// Process translated blocks in a row as long as they fit in `budget` instructions,
+    /// or one instruction by interpreter. Return count of processed cycles, which is the same to count of `Machine::step`.
+    pub fn step<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&mut self, machine: &mut Machine<B, D, I, T, H>, budget: u64) -> Result<u64, Fault> {
+        if *machine.state() != MachineState::Normal || machine.hooks().is_active() {
+            machine.step()?;
+            return Ok(1);
+        }
+        if self.quirks != Some(*machine.quirks()) {
+            self.invalidate_all();
+            self.quirks = Some(*machine.quirks());
+        }
+
+        // Run translated blocks in a row, while they fit in budget.
+        let registers = machine.registers();
+        let mut state = NativeState {
+            v: *registers.general_registers(),
+            l: registers.get_l(),
+            pc: registers.get_pc(),
+        };
+        let quirks = *machine.quirks();
+        let mut cycles = 0;
+        while let Slot::Translated(block) = self.slot(machine.bus(), state.pc, &quirks) {
+            if cycles + block.cycles > budget {
+                break;
+            }
+            block.code.call(&mut state);
+            cycles += block.cycles;
+        }
+        if cycles > 0 {
+            machine.native_registers(cycles).load(state.v, state.l, state.pc);
+            return Ok(cycles);
+        }
+
+        // Otherwise, process one instruction by interpreter.
+        let l = machine.registers().get_l() as usize;
+        match machine.bus().fetch(state.pc) {
+            Some(Instruction::MemDump{ endr }) => self.invalidate(l, endr as usize + 1),
+            Some(Instruction::MemDumpBcdFromReg{ .. }) => self.invalidate(l, 3),
+            _ => (),
+        }
+        machine.step()?;
+        Ok(1)
+    }
+
+    /// Process one 60Hz frame which consists of `cycles` instructions and timer update,
+    /// same to `Machine::run_frame`.
+    pub fn run_frame<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&mut self, machine: &mut Machine<B, D, I, T, H>, cycles: usize) -> Result<TimerSideEffect, Fault> {
+        let mut remaining = cycles as u64;
+        while remaining > 0 {
+            remaining -= self.step(machine, remaining)?;
+        }
+
+        Ok(machine.tick_timers())
+    }
+}
+
+/// Compare machine states which must be the same. Screen is compared only if `screen` is true.
+fn compare(jit: &Machine, interpreter: &Machine, screen: bool) -> Result<(), String> {
+    let (left, right) = (jit.registers(), interpreter.registers());
+    let fields = [
+        ("PC", left.get_pc() == right.get_pc()),
+        ("V", left.general_registers() == right.general_registers()),
+        ("I", left.get_l() == right.get_l()),
+        ("stack", left.stack() == right.stack()),
+        ("cycles", jit.cycles() == interpreter.cycles()),
+        ("state", jit.state() == interpreter.state()),
+        ("memory", jit.memory().get_data_bytes(0, MEMORY_SIZE) == interpreter.memory().get_data_bytes(0, MEMORY_SIZE)),
+        ("screen", !screen || screen_equals(jit.screen(), interpreter.screen())),
+    ];
+    match fields.iter().find(|(_, same)| !same) {
+        Some((name, _)) => Err(format!(
+            "{} differs at cycle {}\n  jit         : {}\n  interpreter : {}",
+            name, interpreter.cycles(), left, right)),
+        None => Ok(()),
+    }
+}
+
+fn screen_equals(left: &Screen, right: &Screen) -> bool {
+    let (width, height) = (super::screen::SCREEN_WIDTH as u8, super::screen::SCREEN_HEIGHT as u8);
+    (0..height).all(|y| (0..width).all(|x| left.is_drawn((x, y)) == right.is_drawn((x, y))))
+}
+
+/// Run ROM by JIT and by interpreter in lockstep, and report the first difference of machine state.
+/// Both machines use the same random seed. States are compared after every translated block,
+/// and screens after every frame.
+pub fn verify_lockstep(rom: &[u8], quirks: Quirks, frames: u64, cycles: usize, seed: u64) -> Result<(), String> {
+    let mut jit_machine = Machine::from_memory(Memory::from_rom(rom), quirks);
+    let mut interpreter = Machine::from_memory(Memory::from_rom(rom), quirks);
+    jit_machine.seed_random(seed);
+    interpreter.seed_random(seed);
+    let mut jit = Jit::new();
+
+    for _ in 0..frames {
+        let mut remaining = cycles as u64;
+        while remaining > 0 {
+            let jit_result = jit.step(&mut jit_machine, remaining);
+            let count = *jit_result.as_ref().unwrap_or(&1);
+            let interpreter_result = (0..count).try_for_each(|_| interpreter.step().map(|_| ()));
+            match (jit_result, interpreter_result) {
+                (Ok(_), Ok(())) => (),
+                (Err(left), Err(right)) if left == right => return Ok(()),
+                (left, right) => return Err(format!("results differ : jit {:?}, interpreter {:?}", left.err(), right.err())),
+            }
+            compare(&jit_machine, &interpreter, false)?;
+            remaining -= count;
+        }
+        if jit_machine.tick_timers() != interpreter.tick_timers() {
+            return Err(format!("timers differ at frame {}", interpreter.frames()));
+        }
+        compare(&jit_machine, &interpreter, true)?;
+    }
+    Ok(())
+}
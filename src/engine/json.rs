@@ -0,0 +1,281 @@
+use std::fmt;
+
+/// Provides JSON value. Members of object keep their order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+const NULL: Json = Json::Null;
+
+impl Json {
+    /// Make object of given members.
+    pub fn object(members: Vec<(&str, Json)>) -> Json {
+        Json::Object(members.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    /// Get member of object. Null is given if there is no such member.
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(members) => members.iter().find(|(name, _)| name == key).map_or(&NULL, |(_, value)| value),
+            _ => &NULL,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match *self {
+            Json::Number(value) if value >= 0.0 && value.fract() == 0.0 => Some(value as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Json::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == Json::Null
+    }
+
+    /// Parse JSON text.
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { chars: text.chars().collect(), pos: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(format!("unexpected {} at {}", parser.chars[parser.pos], parser.pos));
+        }
+        Ok(value)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Json { Json::Bool(value) }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Json { Json::Number(value as f64) }
+}
+
+impl From<&str> for Json {
+    fn from(text: &str) -> Json { Json::String(text.to_string()) }
+}
+
+impl From<String> for Json {
+    fn from(text: String) -> Json { Json::String(text) }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(items: Vec<Json>) -> Json { Json::Array(items) }
+}
+
+/// Write compact JSON text.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(text) => write!(f, "{}", json_string(text)),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    write!(f, "{}{}", if index > 0 { "," } else { "" }, item)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in members.iter().enumerate() {
+                    write!(f, "{}{}:{}", if index > 0 { "," } else { "" }, json_string(key), value)?;
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+/// Quote CSV field if it has separator, quote or newline.
+pub(crate) fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Quote JSON string, escaping quotes, backslashes and control characters.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.peek() {
+            Some(found) if found == c => {
+                self.pos += 1;
+                Ok(())
+            },
+            Some(found) => Err(format!("expected {}, but got {} at {}", c, found, self.pos)),
+            None => Err(format!("expected {}, but got end of text", c)),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        let end = self.pos + word.len();
+        if end <= self.chars.len() && self.chars[self.pos..end].iter().copied().eq(word.chars()) {
+            self.pos = end;
+            Ok(value)
+        } else {
+            Err(format!("invalid value at {}", self.pos))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    members.push((key, self.value()?));
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect('}')?;
+                Ok(Json::Object(members))
+            },
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(']')?;
+                Ok(Json::Array(items))
+            },
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('n') => self.keyword("null", Json::Null),
+            Some(_) => {
+                let start = self.pos;
+                while self.chars.get(self.pos).is_some_and(|&c| c.is_ascii_digit() || "+-.eE".contains(c)) {
+                    self.pos += 1;
+                }
+                let number: String = self.chars[start..self.pos].iter().collect();
+                number.parse().map(Json::Number).map_err(|_| format!("invalid value at {}", start))
+            },
+            None => Err("unexpected end of text".to_string()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            let c = *self.chars.get(self.pos).ok_or("unterminated string")?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escaped = *self.chars.get(self.pos).ok_or("unterminated string")?;
+                    self.pos += 1;
+                    match escaped {
+                        'n' => text.push('\n'),
+                        't' => text.push('\t'),
+                        'r' => text.push('\r'),
+                        'b' => text.push('\u{8}'),
+                        'f' => text.push('\u{c}'),
+                        'u' => {
+                            let code = self.hex4()?;
+                            // Surrogate pair is two escapes.
+                            let code = if (0xD800..0xDC00).contains(&code) && self.chars[self.pos..].starts_with(&['\\', 'u']) {
+                                self.pos += 2;
+                                0x10000 + ((code - 0xD800) << 10) + (self.hex4()?.wrapping_sub(0xDC00) & 0x3FF)
+                            } else {
+                                code
+                            };
+                            text.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        },
+                        c => text.push(c),
+                    }
+                },
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let end = self.pos + 4;
+        if end > self.chars.len() {
+            return Err("unterminated string".to_string());
+        }
+        let digits: String = self.chars[self.pos..end].iter().collect();
+        self.pos = end;
+        u32::from_str_radix(&digits, 16).map_err(|_| format!("invalid escape \\u{}", digits))
+    }
+}
@@ -0,0 +1,405 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    io::{self, Write},
+};
+
+use super::cfg::Cfg;
+use super::isa::{self, Flow, Instruction, Platform};
+use super::json::{csv_field, json_string};
+use super::memory::{MEMORY_SIZE, PROGRAM_START};
+use super::quirks::QuirksProfile;
+
+/// Provides kind of problem found by linter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rule {
+    /// Jump or call target is odd or outside of ROM image.
+    BadTarget,
+    /// I is read while it may be never set on some path.
+    UnsetIndex,
+    /// 0xFx33, 0xFx55, 0xFx65 access past the end of memory.
+    MemoryOverrun,
+    /// RET is reachable from the entry point without CALL.
+    UnmatchedReturn,
+    /// Instructions which are never reached and are not referenced as data.
+    Unreachable,
+    /// Result depends on quirks which differ between platforms.
+    Quirk,
+    /// Opcode of other dialects such as SUPER-CHIP and XO-CHIP, or unknown opcode.
+    Dialect,
+}
+
+impl Rule {
+    /// All rules, which are enabled by default.
+    pub const ALL: [Rule; 7] = [
+        Rule::BadTarget,
+        Rule::UnsetIndex,
+        Rule::MemoryOverrun,
+        Rule::UnmatchedReturn,
+        Rule::Unreachable,
+        Rule::Quirk,
+        Rule::Dialect,
+    ];
+
+    /// Get short name of rule which is also accepted by `from_name`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::BadTarget => "bad-target",
+            Rule::UnsetIndex => "unset-index",
+            Rule::MemoryOverrun => "memory-overrun",
+            Rule::UnmatchedReturn => "unmatched-return",
+            Rule::Unreachable => "unreachable",
+            Rule::Quirk => "quirk",
+            Rule::Dialect => "dialect",
+        }
+    }
+
+    /// Get rule from given short name.
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.iter().copied().find(|rule| rule.name() == name)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Provides one problem found by linter.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Warning {
+    pub pc: u16,
+    pub rule: Rule,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at 0x{:03X} : {}", self.rule, self.pc, self.message)
+    }
+}
+
+/// Provides statically known address in I.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Addr {
+    /// I is not set on any path yet.
+    None,
+    Known(u16),
+    Unknown,
+}
+
+/// Provides state of I before an instruction, merged over every path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Index {
+    /// I is not set on some path.
+    may_be_unset: bool,
+    addr: Addr,
+    /// I may have been advanced by 0xFx55 or 0xFx65, which depends on quirk.
+    after_memory_op: bool,
+}
+
+impl Index {
+    fn join(self, other: Index) -> Index {
+        let addr = match (self.addr, other.addr) {
+            (Addr::None, addr) | (addr, Addr::None) => addr,
+            (Addr::Known(a), Addr::Known(b)) if a == b => Addr::Known(a),
+            _ => Addr::Unknown,
+        };
+        Index {
+            may_be_unset: self.may_be_unset || other.may_be_unset,
+            addr,
+            after_memory_op: self.after_memory_op || other.after_memory_op,
+        }
+    }
+
+    /// Get state after given instruction.
+    fn after(self, instruction: &Instruction) -> Index {
+        type Inst = Instruction;
+        match *instruction {
+            Inst::SetRegL(addr) => Index { may_be_unset: false, addr: Addr::Known(addr), after_memory_op: false },
+            Inst::SetRegLFontAddrFromReg{ .. } => Index { may_be_unset: false, addr: Addr::Unknown, after_memory_op: false },
+            Inst::AddRegL{ .. } => Index { addr: self.known_or(Addr::Unknown), ..self },
+            Inst::MemDump{ .. } | Inst::MemRead{ .. } => Index { addr: self.known_or(Addr::Unknown), after_memory_op: true, ..self },
+            _ => self,
+        }
+    }
+
+    /// Get given address unless I is never set.
+    fn known_or(self, addr: Addr) -> Addr {
+        match self.addr {
+            Addr::None => Addr::None,
+            _ => addr,
+        }
+    }
+}
+
+/// Describe opcode which this interpreter ignores or can not parse.
+fn describe_opcode(word: u16) -> String {
+    let dialect = match word {
+        0x00C0..=0x00CF | 0x00FB..=0x00FF => "SUPER-CHIP ",
+        0xF000 | 0xF002 => "XO-CHIP ",
+        _ => match (word & 0xF00F, word & 0xF0FF) {
+            (0x5002, _) | (0x5003, _) | (_, 0xF001) | (_, 0xF03A) => "XO-CHIP ",
+            (_, 0xF030) | (_, 0xF075) | (_, 0xF085) => "SUPER-CHIP ",
+            _ if word & 0xF000 == 0 => "machine code ",
+            _ => "unknown ",
+        },
+    };
+    format!("{}opcode 0x{:04X}", dialect, word)
+}
+
+/// Provides linter of one ROM.
+struct Linter<'a> {
+    rom: &'a [u8],
+    rules: &'a [Rule],
+    cfg: Cfg,
+    /// Every reachable instruction.
+    code: BTreeMap<u16, Instruction>,
+    warnings: BTreeSet<Warning>,
+}
+
+impl<'a> Linter<'a> {
+    fn warn(&mut self, rule: Rule, pc: u16, message: String) {
+        if self.rules.contains(&rule) {
+            self.warnings.insert(Warning { pc, rule, message });
+        }
+    }
+
+    fn word(&self, addr: u16) -> Option<u16> {
+        let index = (addr as usize).checked_sub(PROGRAM_START)?;
+        let bytes = self.rom.get(index..index + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn rom_end(&self) -> usize {
+        PROGRAM_START + self.rom.len()
+    }
+
+    fn check_targets(&mut self) {
+        let code: Vec<(u16, Instruction)> = self.code.iter().map(|(&pc, &instruction)| (pc, instruction)).collect();
+        for (pc, instruction) in code {
+            let (kind, target) = match instruction {
+                Instruction::JmpAddr(addr) => ("Jump", addr),
+                Instruction::CallSub(addr) => ("Call", addr),
+                _ => continue,
+            };
+            if target % 2 != 0 {
+                self.warn(Rule::BadTarget, pc, format!("{} target 0x{:03X} is odd", kind, target));
+            }
+            if (target as usize) < PROGRAM_START || target as usize + 2 > self.rom_end() {
+                self.warn(Rule::BadTarget, pc, format!("{} target 0x{:03X} is outside of ROM", kind, target));
+            }
+        }
+    }
+
+    /// Get successors of instruction over the whole program. RET goes back to every call site of its functions.
+    fn successors(&self, pc: u16, instruction: &Instruction, returns: &BTreeMap<u16, Vec<u16>>) -> Vec<u16> {
+        let next = match instruction.flow() {
+            Flow::Jump(addr) | Flow::Call(addr) => vec![addr],
+            Flow::IndirectJump => vec![],
+            Flow::Return => returns.get(&pc).cloned().unwrap_or_default(),
+            Flow::Skip => vec![pc + 2, pc + 4],
+            Flow::Next => vec![pc + 2],
+        };
+        next.into_iter().filter(|addr| self.code.contains_key(addr)).collect()
+    }
+
+    /// Find state of I before every instruction, and check instructions which read I.
+    fn check_index(&mut self) {
+        // Return sites of each RET, from call sites of functions which contain it.
+        let mut returns: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+        for function in self.cfg.functions.values() {
+            let sites: Vec<u16> = self.code.iter()
+                .filter(|&(_, instruction)| *instruction == Instruction::CallSub(function.entry))
+                .map(|(&pc, _)| pc + 2)
+                .collect();
+            for block in function.blocks.values() {
+                for &(pc, instruction) in &block.instructions {
+                    if instruction == Instruction::ReturnSubroutine {
+                        returns.entry(pc).or_default().extend(sites.iter().copied());
+                    }
+                }
+            }
+        }
+
+        let entry = PROGRAM_START as u16;
+        let mut states = BTreeMap::new();
+        if self.code.contains_key(&entry) {
+            states.insert(entry, Index { may_be_unset: true, addr: Addr::None, after_memory_op: false });
+        }
+        let mut pending = vec![entry];
+        while let Some(pc) = pending.pop() {
+            let (state, instruction) = match (states.get(&pc), self.code.get(&pc)) {
+                (Some(&state), Some(&instruction)) => (state, instruction),
+                _ => continue,
+            };
+            let after = state.after(&instruction);
+            for next in self.successors(pc, &instruction, &returns) {
+                let joined = states.get(&next).map_or(after, |&old: &Index| old.join(after));
+                if states.get(&next) != Some(&joined) {
+                    states.insert(next, joined);
+                    pending.push(next);
+                }
+            }
+        }
+
+        for (pc, state) in states {
+            let instruction = self.code[&pc];
+            // Whether I is read does not depend on quirks.
+            if !instruction.reads(&QuirksProfile::Chipmunk.quirks()).index {
+                continue;
+            }
+            if state.may_be_unset {
+                self.warn(Rule::UnsetIndex, pc, format!("{} reads I, which may be never set", instruction));
+            }
+            if state.after_memory_op {
+                self.warn(Rule::Quirk, pc, format!("{} reads I after 0xFx55 or 0xFx65, which advance I only on some platforms", instruction));
+            }
+            let count = match instruction {
+                Instruction::MemDumpBcdFromReg{ .. } => 3,
+                Instruction::MemDump{ endr } | Instruction::MemRead{ endr } => endr as usize + 1,
+                _ => continue,
+            };
+            if let Addr::Known(addr) = state.addr {
+                if addr as usize + count > MEMORY_SIZE {
+                    self.warn(Rule::MemoryOverrun, pc, format!("{} accesses {} byte(s) from 0x{:03X}, past the end of memory", instruction, count, addr));
+                }
+            }
+        }
+    }
+
+    fn check_returns(&mut self) {
+        let main = match self.cfg.functions.get(&(PROGRAM_START as u16)) {
+            Some(main) => main.clone(),
+            None => return,
+        };
+        for block in main.blocks.values() {
+            for &(pc, instruction) in &block.instructions {
+                if instruction == Instruction::ReturnSubroutine {
+                    self.warn(Rule::UnmatchedReturn, pc, "RET is reachable from the entry point without CALL".to_string());
+                }
+            }
+        }
+    }
+
+    fn check_instructions(&mut self) {
+        let code: Vec<(u16, Instruction)> = self.code.iter().map(|(&pc, &instruction)| (pc, instruction)).collect();
+        for (pc, instruction) in code {
+            type Inst = Instruction;
+            match instruction {
+                Inst::ShrRegV{ r, f } | Inst::ShlRegV{ r, f } if r != f => {
+                    self.warn(Rule::Quirk, pc, format!("{} shifts V{:X} on some platforms and V{:X} on others", instruction, f, r));
+                },
+                Inst::JmpAddrOffReg0(addr) if addr & 0xF00 != 0 => {
+                    self.warn(Rule::Quirk, pc, format!("{} adds V0 on some platforms and V{:X} on others", instruction, addr >> 8));
+                },
+                _ => (),
+            }
+            match instruction.platform() {
+                Platform::Chip8 => (),
+                Platform::CosmacVip => {
+                    let message = format!("{} is ignored", describe_opcode(self.word(pc).unwrap_or(0)));
+                    self.warn(Rule::Dialect, pc, message);
+                },
+                Platform::SuperChip => {
+                    self.warn(Rule::Dialect, pc, format!("{} draws 16x16 sprite on SUPER-CHIP", instruction));
+                },
+            }
+        }
+
+        // Blocks end at bytes which can not be parsed.
+        let ends: BTreeSet<u16> = self.cfg.functions.values()
+            .flat_map(|function| function.blocks.values())
+            .filter(|block| block.invalid_end)
+            .map(|block| block.end())
+            .collect();
+        for end in ends {
+            if let Some(word) = self.word(end) {
+                let message = format!("{} is reached, but is not supported", describe_opcode(word));
+                self.warn(Rule::Dialect, end, message);
+            }
+        }
+    }
+
+    /// Report gaps of ROM which are not reached. Gaps are skipped as data if they are referenced by
+    /// 0xAnnn or 0xBnnn, are all zero, or start with bytes which can not be parsed.
+    fn check_unreachable(&mut self) {
+        // Bytes which can not be parsed are reached too, and are reported by dialect rule.
+        let reached = self.code.keys().copied().chain(self.cfg.functions.values()
+            .flat_map(|function| function.blocks.values())
+            .filter(|block| block.invalid_end)
+            .map(|block| block.end()));
+        let mut covered = vec![false; self.rom.len()];
+        for pc in reached {
+            for addr in pc..pc.saturating_add(2) {
+                if let Some(covered) = (addr as usize).checked_sub(PROGRAM_START).and_then(|index| covered.get_mut(index)) {
+                    *covered = true;
+                }
+            }
+        }
+        let references: BTreeSet<u16> = self.code.values()
+            .filter_map(|instruction| match *instruction {
+                Instruction::SetRegL(addr) | Instruction::JmpAddrOffReg0(addr) => Some(addr),
+                _ => None,
+            })
+            .collect();
+
+        let mut index = 0;
+        while index < self.rom.len() {
+            if covered[index] {
+                index += 1;
+                continue;
+            }
+            let start = index;
+            while index < self.rom.len() && !covered[index] {
+                index += 1;
+            }
+            let (from, to) = ((PROGRAM_START + start) as u16, (PROGRAM_START + index) as u16);
+            let is_data = references.range(from..to).next().is_some()
+                || self.rom[start..index].iter().all(|&byte| byte == 0)
+                || index - start < 2
+                || isa::parse_instruction(&[self.rom[start], self.rom[start + 1]]).is_none();
+            if !is_data {
+                self.warn(Rule::Unreachable, from, format!("0x{:03X}-0x{:03X} is never reached", from, to - 1));
+            }
+        }
+    }
+}
+
+/// Check ROM statically with given rules, and get warnings sorted by address.
+pub fn lint(rom: &[u8], rules: &[Rule]) -> Vec<Warning> {
+    let cfg = Cfg::build(rom);
+    let mut code = BTreeMap::new();
+    for function in cfg.functions.values() {
+        for block in function.blocks.values() {
+            code.extend(block.instructions.iter().copied());
+        }
+    }
+    let mut linter = Linter { rom, rules, cfg, code, warnings: BTreeSet::new() };
+    linter.check_targets();
+    linter.check_index();
+    linter.check_returns();
+    linter.check_instructions();
+    linter.check_unreachable();
+    linter.warnings.into_iter().collect()
+}
+
+/// Write warnings as CSV with header line.
+pub fn write_csv(warnings: &[Warning], out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "pc,rule,message")?;
+    for warning in warnings {
+        writeln!(out, "0x{:03X},{},{}", warning.pc, warning.rule, csv_field(&warning.message))?;
+    }
+    Ok(())
+}
+
+/// Write warnings as JSON array of objects.
+pub fn write_json(warnings: &[Warning], out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "[")?;
+    for (index, warning) in warnings.iter().enumerate() {
+        writeln!(out, "  {{\"pc\": {}, \"rule\": \"{}\", \"message\": {}}}{}",
+            warning.pc, warning.rule, json_string(&warning.message), if index + 1 < warnings.len() { "," } else { "" })?;
+    }
+    writeln!(out, "]")
+}
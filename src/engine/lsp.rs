@@ -0,0 +1,552 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    iter,
+};
+
+use super::asm;
+use super::isa;
+use super::json::Json;
+use super::memory::PROGRAM_START;
+use super::octo::{self, Program};
+
+/// Provides language of document, which is Octo for `.8o` files and assembly of canonical mnemonics otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Octo,
+    Assembly,
+}
+
+impl Language {
+    /// Get language from language identifier of client, or extension of URI.
+    pub fn of(uri: &str, language_id: &str) -> Language {
+        if language_id == "octo" || uri.ends_with(".8o") {
+            Language::Octo
+        } else {
+            Language::Assembly
+        }
+    }
+}
+
+/// Provides how name is defined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Label,
+    Alias,
+    Constant,
+    Macro,
+}
+
+/// Provides name defined in document, and its position.
+#[derive(Debug, Clone)]
+struct Definition {
+    name: String,
+    kind: Kind,
+    line: usize,
+    column: usize,
+}
+
+struct Document {
+    text: String,
+    language: Language,
+}
+
+impl Document {
+    fn compile(&self, file_name: &str) -> Result<Program, String> {
+        match self.language {
+            Language::Octo => octo::compile(&self.text, file_name),
+            Language::Assembly => asm::assemble(&self.text, file_name),
+        }
+    }
+}
+
+/// Get tokens of line with their columns, without comment.
+fn tokens(line: &str, language: Language) -> Vec<(usize, &str)> {
+    let code = match language {
+        Language::Octo => line,
+        Language::Assembly => line.split(';').next().unwrap_or(""),
+    };
+    let is_separator = |c: char| c.is_whitespace() || (language == Language::Assembly && (c == ',' || c == ':'));
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (column, c) in code.char_indices().chain(iter::once((code.len(), ' '))) {
+        match (start, is_separator(c)) {
+            (Some(from), true) => {
+                tokens.push((from, &code[from..column]));
+                start = None;
+            },
+            (None, false) => start = Some(column),
+            _ => {},
+        }
+    }
+    if language == Language::Octo {
+        if let Some(index) = tokens.iter().position(|(_, text)| text.starts_with('#')) {
+            tokens.truncate(index);
+        }
+    }
+    tokens
+}
+
+/// Get token at given column of line.
+fn token_at(line: &str, column: usize, language: Language) -> Option<&str> {
+    tokens(line, language).into_iter()
+        .find(|(start, text)| (*start..=start + text.len()).contains(&column))
+        .map(|(_, text)| text)
+}
+
+fn definitions(text: &str, language: Language) -> Vec<Definition> {
+    let mut definitions = Vec::new();
+    match language {
+        Language::Octo => {
+            // Directive and its name may be on different lines.
+            let tokens: Vec<(usize, usize, &str)> = text.lines().enumerate()
+                .flat_map(|(line, text)| tokens(text, language).into_iter().map(move |(column, token)| (line, column, token)))
+                .collect();
+            for pair in tokens.windows(2) {
+                let kind = match pair[0].2 {
+                    ":" | ":next" => Kind::Label,
+                    ":alias" => Kind::Alias,
+                    ":const" | ":calc" => Kind::Constant,
+                    ":macro" => Kind::Macro,
+                    _ => continue,
+                };
+                let (line, column, name) = pair[1];
+                definitions.push(Definition { name: name.to_string(), kind, line, column });
+            }
+        },
+        Language::Assembly => {
+            for (line, text) in text.lines().enumerate() {
+                if let Some(label) = asm::split_line(text).label {
+                    let column = text.find(label).unwrap_or(0);
+                    definitions.push(Definition { name: label.to_string(), kind: Kind::Label, line, column });
+                }
+            }
+        },
+    }
+    definitions
+}
+
+/// Format document. Octo source is indented by nesting of labels, `loop`, `begin` and macros, and
+/// assembly is aligned into columns of labels, instructions and comments.
+pub fn format(text: &str, language: Language) -> String {
+    let mut formatted = String::new();
+    match language {
+        Language::Octo => {
+            let mut depth = 0usize;
+            let mut is_in_label = false;
+            for line in text.lines() {
+                let line = line.trim();
+                let tokens: Vec<&str> = tokens(line, language).into_iter().map(|(_, token)| token).collect();
+                let indent = match tokens.first().copied() {
+                    None if line.is_empty() => 0,
+                    Some(":") | Some(":macro") | Some(":org") => {
+                        is_in_label = tokens[0] == ":";
+                        depth
+                    },
+                    Some("again") | Some("end") | Some("else") | Some("}") => depth.saturating_sub(1) + is_in_label as usize,
+                    _ => depth + is_in_label as usize,
+                };
+                for token in &tokens {
+                    match *token {
+                        "loop" | "begin" | "{" => depth += 1,
+                        "again" | "end" | "}" => depth = depth.saturating_sub(1),
+                        _ => {},
+                    }
+                }
+                if !line.is_empty() {
+                    formatted.push_str(&"  ".repeat(indent));
+                }
+                formatted.push_str(line);
+                formatted.push('\n');
+            }
+        },
+        Language::Assembly => {
+            for line in text.lines() {
+                let parts = asm::split_line(line);
+                if parts.label.is_none() && parts.mnemonic.is_none() {
+                    formatted.push_str(line.trim());
+                    formatted.push('\n');
+                    continue;
+                }
+                let mut code = match parts.label {
+                    Some(label) => format!("{:<8}", format!("{}: ", label)),
+                    None => " ".repeat(8),
+                };
+                if let Some(mnemonic) = parts.mnemonic {
+                    let operands: Vec<String> = parts.operands.iter()
+                        .map(|operand| if asm::is_name(operand) { operand.to_string() } else { operand.to_uppercase().replace("0X", "0x") })
+                        .collect();
+                    code.push_str(&format!("{} {}", mnemonic.to_uppercase(), operands.join(", ")));
+                }
+                let mut code = code.trim_end().to_string();
+                if let Some(comment) = parts.comment {
+                    code = format!("{:<24}  ; {}", code, comment.trim());
+                }
+                formatted.push_str(&code);
+                formatted.push('\n');
+            }
+        },
+    }
+    formatted
+}
+
+/// Get byte column of line from column of UTF-16 code units, which LSP positions count.
+fn byte_column(line: &str, character: usize) -> usize {
+    let mut units = 0;
+    for (column, c) in line.char_indices() {
+        if units >= character {
+            return column;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+/// Get column of UTF-16 code units from byte column of line.
+fn utf16_column(line: &str, column: usize) -> usize {
+    line[..column].encode_utf16().count()
+}
+
+fn position(line: usize, character: usize) -> Json {
+    Json::object(vec![("line", line.into()), ("character", character.into())])
+}
+
+fn range(line: usize, start: usize, end: usize) -> Json {
+    Json::object(vec![("start", position(line, start)), ("end", position(line, end))])
+}
+
+/// Get location of byte columns [start, end) of given line, whose text is `text`.
+fn location(uri: &str, text: &str, line: usize, start: usize, end: usize) -> Json {
+    let range = range(line, utf16_column(text, start), utf16_column(text, end));
+    Json::object(vec![("uri", uri.into()), ("range", range)])
+}
+
+fn error_response(id: &Json, code: i32, message: String) -> Json {
+    Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("id", id.clone()),
+        ("error", Json::object(vec![
+            ("code", Json::Number(code as f64)),
+            ("message", message.into()),
+        ])),
+    ])
+}
+
+/// Get diagnostic of error of compiler, which is written as `line N : message`.
+fn diagnostic(text: &str, error: &str) -> Json {
+    let (line, message) = error.strip_prefix("line ")
+        .and_then(|rest| rest.split_once(" : "))
+        .and_then(|(line, message)| Some((line.parse::<usize>().ok()?.saturating_sub(1), message)))
+        .unwrap_or((0, error));
+    let len = text.lines().nth(line).map_or(0, |text| text.encode_utf16().count());
+    Json::object(vec![
+        ("range", range(line, 0, len)),
+        ("severity", 1.into()),
+        ("source", "chipmunk".into()),
+        ("message", message.into()),
+    ])
+}
+
+/// Provides language server of Octo and assembly documents.
+pub struct Server {
+    documents: HashMap<String, Document>,
+    /// Mnemonics of every instruction of ISA.
+    mnemonics: Vec<&'static str>,
+    is_exited: bool,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Server {
+    pub fn new() -> Server {
+        let mut mnemonics: Vec<&'static str> = (0..=u16::MAX)
+            .filter_map(|word| isa::parse_instruction(&word.to_be_bytes()))
+            .map(|instruction| instruction.mnemonic())
+            .collect();
+        mnemonics.sort_unstable();
+        mnemonics.dedup();
+        Server { documents: HashMap::new(), mnemonics, is_exited: false }
+    }
+
+    /// Check whether client asked the server to exit.
+    pub fn is_exited(&self) -> bool {
+        self.is_exited
+    }
+
+    /// Handle one request or notification. Get responses and notifications to be sent to client.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").as_str().unwrap_or("");
+        let params = message.get("params");
+        let id = message.get("id");
+        let uri = params.get("textDocument").get("uri").as_str().unwrap_or("").to_string();
+        let (line, character) = (
+            params.get("position").get("line").as_usize().unwrap_or(0),
+            params.get("position").get("character").as_usize().unwrap_or(0),
+        );
+
+        let result = match method {
+            "initialize" => Json::object(vec![
+                ("capabilities", Json::object(vec![
+                    ("textDocumentSync", 1.into()),
+                    ("hoverProvider", true.into()),
+                    ("definitionProvider", true.into()),
+                    ("referencesProvider", true.into()),
+                    ("completionProvider", Json::object(vec![])),
+                    ("documentFormattingProvider", true.into()),
+                ])),
+                ("serverInfo", Json::object(vec![("name", "chipmunk".into())])),
+            ]),
+            "shutdown" => Json::Null,
+            "exit" => {
+                self.is_exited = true;
+                return Vec::new();
+            },
+            "textDocument/didOpen" => {
+                let item = params.get("textDocument");
+                let language = Language::of(&uri, item.get("languageId").as_str().unwrap_or(""));
+                let text = item.get("text").as_str().unwrap_or("").to_string();
+                self.documents.insert(uri.clone(), Document { text, language });
+                return vec![self.diagnostics(&uri)];
+            },
+            "textDocument/didChange" => {
+                // Whole text is sent on every change.
+                match (self.documents.get_mut(&uri), params.get("contentChanges").as_array().last()) {
+                    (Some(document), Some(change)) => document.text = change.get("text").as_str().unwrap_or("").to_string(),
+                    _ => return Vec::new(),
+                }
+                return vec![self.diagnostics(&uri)];
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![notification("textDocument/publishDiagnostics", Json::object(vec![
+                    ("uri", uri.as_str().into()),
+                    ("diagnostics", Json::Array(Vec::new())),
+                ]))];
+            },
+            "textDocument/hover" => self.hover(&uri, line, character),
+            "textDocument/definition" => self.definition(&uri, line, character),
+            "textDocument/references" => {
+                let include_declaration = params.get("context").get("includeDeclaration").as_bool().unwrap_or(true);
+                self.references(&uri, line, character, include_declaration)
+            },
+            "textDocument/completion" => self.completion(&uri),
+            "textDocument/formatting" => self.formatting(&uri),
+            _ if id.is_null() => return Vec::new(),
+            _ => return vec![error_response(id, -32601, format!("Unknown method {}", method))],
+        };
+        if id.is_null() {
+            return Vec::new();
+        }
+        vec![Json::object(vec![("jsonrpc", "2.0".into()), ("id", id.clone()), ("result", result)])]
+    }
+
+    fn diagnostics(&self, uri: &str) -> Json {
+        let document = &self.documents[uri];
+        let diagnostics = match document.compile(file_name(uri)) {
+            Ok(_) => Vec::new(),
+            Err(err) => vec![diagnostic(&document.text, &err)],
+        };
+        notification("textDocument/publishDiagnostics", Json::object(vec![
+            ("uri", uri.into()),
+            ("diagnostics", Json::Array(diagnostics)),
+        ]))
+    }
+
+    /// Get token at position, and document which has it.
+    fn token(&self, uri: &str, line: usize, character: usize) -> Option<(&Document, &str)> {
+        let document = self.documents.get(uri)?;
+        let text = document.text.lines().nth(line)?;
+        let token = token_at(text, byte_column(text, character), document.language)?;
+        Some((document, token))
+    }
+
+    /// Show address of label, and decoded instructions of line with their encoding and quirks.
+    fn hover(&self, uri: &str, line: usize, character: usize) -> Json {
+        let document = match self.documents.get(uri) {
+            Some(document) => document,
+            None => return Json::Null,
+        };
+        let program = match document.compile(file_name(uri)) {
+            Ok(program) => program,
+            Err(_) => return Json::Null,
+        };
+
+        let mut contents = Vec::new();
+        if let Some((_, token)) = self.token(uri, line, character) {
+            if let Some((addr, _)) = program.symbols.labels().find(|(_, name)| *name == token) {
+                contents.push(format!("`{}` is at 0x{:03X}", token, addr));
+            }
+        }
+        for (addr, _, _) in program.symbols.lines().filter(|(_, _, source_line)| *source_line == line + 1) {
+            let offset = addr as usize - PROGRAM_START;
+            let bytes = [program.rom[offset], program.rom[offset + 1]];
+            match isa::parse_instruction(&bytes) {
+                Some(instruction) => {
+                    contents.push(format!("0x{:03X}  `{}`  encoded as `{:02X}{:02X}`", addr, instruction, bytes[0], bytes[1]));
+                    for caveat in instruction.caveats() {
+                        contents.push(format!("- Quirk : {}", caveat));
+                    }
+                },
+                None => contents.push(format!("0x{:03X}  `{:02X}{:02X}` is not CHIP-8 instruction", addr, bytes[0], bytes[1])),
+            }
+        }
+        if contents.is_empty() {
+            return Json::Null;
+        }
+        Json::object(vec![("contents", Json::object(vec![
+            ("kind", "markdown".into()),
+            ("value", contents.join("\n\n").into()),
+        ]))])
+    }
+
+    fn definition(&self, uri: &str, line: usize, character: usize) -> Json {
+        let (document, token) = match self.token(uri, line, character) {
+            Some(found) => found,
+            None => return Json::Null,
+        };
+        let locations: Vec<Json> = definitions(&document.text, document.language).iter()
+            .filter(|definition| definition.name == token)
+            .map(|definition| {
+                let text = document.text.lines().nth(definition.line).unwrap_or("");
+                location(uri, text, definition.line, definition.column, definition.column + token.len())
+            })
+            .collect();
+        if locations.is_empty() { Json::Null } else { Json::Array(locations) }
+    }
+
+    fn references(&self, uri: &str, line: usize, character: usize, include_declaration: bool) -> Json {
+        let (document, token) = match self.token(uri, line, character) {
+            Some(found) => found,
+            None => return Json::Null,
+        };
+        let definitions = definitions(&document.text, document.language);
+        let mut locations = Vec::new();
+        for (line, text) in document.text.lines().enumerate() {
+            for (column, _) in tokens(text, document.language).into_iter().filter(|(_, text)| *text == token) {
+                let is_declaration = definitions.iter().any(|definition| definition.line == line && definition.column == column);
+                if include_declaration || !is_declaration {
+                    locations.push(location(uri, text, line, column, column + token.len()));
+                }
+            }
+        }
+        Json::Array(locations)
+    }
+
+    /// Offer mnemonics or keywords, registers and names defined in document.
+    fn completion(&self, uri: &str) -> Json {
+        const KEYWORD: usize = 14;
+        const VARIABLE: usize = 6;
+        const CONSTANT: usize = 21;
+        const FUNCTION: usize = 3;
+        let document = match self.documents.get(uri) {
+            Some(document) => document,
+            None => return Json::Array(Vec::new()),
+        };
+        let item = |label: String, kind: usize| Json::object(vec![("label", label.into()), ("kind", kind.into())]);
+
+        let mut items = Vec::new();
+        match document.language {
+            Language::Octo => {
+                items.extend(octo::KEYWORDS.iter().map(|keyword| item(keyword.to_string(), KEYWORD)));
+                items.extend((0..16).map(|r| item(format!("v{:x}", r), VARIABLE)));
+            },
+            Language::Assembly => {
+                items.extend(self.mnemonics.iter().chain(&["DB", "DW"]).map(|mnemonic| item(mnemonic.to_string(), KEYWORD)));
+                items.extend((0..16).map(|r| item(format!("V{:X}", r), VARIABLE)));
+                items.extend(["I", "[I]", "DT", "ST", "K", "F", "B"].iter().map(|name| item(name.to_string(), VARIABLE)));
+            },
+        }
+        for definition in definitions(&document.text, document.language) {
+            let kind = match definition.kind {
+                Kind::Label | Kind::Macro => FUNCTION,
+                Kind::Alias => VARIABLE,
+                Kind::Constant => CONSTANT,
+            };
+            items.push(item(definition.name, kind));
+        }
+        Json::Array(items)
+    }
+
+    /// Replace whole document with formatted text.
+    fn formatting(&self, uri: &str) -> Json {
+        let document = match self.documents.get(uri) {
+            Some(document) => document,
+            None => return Json::Array(Vec::new()),
+        };
+        let formatted = format(&document.text, document.language);
+        if formatted == document.text {
+            return Json::Array(Vec::new());
+        }
+        let line_count = document.text.lines().count() + 1;
+        Json::Array(vec![Json::object(vec![
+            ("range", Json::object(vec![("start", position(0, 0)), ("end", position(line_count, 0))])),
+            ("newText", formatted.into()),
+        ])])
+    }
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object(vec![("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)])
+}
+
+/// Get the last segment of URI, which is written into symbols.
+fn file_name(uri: &str) -> &str {
+    uri.rsplit('/').next().unwrap_or(uri)
+}
+
+/// Read one message, which has `Content-Length` header. Get None at the end of input.
+/// Body which is not JSON is given as error message, so that the next message can still be read.
+pub fn read_message(input: &mut dyn BufRead) -> io::Result<Option<Result<Json, String>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Content-Length is missing"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    let message = String::from_utf8(body)
+        .map_err(|err| err.to_string())
+        .and_then(|text| Json::parse(&text));
+    Ok(Some(message))
+}
+
+/// Write one message with `Content-Length` header.
+pub fn write_message(out: &mut dyn Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    out.flush()
+}
+
+/// Serve client until it asks the server to exit, or input is closed.
+pub fn serve(input: &mut dyn BufRead, out: &mut dyn Write) -> io::Result<()> {
+    let mut server = Server::new();
+    while let Some(message) = read_message(input)? {
+        let replies = match message {
+            Ok(message) => server.handle(&message),
+            Err(err) => vec![error_response(&Json::Null, -32700, format!("Parse error : {}", err))],
+        };
+        for reply in replies {
+            write_message(out, &reply)?;
+        }
+        if server.is_exited() {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,404 @@
+use std::fmt;
+
+use super::isa::Instruction;
+use super::register::{Registers, SideEffect, TimerSideEffect};
+use super::memory::{Memory, MEMORY_SIZE};
+use super::screen::{Screen, DirtyRows};
+use super::keypad::Keypad;
+use super::timer::CountdownTimers;
+use super::peripheral::{Bus, Display, Input, Timers};
+use super::hook::Hooks;
+use super::idle::IdleLoop;
+use super::state::MachineState;
+use super::quirks::Quirks;
+
+/// Provides the visible output of one executed instruction.
+pub enum Output {
+    /// Nothing to present.
+    None,
+    /// Whole screen is cleared.
+    Cleared,
+    /// Rows of screen are changed.
+    Drawn(DirtyRows),
+}
+
+/// Provides the reason why machine could not proceed.
+#[derive(Debug, PartialEq)]
+pub enum Fault {
+    /// Instruction of given address could not be parsed.
+    InvalidInstruction{ pc: u16 },
+    /// Instruction of given address accesses memory past the end.
+    MemoryOutOfRange{ pc: u16, l: u16 },
+    /// Instruction of given address returns without any subroutine call.
+    StackUnderflow{ pc: u16 },
+    /// Instruction of given address checks key which is not on the keypad.
+    InvalidKey{ pc: u16, key: u8 },
+}
+
+impl Fault {
+    /// Get address of the instruction which faulted.
+    pub fn pc(&self) -> u16 {
+        match *self {
+            Fault::InvalidInstruction{ pc } | Fault::MemoryOutOfRange{ pc, .. }
+            | Fault::StackUnderflow{ pc } | Fault::InvalidKey{ pc, .. } => pc,
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::InvalidInstruction{ pc } => write!(f, "Invalid instruction at 0x{:03X}", pc),
+            Fault::MemoryOutOfRange{ pc, l } => write!(f, "Memory out of range from L 0x{:04X} at 0x{:03X}", l, pc),
+            Fault::StackUnderflow{ pc } => write!(f, "Stack underflow at 0x{:03X}", pc),
+            Fault::InvalidKey{ pc, key } => write!(f, "Invalid key 0x{:02X} at 0x{:03X}", key, pc),
+        }
+    }
+}
+
+/// Provides whole CHIP-8 machine which does not depend on any rendering device.
+/// Instructions are processed by `step`, and timers by `tick_timers` in 60Hz.
+///
+/// Memory, display, keypad and timers are peripherals behind `Bus`, `Display`, `Input` and `Timers`,
+/// and `Memory`, `Screen`, `Keypad` and `CountdownTimers` are used by default.
+/// Tooling callbacks are given by `H`, and no hooks are called by default.
+#[derive(Clone)]
+pub struct Machine<B: Bus = Memory, D: Display = Screen, I: Input = Keypad, T: Timers = CountdownTimers, H: Hooks = ()> {
+    bus: B,
+    registers: Registers,
+    display: D,
+    input: I,
+    timers: T,
+    hooks: H,
+    state: MachineState,
+    quirks: Quirks,
+    cycles: u64,
+    frames: u64,
+    beeping: bool,
+    idle: IdleLoop,
+}
+
+impl Machine {
+    /// Create new machine with given program bytes and quirks.
+    pub fn new(rom: &[u8], quirks: Quirks) -> Machine {
+        Machine::from_memory(Memory::from_rom(rom), quirks)
+    }
+
+    /// Create new machine with already loaded memory.
+    pub fn from_memory(memory: Memory, quirks: Quirks) -> Machine {
+        Machine::with_peripherals(memory, Screen::new(), Keypad::new(), CountdownTimers::new(), quirks)
+    }
+
+    pub fn memory(&self) -> &Memory { &self.bus }
+
+    pub fn screen(&self) -> &Screen { &self.display }
+}
+
+impl<B: Bus, D: Display, I: Input, T: Timers> Machine<B, D, I, T> {
+    /// Create new machine with given peripherals.
+    /// Program must be already loaded into `bus`.
+    pub fn with_peripherals(bus: B, display: D, input: I, timers: T, quirks: Quirks) -> Machine<B, D, I, T> {
+        Machine {
+            bus,
+            registers: Registers::new(),
+            display,
+            input,
+            timers,
+            hooks: (),
+            state: MachineState::Normal,
+            quirks,
+            cycles: 0,
+            frames: 0,
+            beeping: false,
+            idle: IdleLoop::default(),
+        }
+    }
+}
+
+impl<B: Bus, D: Display, I: Input, T: Timers, H: Hooks> Machine<B, D, I, T, H> {
+    /// Replace hooks of machine with given hooks.
+    pub fn with_hooks<N: Hooks>(self, hooks: N) -> Machine<B, D, I, T, N> {
+        Machine {
+            bus: self.bus,
+            registers: self.registers,
+            display: self.display,
+            input: self.input,
+            timers: self.timers,
+            hooks,
+            state: self.state,
+            quirks: self.quirks,
+            cycles: self.cycles,
+            frames: self.frames,
+            beeping: self.beeping,
+            idle: self.idle,
+        }
+    }
+
+    pub fn hooks(&self) -> &H { &self.hooks }
+
+    /// Get registers to be updated by native code, which processed `cycles` instructions.
+    pub(crate) fn native_registers(&mut self, cycles: u64) -> &mut Registers {
+        self.cycles += cycles;
+        self.idle.reset();
+        &mut self.registers
+    }
+
+    pub fn hooks_mut(&mut self) -> &mut H { &mut self.hooks }
+
+    pub fn bus(&self) -> &B { &self.bus }
+
+    pub fn bus_mut(&mut self) -> &mut B {
+        self.idle.reset();
+        &mut self.bus
+    }
+
+    pub fn registers(&self) -> &Registers { &self.registers }
+
+    /// Reset random number generator of machine with given seed.
+    pub fn seed_random(&mut self, seed: u64) {
+        self.registers.seed_random(seed);
+    }
+
+    pub fn display(&self) -> &D { &self.display }
+
+    pub fn input(&self) -> &I { &self.input }
+
+    pub fn timers(&self) -> &T { &self.timers }
+
+    pub fn state(&self) -> &MachineState { &self.state }
+
+    pub fn quirks(&self) -> &Quirks { &self.quirks }
+
+    /// Get count of instructions processed so far.
+    pub fn cycles(&self) -> u64 { self.cycles }
+
+    /// Get count of 60Hz frames processed so far.
+    pub fn frames(&self) -> u64 { self.frames }
+
+    /// Check whether machine can not change until key input or the next frame.
+    /// It is true while waiting for key press or display, or while spinning in idle loop.
+    pub fn is_idle(&self) -> bool {
+        self.state != MachineState::Normal || self.idle.period().is_some()
+    }
+
+    /// Press given key.
+    /// If machine is waiting for key press, pressed key is stored and machine is resumed.
+    pub fn press_key(&mut self, key: u8) {
+        self.input.press(key);
+        self.idle.reset();
+
+        if let MachineState::WaitKeyPress{ r } = self.state {
+            self.registers.set_general_register(r, key);
+            self.state = MachineState::Normal;
+        }
+    }
+
+    /// Release given key.
+    pub fn release_key(&mut self, key: u8) {
+        if self.input.is_pressed(key) {
+            self.idle.reset();
+        }
+        self.input.release(key);
+    }
+
+    /// Release all keys.
+    pub fn release_all_keys(&mut self) {
+        if (0..16).any(|key| self.input.is_pressed(key)) {
+            self.idle.reset();
+        }
+        self.input.release_all();
+    }
+
+    /// Process one instruction.
+    /// If machine is waiting for something, nothing is processed.
+    pub fn step(&mut self) -> Result<Output, Fault> {
+        if self.state != MachineState::Normal {
+            return Ok(Output::None);
+        }
+
+        // Parse instruction and process.
+        let pc = self.registers.get_pc();
+        let instruction = match self.bus.fetch(pc) {
+            Some(instruction) => instruction,
+            None => return Err(Fault::InvalidInstruction{ pc }),
+        };
+        self.cycles += 1;
+        let is_idle = self.idle.before(&instruction, &self.registers);
+        if self.hooks.is_active() {
+            self.hooks.before_instruction(pc, &instruction, &self.registers);
+        }
+
+        // Update register with instruction, and process consequential side effects.
+        // Memory accesses going past the end, return without call and keys past 0xF are faults, instead of panics.
+        if instruction == Instruction::ReturnSubroutine && self.registers.stack().is_empty() {
+            return Err(Fault::StackUnderflow{ pc });
+        }
+        let out_of_range = |l: u16, count: usize| (l as usize) + count > MEMORY_SIZE;
+        let output = match self.registers.update_registers(instruction, &self.quirks) {
+            Some(SideEffect::Draw{ n, l, .. }) if out_of_range(l, (n as usize).max(1)) => {
+                return Err(Fault::MemoryOutOfRange{ pc, l });
+            },
+            Some(SideEffect::MemDump{ count, l, .. }) if out_of_range(l, count as usize) => {
+                return Err(Fault::MemoryOutOfRange{ pc, l });
+            },
+            Some(SideEffect::MemRead{ count, l }) if out_of_range(l, count as usize) => {
+                return Err(Fault::MemoryOutOfRange{ pc, l });
+            },
+            Some(SideEffect::CheckKeyPressed{ key }) | Some(SideEffect::CheckKeyReleased{ key }) if key > 0xF => {
+                return Err(Fault::InvalidKey{ pc, key });
+            },
+            Some(SideEffect::ClearDisplay) => {
+                self.display.clear();
+                Output::Cleared
+            },
+            Some(SideEffect::Draw{ pos, n, l: addr }) => {
+                // Update screen buffer and get dirty rows to update window buffer.
+                // New carry flag value will be returned.
+                let mut sprite = [0u8; 16];
+                let sprite = &mut sprite[..n as usize];
+                self.bus.read_into(addr, sprite);
+                let (dirty_rows, is_any_erased) = self.display.draw(pos, sprite, self.quirks.clip_sprites);
+                if self.hooks.is_active() {
+                    self.hooks.on_draw(pos, sprite, is_any_erased);
+                }
+
+                // Update VF (carry & borrow flag)
+                self.registers.update_vf(is_any_erased);
+
+                if self.quirks.display_wait {
+                    self.state = MachineState::WaitDisplay;
+                }
+                Output::Drawn(dirty_rows)
+            },
+            Some(SideEffect::MemDump{ dump_vals, count, l }) => {
+                let dump_vals = &dump_vals[..count as usize];
+                if self.hooks.is_active() {
+                    for (addr, &val) in (l..).zip(dump_vals.iter()) {
+                        self.hooks.on_memory_write(addr, self.bus.read(addr), val);
+                    }
+                }
+                self.bus.write_from(l, dump_vals);
+                Output::None
+            },
+            Some(SideEffect::MemRead{ count, l }) => {
+                // First, get values from memory [l, l + count)
+                // Second, store from v0 to v0 + (count - 1).
+                let mut vals = [0u8; 16];
+                let vals = &mut vals[..count as usize];
+                self.bus.read_into(l, vals);
+                self.registers.store_from_v0(vals);
+                Output::None
+            },
+            Some(SideEffect::WaitKeyPress{ r }) => {
+                // Let machine wait for new key press.
+                self.state = MachineState::WaitKeyPress{ r };
+                if self.hooks.is_active() {
+                    self.hooks.on_key_wait(r);
+                }
+                Output::None
+            },
+            Some(SideEffect::CheckKeyPressed{ key }) => {
+                match self.input.is_pressed(key) {
+                    true => self.registers.increase_pc(2),
+                    false => self.registers.increase_pc(1),
+                }
+                Output::None
+            },
+            Some(SideEffect::CheckKeyReleased{ key }) => {
+                match self.input.is_pressed(key) {
+                    false => self.registers.increase_pc(2),
+                    true => self.registers.increase_pc(1),
+                }
+                Output::None
+            },
+            Some(SideEffect::ReadDelay{ r }) => {
+                self.registers.set_general_register(r, self.timers.delay());
+                Output::None
+            },
+            Some(SideEffect::SetDelay{ val }) => {
+                self.timers.set_delay(val);
+                Output::None
+            },
+            Some(SideEffect::SetSound{ val }) => {
+                self.timers.set_sound(val);
+                Output::None
+            },
+            None => Output::None,
+        };
+
+        if is_idle {
+            self.idle.after(&self.registers);
+        }
+        if self.hooks.is_active() {
+            self.hooks.after_instruction(pc, &instruction, &self.registers);
+        }
+        Ok(output)
+    }
+
+    /// Process one instruction as `step` does, but while machine spins in idle loop, keep processing
+    /// at most `budget` instructions and skip whole iterations at once, only counting cycles.
+    /// So machine ends in the same state as the same count of `step` calls would, even if caller
+    /// changes input between calls. Returns early at the first instruction with visible output.
+    /// Machine with active hooks processes one instruction, so that every callback is called.
+    pub fn step_fast(&mut self, budget: u64) -> Result<Output, Fault> {
+        if self.hooks.is_active() {
+            return self.step();
+        }
+
+        let mut remaining = budget;
+        while remaining > 0 && self.state == MachineState::Normal {
+            if let Some(period) = self.idle.period() {
+                let skipped = remaining / period * period;
+                self.cycles += skipped;
+                remaining -= skipped;
+                if remaining == 0 {
+                    break;
+                }
+            }
+
+            let cycles = self.cycles;
+            let output = self.step()?;
+            if !matches!(output, Output::None) {
+                return Ok(output);
+            }
+            remaining -= (self.cycles - cycles).min(remaining);
+            if self.idle.period().is_none() {
+                break;
+            }
+        }
+        Ok(Output::None)
+    }
+
+    /// Process delay / sound timer decreasement. Must be called in 60Hz.
+    /// Unlike instruction processing, timer is processed even when machine is waiting.
+    pub fn tick_timers(&mut self) -> TimerSideEffect {
+        self.idle.reset();
+        if self.state == MachineState::WaitDisplay {
+            self.state = MachineState::Normal;
+        }
+        self.frames += 1;
+
+        let side_effect = self.timers.tick();
+        if self.hooks.is_active() {
+            self.hooks.on_frame(self.frames);
+            let beeping = side_effect == TimerSideEffect::Beep;
+            if beeping != self.beeping {
+                self.hooks.on_beep(beeping);
+            }
+        }
+        self.beeping = side_effect == TimerSideEffect::Beep;
+        side_effect
+    }
+
+    /// Process one 60Hz frame which consists of `cycles` instructions and timer update.
+    /// Idle loop is skipped by `step_fast`.
+    pub fn run_frame(&mut self, cycles: usize) -> Result<TimerSideEffect, Fault> {
+        let end = self.cycles + cycles as u64;
+        // Waiting machine processes nothing until the end of frame.
+        while self.cycles < end && self.state == MachineState::Normal {
+            self.step_fast(end - self.cycles)?;
+        }
+
+        Ok(self.tick_timers())
+    }
+}
@@ -1,15 +1,62 @@
 use std::fs;
 use std::io::Read;
+use std::cell::Cell;
 use super::isa;
+use super::peripheral::Bus;
+
+extern crate rand;
+
+/// Byte size of whole memory.
+pub const MEMORY_SIZE: usize = 4 << 10;
+/// Byte size of default font data placed from address 0.
+pub const FONT_SIZE: usize = 16 * 5;
+/// Address where program is placed.
+pub const PROGRAM_START: usize = 0x200;
+
+/// Provides decoded instruction cached for one address.
+#[derive(Debug, Clone, Copy)]
+enum Decoded {
+    /// Not decoded yet, or invalidated by write.
+    Unknown,
+    /// Bytes can not be parsed.
+    Invalid,
+    Valid(isa::Instruction),
+}
 
+/// Provides 4KiB memory of CHIP-8.
+/// Instructions are decoded once per address and cached until bytes of the address are written.
+#[derive(Clone)]
 pub struct Memory {
     memory: Vec<u8>,
+    decoded: Vec<Cell<Decoded>>,
 }
 
 impl Memory {
     pub fn new(valid_file_path: &str) -> Option<Memory> {
-        let mut memory = Vec::<u8>::new();
-        memory.resize(4 << 10, 0);
+        // Read file.
+        let mut file = {
+            if let Ok(file) = fs::File::open(valid_file_path) {
+                file
+            } else {
+                println!("Unexpected error occurred.");
+                return None;
+            }
+        };
+
+        // Copy data (instruction & data) into vec.
+        let mut data_buffer = Vec::<u8>::new();
+        match file.read_to_end(&mut data_buffer) {
+            Ok(_) => (),
+            Err(_) => return None,
+        }
+
+        Some(Memory::from_rom(&data_buffer))
+    }
+
+    /// Create memory from given program bytes, which are placed from 0x200.
+    /// Bytes exceeding 4KiB memory are discarded.
+    pub fn from_rom(rom: &[u8]) -> Memory {
+        let mut memory = vec![0u8; MEMORY_SIZE];
 
         // Set default font data into initial memory.
         let font_pack = 
@@ -35,35 +82,36 @@ impl Memory {
         };
         for (t, r) in memory.iter_mut().zip(font_pack.iter()) { *t = *r; }
 
-        // Set file memory.
-        // Read file.
-        let mut file = {
-            if let Ok(file) = fs::File::open(valid_file_path) {
-                file
-            } else {
-                println!("Unexpected error occurred.");
-                return None;
-            }
-        };
-
-        // Copy data (instruction & data) into vec.
-        let mut data_buffer = Vec::<u8>::new();
-        match file.read_to_end(&mut data_buffer) {
-            Ok(_) => (),
-            Err(_) => return None,
-        }
-
         // Copy to 0x512~ of memory (to 4KiB)
-        for (t, r) in memory.iter_mut().skip(0x200).zip(data_buffer.iter_mut()) {
+        for (t, r) in memory.iter_mut().skip(0x200).zip(rom.iter()) {
             *t = *r;
         }
 
-        // Return
-        Some(Memory { memory })
+        Memory { memory, decoded: vec![Cell::new(Decoded::Unknown); MEMORY_SIZE] }
     }
 
-    ///
-    #[allow(dead_code)]
+    /// Invalidate cached instructions overlapping with [from, from + count).
+    fn invalidate(&self, from: usize, count: usize) {
+        if count == 0 { return; }
+        for decoded in &self.decoded[from.saturating_sub(1)..(from + count).min(MEMORY_SIZE)] {
+            decoded.set(Decoded::Unknown);
+        }
+    }
+
+    /// Fill memory outside of font and program of given length with random values,
+    /// which simulates power-on state of real hardware.
+    pub fn randomize_free(&mut self, rom_len: usize) {
+        let rom_end = PROGRAM_START + rom_len;
+        for (addr, byte) in self.memory.iter_mut().enumerate() {
+            if (FONT_SIZE..PROGRAM_START).contains(&addr) || addr >= rom_end {
+                *byte = rand::random::<u8>();
+            }
+        }
+        self.invalidate(0, MEMORY_SIZE);
+    }
+
+    /// Print all memory values as hexadecimal dump.
+    #[allow(dead_code, clippy::manual_is_multiple_of)]
     pub fn print_memory_dump(&self) {
         enum InstructionState { Left, Right, }
         let mut instruction: [u8; 2] = [0, 0];
@@ -96,29 +144,59 @@ impl Memory {
         // Check out of range exception.
         if (addr + 1) as usize >= self.memory.len() { return None; } 
 
-        // Parse instruction.
+        // Use cached instruction if exists.
         let addr = addr as usize;
-        let bytes: [u8; 2] = [self.memory[addr], self.memory[addr + 1]];
+        match self.decoded[addr].get() {
+            Decoded::Valid(instruction) => return Some(instruction),
+            Decoded::Invalid => return None,
+            Decoded::Unknown => (),
+        }
 
-        isa::parse_instruction(&bytes)
+        // Parse instruction.
+        let bytes: [u8; 2] = [self.memory[addr], self.memory[addr + 1]];
+        let instruction = isa::parse_instruction(&bytes);
+        self.decoded[addr].set(instruction.map_or(Decoded::Invalid, Decoded::Valid));
+        instruction
     }
 
-    pub fn get_data_bytes(&self, addr: usize, count: usize) -> Vec<u8> {
-        assert!(addr < (4usize << 10));
+    /// Get `count` bytes from `addr`. Bytes past the end of memory are not included.
+    pub fn get_data_bytes(&self, addr: usize, count: usize) -> &[u8] {
+        assert!(addr < MEMORY_SIZE);
 
-        self.memory.iter()
-            .skip(addr)
-            .take(count)
-            .map(|&x| x)
-            .collect()
+        &self.memory[addr..(addr + count).min(MEMORY_SIZE)]
     }
 
-    pub fn store_from(&mut self, dump_vals: &[u8], mut l: u16 ) {
-        for &val in dump_vals {
-            assert!(l < (4 << 10));
-            self.memory[l as usize] = val;
-            l += 1;
-        }
+    pub fn store_from(&mut self, dump_vals: &[u8], l: u16) {
+        let l = l as usize;
+        assert!(l + dump_vals.len() <= MEMORY_SIZE);
+
+        self.memory[l..l + dump_vals.len()].copy_from_slice(dump_vals);
+        self.invalidate(l, dump_vals.len());
     }
 }
 
+impl Bus for Memory {
+    fn read(&self, addr: u16) -> u8 {
+        self.memory[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.store_from(&[value], addr);
+    }
+
+    fn fetch(&self, pc: u16) -> Option<isa::Instruction> {
+        self.parse_instruction(pc)
+    }
+
+    /// Bytes past the end of memory are read as 0.
+    fn read_into(&self, addr: u16, buffer: &mut [u8]) {
+        let bytes = self.get_data_bytes(addr as usize, buffer.len());
+        let (head, tail) = buffer.split_at_mut(bytes.len());
+        head.copy_from_slice(bytes);
+        tail.fill(0);
+    }
+
+    fn write_from(&mut self, addr: u16, bytes: &[u8]) {
+        self.store_from(bytes, addr);
+    }
+}
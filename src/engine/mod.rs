@@ -6,4 +6,31 @@ pub mod keypad;
 pub mod state;
 pub mod check;
 pub mod device;
-pub mod timer;
\ No newline at end of file
+pub mod frontend;
+pub mod timer;
+pub mod peripheral;
+pub mod quirks;
+pub mod hook;
+mod idle;
+pub mod machine;
+#[cfg(all(feature = "jit", target_arch = "x86_64", unix))]
+pub mod jit;
+pub mod recompile;
+pub mod trace;
+pub mod profile;
+pub mod symbols;
+pub mod coverage;
+pub mod sanitizer;
+pub mod batch;
+pub mod env;
+pub mod replay;
+pub mod search;
+pub mod provenance;
+pub mod cfg;
+pub mod decompile;
+pub mod lint;
+pub mod rom;
+pub mod octo;
+pub mod asm;
+pub mod json;
+pub mod lsp;
@@ -0,0 +1,904 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+};
+
+use super::isa::Instruction;
+use super::memory::{MEMORY_SIZE, PROGRAM_START};
+use super::symbols::SymbolTable;
+
+const VF: u8 = 0xF;
+/// Maximum depth of macro invoked inside of macro, which stops recursive macros.
+const MAX_MACRO_DEPTH: usize = 64;
+
+/// Directives and statement keywords, which are offered as completion of editors.
+pub const KEYWORDS: [&str; 47] = [
+    ":", ":next", ":alias", ":const", ":calc", ":byte", ":org", ":call", ":unpack", ":macro",
+    "return", "clear", "bcd", "save", "load", "sprite", "jump", "jump0", "native", "i", "delay", "buzzer", "pitch",
+    "key", "random", "hex", "bighex", "long", "if", "then", "begin", "else", "end", "loop", "while", "again",
+    "hires", "lores", "exit", "scroll-left", "scroll-right", "scroll-down", "scroll-up", "saveflags", "loadflags",
+    "plane", "audio",
+];
+
+/// Provides ROM compiled from source, with labels and source line of every instruction.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub rom: Vec<u8>,
+    pub symbols: SymbolTable,
+}
+
+/// Provides one whitespace separated token and its source line.
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    text: String,
+    line: usize,
+    /// Count of nested macro expansions which produced token.
+    depth: usize,
+}
+
+/// Provides how address of label is written into ROM once label is defined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Patch {
+    /// Low 12 bits of instruction, such as 0x1nnn.
+    Addr12,
+    /// 16 bit address following 0xF000.
+    Long,
+    /// Second byte of `v0 := (nibble << 4) | addr >> 8` of `:unpack`.
+    UnpackHigh(u8),
+    /// Second byte of `v1 := addr & 0xFF` of `:unpack`.
+    UnpackLow,
+}
+
+#[derive(Debug, Clone)]
+struct Fixup {
+    offset: usize,
+    patch: Patch,
+    label: String,
+    line: usize,
+}
+
+/// Provides operand of instruction taking address, which may be defined later.
+enum Target {
+    Addr(u16),
+    Label(String),
+}
+
+/// Provides control structure which is not closed yet.
+enum Control {
+    /// Start address, and offsets of jumps out of loop by `while`.
+    Loop{ start: u16, breaks: Vec<usize> },
+    /// Offset of jump into `else` or `end`.
+    If{ jump: usize },
+    /// Offset of jump from the end of `if` body over `else` body.
+    Else{ jump: usize },
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// Provides condition of `if` and `while`, as instructions which test it.
+struct Condition {
+    /// Instructions computing VF before the skip, for `<`, `>`, `<=` and `>=`.
+    prelude: Vec<Instruction>,
+    /// Skips the next instruction if condition is true.
+    skip_if_true: Instruction,
+    /// Skips the next instruction if condition is false.
+    skip_if_false: Instruction,
+}
+
+/// Provides operand of register instructions.
+enum Operand {
+    Reg(u8),
+    Byte(u8),
+}
+
+/// Split source into tokens. `#` starts a comment.
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (line_no, line) in source.lines().enumerate() {
+        for text in line.split_whitespace() {
+            if text.starts_with('#') {
+                break;
+            }
+            tokens.push(Token { text: text.to_string(), line: line_no + 1, depth: 0 });
+        }
+    }
+    tokens
+}
+
+/// Parse decimal, `0x` hexadecimal or `0b` binary number, which may be negative.
+fn parse_literal(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn unary(op: &str, value: f64) -> Option<f64> {
+    let result = match op {
+        "-" => -value,
+        "~" => !(value as i64) as f64,
+        "!" => (value == 0.0) as i64 as f64,
+        "floor" => value.floor(),
+        "ceil" => value.ceil(),
+        "abs" => value.abs(),
+        "sqrt" => value.sqrt(),
+        "sin" => value.sin(),
+        "cos" => value.cos(),
+        "tan" => value.tan(),
+        "exp" => value.exp(),
+        "log" => value.ln(),
+        "sign" => value.signum(),
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn binary(op: &str, left: f64, right: f64) -> Option<f64> {
+    let (l, r) = (left as i64, right as i64);
+    let result = match op {
+        "+" => left + right,
+        "-" => left - right,
+        "*" => left * right,
+        "/" => left / right,
+        "%" => left % right,
+        "&" => (l & r) as f64,
+        "|" => (l | r) as f64,
+        "^" => (l ^ r) as f64,
+        "<<" => l.checked_shl(r as u32).unwrap_or(0) as f64,
+        ">>" => l.checked_shr(r as u32).unwrap_or(0) as f64,
+        "pow" => left.powf(right),
+        "min" => left.min(right),
+        "max" => left.max(right),
+        "<" => (left < right) as i64 as f64,
+        ">" => (left > right) as i64 as f64,
+        "<=" => (left <= right) as i64 as f64,
+        ">=" => (left >= right) as i64 as f64,
+        "==" => (left == right) as i64 as f64,
+        "!=" => (left != right) as i64 as f64,
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Provides compiler of one source, whose tokens are consumed from the end.
+struct Compiler {
+    file_name: String,
+    tokens: Vec<Token>,
+    /// Line of the current statement.
+    line: usize,
+    /// Macro expansion depth of the current statement.
+    depth: usize,
+    rom: Vec<u8>,
+    /// Offset from 0x200 which the next byte is written at.
+    here: usize,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    controls: Vec<Control>,
+    symbols: SymbolTable,
+    /// Any byte is emitted.
+    is_started: bool,
+}
+
+impl Compiler {
+    fn new(source: &str, file_name: &str) -> Compiler {
+        let mut tokens = tokenize(source);
+        tokens.reverse();
+        Compiler {
+            file_name: file_name.to_string(),
+            tokens,
+            line: 1,
+            depth: 0,
+            rom: Vec::new(),
+            here: 0,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            controls: Vec::new(),
+            symbols: SymbolTable::new(),
+            is_started: false,
+        }
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, String> {
+        Err(format!("line {} : {}", self.line, message))
+    }
+
+    fn addr(&self) -> u16 {
+        (PROGRAM_START + self.here) as u16
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        match self.tokens.pop() {
+            Some(token) => {
+                self.line = token.line;
+                self.depth = token.depth;
+                Ok(token.text)
+            },
+            None => self.error("unexpected end of source".to_string()),
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.last().map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token != text {
+            return self.error(format!("expected {}, but got {}", text, token));
+        }
+        Ok(())
+    }
+
+    /// Program starts at `main`, so jump into it before the first byte unless `main` is there.
+    fn start(&mut self) -> Result<(), String> {
+        if self.is_started {
+            return Ok(());
+        }
+        self.is_started = true;
+        if self.here != 0 || self.labels.get("main") != Some(&self.addr()) {
+            let here = std::mem::replace(&mut self.here, 0);
+            self.fixups.push(Fixup { offset: 0, patch: Patch::Addr12, label: "main".to_string(), line: self.line });
+            self.emit_instruction(Instruction::JmpAddr(0))?;
+            self.here = here.max(self.here);
+        }
+        Ok(())
+    }
+
+    fn emit_byte(&mut self, byte: u8) -> Result<(), String> {
+        self.start()?;
+        if PROGRAM_START + self.here >= MEMORY_SIZE {
+            return self.error("program is larger than memory".to_string());
+        }
+        if self.here >= self.rom.len() {
+            self.rom.resize(self.here + 1, 0);
+        }
+        self.rom[self.here] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    /// Emit opcode, whose address is mapped to the current line.
+    fn emit(&mut self, word: u16) -> Result<(), String> {
+        self.start()?;
+        self.symbols.add_line(self.addr(), &self.file_name, self.line);
+        let [high, low] = word.to_be_bytes();
+        self.emit_byte(high)?;
+        self.emit_byte(low)
+    }
+
+    fn emit_instruction(&mut self, instruction: Instruction) -> Result<(), String> {
+        self.emit(u16::from_be_bytes(instruction.encode()))
+    }
+
+    /// Emit instruction taking address, which is patched later if target is not defined yet.
+    fn emit_with_target(&mut self, instruction: fn(u16) -> Instruction, target: Target) -> Result<(), String> {
+        match target {
+            Target::Addr(addr) => self.emit_instruction(instruction(addr)),
+            Target::Label(label) => {
+                self.fixups.push(Fixup { offset: self.here, patch: Patch::Addr12, label, line: self.line });
+                self.emit_instruction(instruction(0))
+            },
+        }
+    }
+
+    /// Write 12 bit address into instruction at given offset.
+    fn patch_addr12(&mut self, offset: usize, addr: u16) {
+        self.rom[offset] = (self.rom[offset] & 0xF0) | (addr >> 8) as u8 & 0x0F;
+        self.rom[offset + 1] = addr as u8;
+    }
+
+    /// Define label at given offset from the current address.
+    fn define_label(&mut self, name: &str, offset: u16) -> Result<(), String> {
+        // Jump into `main` goes before any other label.
+        if name != "main" {
+            self.start()?;
+        }
+        let addr = self.addr() + offset;
+        if self.labels.contains_key(name) || self.constants.contains_key(name) {
+            return self.error(format!("name {} is already defined", name));
+        }
+        self.labels.insert(name.to_string(), addr);
+        self.symbols.add_label(addr, name);
+        Ok(())
+    }
+
+    fn register(&self, text: &str) -> Option<u8> {
+        if let Some(&r) = self.aliases.get(text) {
+            return Some(r);
+        }
+        let digit = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    fn expect_register(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        match self.register(&token) {
+            Some(r) => Ok(r),
+            None => self.error(format!("expected register, but got {}", token)),
+        }
+    }
+
+    /// Get value of literal, constant, defined label, or `{ expression }`.
+    fn value_of(&mut self, token: &str) -> Result<Option<f64>, String> {
+        if token == "{" {
+            return self.calc().map(Some);
+        }
+        if let Some(value) = parse_literal(token) {
+            return Ok(Some(value));
+        }
+        if let Some(&value) = self.constants.get(token) {
+            return Ok(Some(value));
+        }
+        Ok(self.labels.get(token).map(|&addr| addr as f64))
+    }
+
+    fn expect_value(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+        match self.value_of(&token)? {
+            Some(value) => Ok(value),
+            None => self.error(format!("undefined name {}", token)),
+        }
+    }
+
+    fn expect_byte(&mut self) -> Result<u8, String> {
+        let value = self.expect_value()? as i64;
+        if !(-128..=255).contains(&value) {
+            return self.error(format!("value {} does not fit in byte", value));
+        }
+        Ok(value as u8)
+    }
+
+    fn expect_nibble(&mut self) -> Result<u8, String> {
+        let value = self.expect_value()? as i64;
+        if !(0..16).contains(&value) {
+            return self.error(format!("value {} does not fit in nibble", value));
+        }
+        Ok(value as u8)
+    }
+
+    /// Get address operand. Names which are not defined yet are labels defined later.
+    fn expect_target(&mut self) -> Result<Target, String> {
+        let token = self.next()?;
+        match self.value_of(&token)? {
+            Some(value) if (0.0..MEMORY_SIZE as f64).contains(&value) => Ok(Target::Addr(value as u16)),
+            Some(value) => self.error(format!("address {} is out of memory", value)),
+            None if self.register(&token).is_some() => self.error(format!("expected address, but got {}", token)),
+            None => Ok(Target::Label(token)),
+        }
+    }
+
+    fn expect_operand(&mut self) -> Result<Operand, String> {
+        if let Some(r) = self.peek().and_then(|token| self.register(token)) {
+            self.next()?;
+            return Ok(Operand::Reg(r));
+        }
+        Ok(Operand::Byte(self.expect_byte()?))
+    }
+
+    /// Evaluate expression right after `{` up to `}`. Operators have no precedence and
+    /// are evaluated from right to left, as in Octo.
+    fn calc(&mut self) -> Result<f64, String> {
+        let value = self.calc_expression()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+
+    fn calc_expression(&mut self) -> Result<f64, String> {
+        let left = self.calc_term()?;
+        match self.peek() {
+            Some(op) if binary(op, 0.0, 0.0).is_some() => {
+                let op = self.next()?;
+                let right = self.calc_expression()?;
+                Ok(binary(&op, left, right).unwrap())
+            },
+            _ => Ok(left),
+        }
+    }
+
+    fn calc_term(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+        if token == "(" {
+            let value = self.calc_expression()?;
+            self.expect(")")?;
+            return Ok(value);
+        }
+        if token == "HERE" {
+            return Ok(self.addr() as f64);
+        }
+        if unary(&token, 0.0).is_some() && parse_literal(&token).is_none() {
+            let value = self.calc_term()?;
+            return Ok(unary(&token, value).unwrap());
+        }
+        match self.value_of(&token)? {
+            Some(value) => Ok(value),
+            None => self.error(format!("undefined name {}", token)),
+        }
+    }
+
+    /// Parse condition of `if` or `while`. Comparisons other than `==` and `!=` use VF.
+    fn condition(&mut self) -> Result<Condition, String> {
+        type Inst = Instruction;
+        let r = self.expect_register()?;
+        let op = self.next()?;
+        let condition = |skip_if_true, skip_if_false| Condition { prelude: vec![], skip_if_true, skip_if_false };
+        match op.as_str() {
+            "key" => return Ok(condition(Inst::SkipKeyPressed{ r }, Inst::SkipKeyReleased{ r })),
+            "-key" => return Ok(condition(Inst::SkipKeyReleased{ r }, Inst::SkipKeyPressed{ r })),
+            _ => (),
+        }
+        let operand = self.expect_operand()?;
+        match (op.as_str(), &operand) {
+            ("==", &Operand::Reg(f)) => return Ok(condition(Inst::SkipRegEq{ r, f }, Inst::SkipRegNeq{ r, f })),
+            ("==", &Operand::Byte(val)) => return Ok(condition(Inst::SkipEq{ r, val }, Inst::SkipNeq{ r, val })),
+            ("!=", &Operand::Reg(f)) => return Ok(condition(Inst::SkipRegNeq{ r, f }, Inst::SkipRegEq{ r, f })),
+            ("!=", &Operand::Byte(val)) => return Ok(condition(Inst::SkipNeq{ r, val }, Inst::SkipEq{ r, val })),
+            _ => (),
+        }
+
+        // VF becomes 1 if left >= right. Constant is loaded into VF, and register is subtracted.
+        let left = Operand::Reg(r);
+        let (left, right, is_true_if_ge) = match op.as_str() {
+            ">=" => (left, operand, true),
+            "<" => (left, operand, false),
+            "<=" => (operand, left, true),
+            ">" => (operand, left, false),
+            _ => return self.error(format!("unknown comparison {}", op)),
+        };
+        let prelude = match (left, right) {
+            (left, Operand::Reg(right)) => {
+                let load = match left {
+                    Operand::Reg(left) => Inst::SetRegV{ r: VF, f: left },
+                    Operand::Byte(left) => Inst::SetByte{ r: VF, val: left },
+                };
+                vec![load, Inst::SubRegV{ r: VF, f: right }]
+            },
+            (Operand::Reg(left), Operand::Byte(right)) => vec![Inst::SetByte{ r: VF, val: right }, Inst::SubNRegV{ r: VF, f: left }],
+            (Operand::Byte(_), Operand::Byte(_)) => unreachable!(),
+        };
+        let (is_one, is_zero) = (Inst::SkipNeq{ r: VF, val: 0 }, Inst::SkipEq{ r: VF, val: 0 });
+        Ok(if is_true_if_ge {
+            Condition { prelude, skip_if_true: is_one, skip_if_false: is_zero }
+        } else {
+            Condition { prelude, skip_if_true: is_zero, skip_if_false: is_one }
+        })
+    }
+
+    /// Emit prelude of condition, and jump which is taken if condition is false. Get offset of the jump.
+    fn emit_branch(&mut self, condition: Condition) -> Result<usize, String> {
+        for instruction in condition.prelude {
+            self.emit_instruction(instruction)?;
+        }
+        self.emit_instruction(condition.skip_if_true)?;
+        let offset = self.here;
+        self.emit_instruction(Instruction::JmpAddr(0))?;
+        Ok(offset)
+    }
+
+    /// Compile statement of register, such as `v0 += 1`.
+    fn register_statement(&mut self, r: u8) -> Result<(), String> {
+        type Inst = Instruction;
+        let op = self.next()?;
+        let inst = match op.as_str() {
+            ":=" => match self.peek() {
+                Some("delay") => { self.next()?; Inst::SetDelayToReg{ r } },
+                Some("key") => { self.next()?; Inst::WaitKeyPress{ r } },
+                Some("random") => { self.next()?; Inst::RndAnd{ r, val: self.expect_byte()? } },
+                _ => match self.expect_operand()? {
+                    Operand::Reg(f) => Inst::SetRegV{ r, f },
+                    Operand::Byte(val) => Inst::SetByte{ r, val },
+                },
+            },
+            "+=" => match self.expect_operand()? {
+                Operand::Reg(f) => Inst::AddRegV{ r, f },
+                Operand::Byte(val) => Inst::AddByte{ r, val },
+            },
+            "-=" => match self.expect_operand()? {
+                Operand::Reg(f) => Inst::SubRegV{ r, f },
+                Operand::Byte(val) => Inst::AddByte{ r, val: val.wrapping_neg() },
+            },
+            "=-" => Inst::SubNRegV{ r, f: self.expect_register()? },
+            "|=" => Inst::OrRegV{ r, f: self.expect_register()? },
+            "&=" => Inst::AndRegV{ r, f: self.expect_register()? },
+            "^=" => Inst::XorRegV{ r, f: self.expect_register()? },
+            ">>=" => Inst::ShrRegV{ r, f: self.expect_register()? },
+            "<<=" => Inst::ShlRegV{ r, f: self.expect_register()? },
+            _ => return self.error(format!("unknown operator {}", op)),
+        };
+        self.emit_instruction(inst)
+    }
+
+    /// Compile statement of I, such as `i := sprite` and `i += v0`.
+    fn index_statement(&mut self) -> Result<(), String> {
+        let op = self.next()?;
+        match op.as_str() {
+            "+=" => {
+                let r = self.expect_register()?;
+                self.emit_instruction(Instruction::AddRegL{ r })
+            },
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.next()?;
+                    let r = self.expect_register()?;
+                    self.emit_instruction(Instruction::SetRegLFontAddrFromReg{ r })
+                },
+                Some("bighex") => {
+                    self.next()?;
+                    let r = self.expect_register()? as u16;
+                    self.emit(0xF030 | r << 8)
+                },
+                Some("long") => {
+                    self.next()?;
+                    self.emit(0xF000)?;
+                    match self.expect_target()? {
+                        Target::Addr(addr) => self.emit(addr),
+                        Target::Label(label) => {
+                            self.fixups.push(Fixup { offset: self.here, patch: Patch::Long, label, line: self.line });
+                            self.emit(0)
+                        },
+                    }
+                },
+                _ => {
+                    let target = self.expect_target()?;
+                    self.emit_with_target(Instruction::SetRegL, target)
+                },
+            },
+            _ => self.error(format!("unknown operator {}", op)),
+        }
+    }
+
+    /// Compile `save` and `load`, which take range of registers on XO-CHIP.
+    fn memory_statement(&mut self, is_save: bool) -> Result<(), String> {
+        let r = self.expect_register()?;
+        if self.peek() == Some("-") {
+            self.next()?;
+            let f = self.expect_register()? as u16;
+            let kind = if is_save { 0x2 } else { 0x3 };
+            return self.emit(0x5000 | (r as u16) << 8 | f << 4 | kind);
+        }
+        self.emit_instruction(if is_save { Instruction::MemDump{ endr: r } } else { Instruction::MemRead{ endr: r } })
+    }
+
+    fn directive(&mut self, directive: &str) -> Result<(), String> {
+        match directive {
+            ":" => {
+                let name = self.next()?;
+                self.define_label(&name, 0)
+            },
+            ":next" => {
+                let name = self.next()?;
+                self.define_label(&name, 1)
+            },
+            ":alias" => {
+                let name = self.next()?;
+                let r = self.expect_register()?;
+                self.aliases.insert(name, r);
+                Ok(())
+            },
+            ":const" => {
+                let name = self.next()?;
+                let value = self.expect_value()?;
+                self.constants.insert(name, value);
+                Ok(())
+            },
+            ":calc" => {
+                let name = self.next()?;
+                self.expect("{")?;
+                let value = self.calc()?;
+                self.constants.insert(name, value);
+                Ok(())
+            },
+            ":byte" => {
+                let byte = self.expect_byte()?;
+                self.emit_byte(byte)
+            },
+            ":org" => {
+                let addr = self.expect_value()? as i64;
+                if addr < PROGRAM_START as i64 || addr >= MEMORY_SIZE as i64 {
+                    return self.error(format!("address {} is out of program", addr));
+                }
+                self.here = addr as usize - PROGRAM_START;
+                Ok(())
+            },
+            ":call" => {
+                let target = self.expect_target()?;
+                self.emit_with_target(Instruction::CallSub, target)
+            },
+            ":unpack" => {
+                let nibble = self.expect_nibble()?;
+                let (high, low) = match self.expect_target()? {
+                    Target::Addr(addr) => ((nibble << 4) | (addr >> 8) as u8, addr as u8),
+                    Target::Label(label) => {
+                        self.fixups.push(Fixup { offset: self.here + 1, patch: Patch::UnpackHigh(nibble), label: label.clone(), line: self.line });
+                        self.fixups.push(Fixup { offset: self.here + 3, patch: Patch::UnpackLow, label, line: self.line });
+                        (0, 0)
+                    },
+                };
+                self.emit_instruction(Instruction::SetByte{ r: 0, val: high })?;
+                self.emit_instruction(Instruction::SetByte{ r: 1, val: low })
+            },
+            ":macro" => {
+                let name = self.next()?;
+                let mut params = Vec::new();
+                while self.peek() != Some("{") {
+                    params.push(self.next()?);
+                }
+                self.next()?;
+                let mut body = Vec::new();
+                let mut depth = 1;
+                loop {
+                    let token = self.tokens.pop().ok_or_else(|| format!("line {} : macro {} is not closed", self.line, name))?;
+                    match token.text.as_str() {
+                        "{" => depth += 1,
+                        "}" => depth -= 1,
+                        _ => (),
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    body.push(token);
+                }
+                self.macros.insert(name, Macro { params, body });
+                Ok(())
+            },
+            ":proto" | ":breakpoint" => {
+                self.next()?;
+                Ok(())
+            },
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+                Ok(())
+            },
+            _ => self.error(format!("unsupported directive {}", directive)),
+        }
+    }
+
+    /// Replace invocation of macro with its body, whose parameters are replaced with arguments.
+    fn expand(&mut self, name: &str) -> Result<(), String> {
+        let depth = self.depth + 1;
+        if depth > MAX_MACRO_DEPTH {
+            return self.error(format!("macro {} is nested too deeply", name));
+        }
+        let count = self.macros[name].params.len();
+        let mut args = Vec::with_capacity(count);
+        for _ in 0..count {
+            args.push(self.next()?);
+        }
+        let definition = &self.macros[name];
+        let body: Vec<Token> = definition.body.iter()
+            .map(|token| match definition.params.iter().position(|param| *param == token.text) {
+                Some(index) => Token { text: args[index].clone(), line: token.line, depth },
+                None => Token { depth, ..token.clone() },
+            })
+            .collect();
+        self.tokens.extend(body.into_iter().rev());
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        type Inst = Instruction;
+        let token = self.next()?;
+        if token.starts_with(':') {
+            return self.directive(&token);
+        }
+        if self.macros.contains_key(&token) {
+            return self.expand(&token);
+        }
+        if let Some(r) = self.register(&token) {
+            return self.register_statement(r);
+        }
+
+        match token.as_str() {
+            ";" | "return" => self.emit_instruction(Inst::ReturnSubroutine),
+            "clear" => self.emit_instruction(Inst::ClearDisplay),
+            "bcd" => {
+                let r = self.expect_register()?;
+                self.emit_instruction(Inst::MemDumpBcdFromReg{ r })
+            },
+            "save" => self.memory_statement(true),
+            "load" => self.memory_statement(false),
+            "sprite" => {
+                let (x, y) = (self.expect_register()?, self.expect_register()?);
+                let n = self.expect_nibble()?;
+                self.emit_instruction(Inst::DispSpr{ rp: (x, y), n })
+            },
+            "jump" => {
+                let target = self.expect_target()?;
+                self.emit_with_target(Inst::JmpAddr, target)
+            },
+            "jump0" => {
+                let target = self.expect_target()?;
+                self.emit_with_target(Inst::JmpAddrOffReg0, target)
+            },
+            "native" => {
+                match self.expect_target()? {
+                    Target::Addr(addr) => self.emit(addr & 0x0FFF),
+                    Target::Label(label) => {
+                        self.fixups.push(Fixup { offset: self.here, patch: Patch::Addr12, label, line: self.line });
+                        self.emit(0)
+                    },
+                }
+            },
+            "i" => self.index_statement(),
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let r = self.expect_register()?;
+                match token.as_str() {
+                    "delay" => self.emit_instruction(Inst::SetDelayFromReg{ r }),
+                    "buzzer" => self.emit_instruction(Inst::SetSoundFromReg{ r }),
+                    _ => self.emit(0xF03A | (r as u16) << 8),
+                }
+            },
+            "if" => {
+                let condition = self.condition()?;
+                match self.next()?.as_str() {
+                    "then" => {
+                        for instruction in &condition.prelude {
+                            self.emit_instruction(*instruction)?;
+                        }
+                        self.emit_instruction(condition.skip_if_false)
+                    },
+                    "begin" => {
+                        let jump = self.emit_branch(condition)?;
+                        self.controls.push(Control::If{ jump });
+                        Ok(())
+                    },
+                    other => self.error(format!("expected then or begin, but got {}", other)),
+                }
+            },
+            "else" => match self.controls.pop() {
+                Some(Control::If{ jump }) => {
+                    let end_jump = self.here;
+                    self.emit_instruction(Inst::JmpAddr(0))?;
+                    self.patch_addr12(jump, self.addr());
+                    self.controls.push(Control::Else{ jump: end_jump });
+                    Ok(())
+                },
+                _ => self.error("else without if".to_string()),
+            },
+            "end" => match self.controls.pop() {
+                Some(Control::If{ jump }) | Some(Control::Else{ jump }) => {
+                    self.patch_addr12(jump, self.addr());
+                    Ok(())
+                },
+                _ => self.error("end without if".to_string()),
+            },
+            "loop" => {
+                self.controls.push(Control::Loop{ start: self.addr(), breaks: Vec::new() });
+                Ok(())
+            },
+            "while" => {
+                let condition = self.condition()?;
+                let jump = self.emit_branch(condition)?;
+                match self.controls.iter_mut().rev().find_map(|control| match control {
+                    Control::Loop{ breaks, .. } => Some(breaks),
+                    _ => None,
+                }) {
+                    Some(breaks) => {
+                        breaks.push(jump);
+                        Ok(())
+                    },
+                    None => self.error("while without loop".to_string()),
+                }
+            },
+            "again" => match self.controls.pop() {
+                Some(Control::Loop{ start, breaks }) => {
+                    self.emit_instruction(Inst::JmpAddr(start))?;
+                    for jump in breaks {
+                        self.patch_addr12(jump, self.addr());
+                    }
+                    Ok(())
+                },
+                _ => self.error("again without loop".to_string()),
+            },
+            // SUPER-CHIP and XO-CHIP instructions.
+            "hires" => self.emit(0x00FF),
+            "lores" => self.emit(0x00FE),
+            "exit" => self.emit(0x00FD),
+            "scroll-left" => self.emit(0x00FC),
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-down" => {
+                let n = self.expect_nibble()? as u16;
+                self.emit(0x00C0 | n)
+            },
+            "scroll-up" => {
+                let n = self.expect_nibble()? as u16;
+                self.emit(0x00D0 | n)
+            },
+            "saveflags" | "loadflags" => {
+                let r = self.expect_register()? as u16;
+                self.emit(if token == "saveflags" { 0xF075 } else { 0xF085 } | r << 8)
+            },
+            "plane" => {
+                let n = self.expect_nibble()? as u16;
+                self.emit(0xF001 | n << 8)
+            },
+            "audio" => self.emit(0xF002),
+            _ => {
+                // Numbers are data, and other names are calls of labels.
+                if let Some(value) = self.value_of_data(&token)? {
+                    return self.emit_byte(value);
+                }
+                self.emit_with_target(Inst::CallSub, Target::Label(token))
+            },
+        }
+    }
+
+    /// Get byte of data statement, which is literal or constant.
+    fn value_of_data(&mut self, token: &str) -> Result<Option<u8>, String> {
+        let value = match parse_literal(token).or_else(|| self.constants.get(token).copied()) {
+            Some(value) => value as i64,
+            None => return Ok(None),
+        };
+        if !(-128..=255).contains(&value) {
+            return self.error(format!("value {} does not fit in byte", value));
+        }
+        Ok(Some(value as u8))
+    }
+
+    fn compile(mut self) -> Result<Program, String> {
+        while !self.tokens.is_empty() {
+            self.statement()?;
+        }
+        if let Some(control) = self.controls.last() {
+            let name = match control {
+                Control::Loop{ .. } => "loop",
+                Control::If{ .. } | Control::Else{ .. } => "if",
+            };
+            return self.error(format!("{} is not closed", name));
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let addr = match self.labels.get(&fixup.label) {
+                Some(&addr) => addr,
+                None => return Err(format!("line {} : undefined name {}", fixup.line, fixup.label)),
+            };
+            match fixup.patch {
+                Patch::Addr12 => self.patch_addr12(fixup.offset, addr),
+                Patch::Long => self.rom[fixup.offset..fixup.offset + 2].copy_from_slice(&addr.to_be_bytes()),
+                Patch::UnpackHigh(nibble) => self.rom[fixup.offset] = (nibble << 4) | (addr >> 8) as u8,
+                Patch::UnpackLow => self.rom[fixup.offset] = addr as u8,
+            }
+        }
+        Ok(Program { rom: self.rom, symbols: self.symbols })
+    }
+}
+
+/// Compile Octo source. `file_name` is written into source lines of symbols.
+///
+/// Supported are labels, `:alias`, `:const`, `:calc`, `:macro`, `:byte`, `:org`, `:next`, `:call`, `:unpack`,
+/// `loop`/`while`/`again`, `if`/`then` and `if`/`begin`/`else`/`end`, statements of CHIP-8, and SUPER-CHIP and
+/// XO-CHIP extensions. Program starts at `: main`.
+pub fn compile(source: &str, file_name: &str) -> Result<Program, String> {
+    Compiler::new(source, file_name).compile()
+}
+
+/// Compile Octo source file.
+pub fn compile_file(path: &str) -> Result<Program, String> {
+    let source = fs::read_to_string(path).map_err(|err| format!("{} : {}", path, err))?;
+    let file_name = Path::new(path).file_name().map_or(path.to_string(), |name| name.to_string_lossy().into_owned());
+    compile(&source, &file_name).map_err(|err| format!("{} : {}", path, err))
+}
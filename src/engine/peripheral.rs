@@ -0,0 +1,88 @@
+use super::isa;
+use super::screen::{DirtyRows, Row, MAX_WIDTH};
+use super::register::TimerSideEffect;
+
+/// Provides memory seen by the interpreter core.
+/// Every instruction fetch and data access of `Machine` goes through this trait,
+/// so that memory-mapped hooks or logging memory can be substituted.
+///
+/// Given address is always less than `MEMORY_SIZE`.
+pub trait Bus {
+    /// Read one byte. Implementations which have to record reads need interior mutability.
+    fn read(&self, addr: u16) -> u8;
+
+    /// Write one byte.
+    fn write(&mut self, addr: u16, value: u8);
+
+    /// Read bytes from `addr` into whole `buffer`.
+    fn read_into(&self, addr: u16, buffer: &mut [u8]) {
+        for (addr, byte) in (addr..).zip(buffer.iter_mut()) {
+            *byte = self.read(addr);
+        }
+    }
+
+    /// Write whole `bytes` from `addr`.
+    fn write_from(&mut self, addr: u16, bytes: &[u8]) {
+        for (addr, &byte) in (addr..).zip(bytes.iter()) {
+            self.write(addr, byte);
+        }
+    }
+
+    /// Fetch and parse instruction of given address.
+    fn fetch(&self, pc: u16) -> Option<isa::Instruction> {
+        isa::parse_instruction(&self.fetch_opcode(pc)?)
+    }
+
+    /// Fetch raw opcode bytes of given address. Returns None if it goes past the end.
+    fn fetch_opcode(&self, pc: u16) -> Option<[u8; 2]> {
+        if (pc as usize) + 1 >= super::memory::MEMORY_SIZE { return None; }
+        Some([self.read(pc), self.read(pc + 1)])
+    }
+}
+
+/// Provides monochrome display seen by the interpreter core.
+pub trait Display {
+    /// Clear whole display.
+    fn clear(&mut self);
+
+    /// Draw sprite `bytes` at given position with XOR, and return dirty rows
+    /// and whether any pixel is erased. See `Screen::draw` for wrapping and clipping.
+    fn draw(&mut self, pos: (u8, u8), bytes: &[u8], clip: bool) -> (DirtyRows, bool);
+
+    /// Get packed pixels of given row. See `screen::Row` for bit order.
+    fn row(&self, y: u8) -> Row;
+
+    /// Check whether pixel of given position is drawn or not.
+    fn is_drawn(&self, (x, y): (u8, u8)) -> bool {
+        self.row(y) & (1 << (MAX_WIDTH - 1 - x as usize)) != 0
+    }
+}
+
+/// Provides 16-key keypad seen by the interpreter core.
+pub trait Input {
+    /// Check whether given key is pressed or not.
+    fn is_pressed(&self, key: u8) -> bool;
+
+    /// Set given key to pressed state.
+    fn press(&mut self, key: u8);
+
+    /// Set given key to released state.
+    fn release(&mut self, key: u8);
+
+    /// Set all keys to released state.
+    fn release_all(&mut self);
+}
+
+/// Provides delay and sound timers seen by the interpreter core.
+pub trait Timers {
+    fn delay(&self) -> u8;
+
+    fn set_delay(&mut self, value: u8);
+
+    fn sound(&self) -> u8;
+
+    fn set_sound(&mut self, value: u8);
+
+    /// Decrease timers. Called in 60Hz.
+    fn tick(&mut self) -> TimerSideEffect;
+}
@@ -0,0 +1,255 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+use super::isa::Instruction;
+use super::machine::Machine;
+use super::peripheral::{Bus, Display, Input, Timers};
+use super::hook::Hooks;
+use super::state::MachineState;
+use super::register::INIT_PROGRAM_COUNTER_VAL;
+use super::symbols::SymbolTable;
+
+/// Provides time (in cycles) spent by one subroutine.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FunctionTime {
+    /// Count of calls. Entry routine is never called, so it is always 0.
+    pub calls: u64,
+    /// Cycles spent in function including called subroutines.
+    pub inclusive: u64,
+    /// Cycles spent in function itself.
+    pub exclusive: u64,
+}
+
+/// Provides cycles processed in one 60Hz frame.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FrameProfile {
+    /// Count of instructions processed in frame.
+    pub cycles: u64,
+    /// Count of instructions processed before reaching the first sync point,
+    /// which is DT read (0xFx07), key wait (0xFx0A) or display wait.
+    pub busy_cycles: u64,
+    /// Whether any sync point is reached in frame. If not, frame overran its budget.
+    pub synced: bool,
+}
+
+/// Provides execution profiler.
+/// `observe` must be called right before every `Machine::step`, and `end_frame` before
+/// every `Machine::tick_timers`.
+pub struct Profiler {
+    pc_counts: Vec<u64>,
+    instruction_counts: HashMap<&'static str, u64>,
+    stack: Vec<u16>,
+    functions: HashMap<u16, FunctionTime>,
+    folded: HashMap<Vec<u16>, u64>,
+    frames: Vec<FrameProfile>,
+    current: FrameProfile,
+    /// Labels which name functions and hot addresses in reports.
+    symbols: SymbolTable,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new()
+    }
+}
+
+/// Get function name of given entry address.
+pub fn function_name(addr: u16) -> String {
+    if addr == INIT_PROGRAM_COUNTER_VAL {
+        "main".to_string()
+    } else {
+        format!("sub_{:03X}", addr)
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            pc_counts: vec![0; 4 << 10],
+            instruction_counts: HashMap::new(),
+            stack: vec![INIT_PROGRAM_COUNTER_VAL],
+            functions: HashMap::new(),
+            folded: HashMap::new(),
+            frames: Vec::new(),
+            current: FrameProfile::default(),
+            symbols: SymbolTable::new(),
+        }
+    }
+
+    /// Name functions and hot addresses of reports by labels of given symbols.
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = symbols;
+    }
+
+    /// Get label of function, or name given by `function_name`.
+    fn name_of(&self, addr: u16) -> String {
+        self.symbols.label(addr).map_or_else(|| function_name(addr), str::to_string)
+    }
+
+    /// Count the instruction that machine is about to process.
+    pub fn observe<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&mut self, machine: &Machine<B, D, I, T, H>) {
+        if *machine.state() != MachineState::Normal {
+            self.current.synced = true;
+            return;
+        }
+
+        let pc = machine.registers().get_pc();
+        let instruction = match machine.bus().fetch(pc) {
+            Some(instruction) => instruction,
+            None => return,
+        };
+
+        self.pc_counts[pc as usize] += 1;
+        *self.instruction_counts.entry(instruction.form()).or_insert(0) += 1;
+
+        // Attribute cycle to every function in stack once, even for recursive calls.
+        for (i, &func) in self.stack.iter().enumerate() {
+            if !self.stack[..i].contains(&func) {
+                self.functions.entry(func).or_default().inclusive += 1;
+            }
+        }
+        self.functions.entry(*self.stack.last().unwrap()).or_default().exclusive += 1;
+        *self.folded.entry(self.stack.clone()).or_insert(0) += 1;
+
+        // Frame budget.
+        self.current.cycles += 1;
+        if !self.current.synced {
+            self.current.busy_cycles += 1;
+        }
+        let is_sync = match instruction {
+            Instruction::SetDelayToReg{ .. } | Instruction::WaitKeyPress{ .. } => true,
+            Instruction::DispSpr{ .. } => machine.quirks().display_wait,
+            _ => false,
+        };
+        self.current.synced |= is_sync;
+
+        // Track call stack. Unmatched return is ignored to keep entry routine.
+        match instruction {
+            Instruction::CallSub(addr) => {
+                self.functions.entry(addr).or_default().calls += 1;
+                self.stack.push(addr);
+            },
+            Instruction::ReturnSubroutine if self.stack.len() > 1 => {
+                self.stack.pop();
+            },
+            _ => (),
+        }
+    }
+
+    /// Close current 60Hz frame.
+    pub fn end_frame(&mut self) {
+        let frame = std::mem::take(&mut self.current);
+        self.frames.push(frame);
+    }
+
+    /// Get execution count of given address.
+    pub fn pc_count(&self, addr: u16) -> u64 {
+        self.pc_counts.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    /// Get execution counts of each instruction variant, keyed by `Instruction::form`.
+    pub fn instruction_counts(&self) -> &HashMap<&'static str, u64> { &self.instruction_counts }
+
+    /// Get time of functions, keyed by entry address.
+    pub fn functions(&self) -> &HashMap<u16, FunctionTime> { &self.functions }
+
+    /// Get profiles of closed frames.
+    pub fn frames(&self) -> &[FrameProfile] { &self.frames }
+
+    /// Get total count of observed instructions.
+    pub fn total_cycles(&self) -> u64 {
+        self.pc_counts.iter().sum()
+    }
+
+    /// Write human readable report. Only `top` entries are written for hot addresses and overrun frames.
+    pub fn write_report<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&self, out: &mut dyn Write, machine: &Machine<B, D, I, T, H>, top: usize) -> io::Result<()> {
+        let total = self.total_cycles().max(1);
+        let percent = |count: u64| 100.0 * count as f64 / total as f64;
+
+        let overruns: Vec<usize> = self.frames.iter().enumerate()
+            .filter(|(_, frame)| frame.cycles > 0 && !frame.synced)
+            .map(|(i, _)| i)
+            .collect();
+        writeln!(out, "== Summary ==")?;
+        writeln!(out, "Frames : {}, Cycles : {}, Overrun frames : {}", self.frames.len(), self.total_cycles(), overruns.len())?;
+
+        writeln!(out, "\n== Hot addresses ==")?;
+        writeln!(out, "{:>6} {:>10} {:>7}  INSTRUCTION", "ADDR", "COUNT", "%")?;
+        let mut hot: Vec<(usize, u64)> = self.pc_counts.iter().copied().enumerate().filter(|&(_, c)| c > 0).collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        for &(addr, count) in hot.iter().take(top) {
+            let instruction = match machine.bus().fetch(addr as u16) {
+                Some(instruction) => instruction.to_string(),
+                None => "???".to_string(),
+            };
+            match self.symbols.locate(addr as u16) {
+                Some(name) => writeln!(out, " 0x{:03X} {:>10} {:>6.2}%  {:<20} ; {}", addr, count, percent(count), instruction, name)?,
+                None => writeln!(out, " 0x{:03X} {:>10} {:>6.2}%  {}", addr, count, percent(count), instruction)?,
+            }
+        }
+
+        writeln!(out, "\n== Instruction mix ==")?;
+        let mut mix: Vec<(&&str, &u64)> = self.instruction_counts.iter().collect();
+        mix.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (name, &count) in mix {
+            writeln!(out, " {:<24} {:>10} {:>6.2}%", name, count, percent(count))?;
+        }
+
+        writeln!(out, "\n== Subroutines ==")?;
+        writeln!(out, " {:<10} {:>8} {:>10} {:>7} {:>10} {:>7}", "FUNCTION", "CALLS", "INCLUSIVE", "%", "EXCLUSIVE", "%")?;
+        let mut functions: Vec<(&u16, &FunctionTime)> = self.functions.iter().collect();
+        functions.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(b.0)));
+        for (&addr, time) in functions {
+            writeln!(out, " {:<10} {:>8} {:>10} {:>6.2}% {:>10} {:>6.2}%",
+                self.name_of(addr), time.calls,
+                time.inclusive, percent(time.inclusive),
+                time.exclusive, percent(time.exclusive))?;
+        }
+
+        writeln!(out, "\n== Frames ==")?;
+        let stats = |counts: Vec<u64>| (
+            counts.iter().min().copied().unwrap_or(0),
+            counts.iter().sum::<u64>() as f64 / counts.len() as f64,
+            counts.iter().max().copied().unwrap_or(0),
+        );
+        if !self.frames.is_empty() {
+            let (min, avg, max) = stats(self.frames.iter().map(|frame| frame.cycles).collect());
+            writeln!(out, " Cycles per frame : min {}, avg {:.1}, max {}", min, avg, max)?;
+            let (min, avg, max) = stats(self.frames.iter().map(|frame| frame.busy_cycles).collect());
+            writeln!(out, " Busy cycles per frame : min {}, avg {:.1}, max {}", min, avg, max)?;
+        }
+
+        // Frames with the most busy cycles are the closest to the budget.
+        writeln!(out, " {:>6} {:>8} {:>8}  SYNC", "FRAME", "CYCLES", "BUSY")?;
+        let mut heavy: Vec<(usize, &FrameProfile)> = self.frames.iter().enumerate().collect();
+        heavy.sort_by(|a, b| b.1.busy_cycles.cmp(&a.1.busy_cycles).then(a.0.cmp(&b.0)));
+        for &(i, frame) in heavy.iter().take(top) {
+            writeln!(out, " {:>6} {:>8} {:>8}  {}", i, frame.cycles, frame.busy_cycles, if frame.synced { "yes" } else { "no" })?;
+        }
+        for &i in overruns.iter().take(top) {
+            writeln!(out, " Frame {:>6} overran : {} cycles without sync point", i, self.frames[i].cycles)?;
+        }
+        if overruns.len() > top {
+            writeln!(out, " ... and {} more overrun frames", overruns.len() - top)?;
+        }
+        Ok(())
+    }
+
+    /// Write folded stacks (`main;sub_2A4 123`) which can be used by flamegraph tools.
+    pub fn write_folded(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut stacks: Vec<(String, u64)> = self.folded.iter()
+            .map(|(stack, &count)| {
+                let names: Vec<String> = stack.iter().map(|&addr| self.name_of(addr)).collect();
+                (names.join(";"), count)
+            })
+            .collect();
+        stacks.sort();
+
+        for (stack, count) in stacks {
+            writeln!(out, "{} {}", stack, count)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::{self, Write as _},
+};
+
+use super::isa::Instruction;
+use super::machine::Machine;
+use super::peripheral::{Bus, Display, Input, Timers};
+use super::hook::Hooks;
+use super::memory::MEMORY_SIZE;
+use super::register::GENERAL_REGISTERS_CNT;
+use super::state::MachineState;
+
+/// Count of writes kept per location. Older writes are forgotten.
+const HISTORY_LEN: usize = 16;
+
+const VF: u8 = 0xF;
+
+/// Provides storage whose provenance is tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Location {
+    /// General register Vx.
+    V(u8),
+    I,
+    Memory(u16),
+}
+
+impl Location {
+    /// Parse `V3`, `VF`, `I` or memory address such as `0x2F0`.
+    pub fn parse(text: &str) -> Option<Location> {
+        if text == "I" {
+            return Some(Location::I);
+        }
+        if let Some(addr) = text.strip_prefix("0x") {
+            return u16::from_str_radix(addr, 16).ok()
+                .filter(|&addr| (addr as usize) < MEMORY_SIZE)
+                .map(Location::Memory);
+        }
+        let r = text.strip_prefix('V')?;
+        u8::from_str_radix(r, 16).ok().filter(|&r| r < 16).map(Location::V)
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Location::V(r) => r as usize,
+            Location::I => GENERAL_REGISTERS_CNT,
+            Location::Memory(addr) => GENERAL_REGISTERS_CNT + 1 + addr as usize,
+        }
+    }
+
+    /// Get current value of location.
+    pub fn read<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(self, machine: &Machine<B, D, I, T, H>) -> u16 {
+        match self {
+            Location::V(r) => machine.registers().general_register(r) as u16,
+            Location::I => machine.registers().get_l(),
+            Location::Memory(addr) => machine.bus().read(addr) as u16,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::V(r) => write!(f, "V{:X}", r),
+            Location::I => write!(f, "I"),
+            Location::Memory(addr) => write!(f, "0x{:03X}", addr),
+        }
+    }
+}
+
+/// Provides where written value comes from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    /// Constant of instruction.
+    Immediate(u16),
+    /// Value of register or memory at the time of write.
+    Location(Location),
+    /// Random number of 0xCxkk.
+    Random,
+    /// Key pressed for 0xFx0A.
+    Key,
+    DelayTimer,
+    /// Pixels of screen, which decide collision flag of 0xDxyn.
+    Screen,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Immediate(value) => write!(f, "immediate 0x{:02X}", value),
+            Source::Location(location) => write!(f, "{}", location),
+            Source::Random => write!(f, "random"),
+            Source::Key => write!(f, "key"),
+            Source::DelayTimer => write!(f, "delay timer"),
+            Source::Screen => write!(f, "screen"),
+        }
+    }
+}
+
+/// Provides one write into location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Write {
+    /// Address of instruction which wrote.
+    pub pc: u16,
+    /// Cycle of instruction which wrote.
+    pub cycle: u64,
+    pub instruction: Instruction,
+    /// Written value. `None` until the instruction completes.
+    pub value: Option<u16>,
+    pub sources: Vec<Source>,
+}
+
+/// Provides result of looking up the write which a location held at some time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Origin<'a> {
+    /// Location is never written, so it holds font, ROM or zero.
+    Initial,
+    Written(&'a Write),
+    /// Write is older than kept history.
+    Forgotten,
+}
+
+/// Provides writes of one location, the newest last.
+#[derive(Debug, Default, Clone)]
+struct History {
+    writes: VecDeque<Write>,
+    is_truncated: bool,
+}
+
+/// Provides data-flow provenance tracking of V registers, I and memory.
+/// `observe` must be called right before every `Machine::step`.
+///
+/// Each write records the instruction and the sources which the value is derived from, so that
+/// `explain` can follow where a value came from through earlier writes of its sources.
+pub struct Provenance {
+    histories: Vec<History>,
+    /// Locations written by the last observed instruction, whose values are not known yet.
+    pending: Vec<Location>,
+}
+
+impl Default for Provenance {
+    fn default() -> Self {
+        Provenance::new()
+    }
+}
+
+impl Provenance {
+    pub fn new() -> Provenance {
+        Provenance {
+            histories: vec![History::default(); GENERAL_REGISTERS_CNT + 1 + MEMORY_SIZE],
+            pending: Vec::new(),
+        }
+    }
+
+    /// Get the latest write of location.
+    pub fn last_write(&self, location: Location) -> Origin<'_> {
+        self.origin_before(location, u64::MAX)
+    }
+
+    /// Get the write which location held right before instruction of given cycle.
+    pub fn origin_before(&self, location: Location, cycle: u64) -> Origin<'_> {
+        let history = &self.histories[location.index()];
+        match history.writes.iter().rev().find(|write| write.cycle < cycle) {
+            Some(write) => Origin::Written(write),
+            None if history.is_truncated => Origin::Forgotten,
+            None => Origin::Initial,
+        }
+    }
+
+    fn record(&mut self, location: Location, write: Write) {
+        let history = &mut self.histories[location.index()];
+        // Instruction such as `ADD VF, Vy` writes the same location twice, which is one write of the final value.
+        if let Some(last) = history.writes.back_mut().filter(|last| last.cycle == write.cycle && last.pc == write.pc) {
+            for source in write.sources {
+                if !last.sources.contains(&source) {
+                    last.sources.push(source);
+                }
+            }
+            return;
+        }
+        if history.writes.len() >= HISTORY_LEN {
+            history.writes.pop_front();
+            history.is_truncated = true;
+        }
+        history.writes.push_back(write);
+        self.pending.push(location);
+    }
+
+    /// Record writes of the instruction that machine is about to process.
+    pub fn observe<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&mut self, machine: &Machine<B, D, I, T, H>) {
+        if *machine.state() != MachineState::Normal {
+            return;
+        }
+        // Values written by the previous instruction are known now.
+        for location in std::mem::take(&mut self.pending) {
+            let value = location.read(machine);
+            if let Some(write) = self.histories[location.index()].writes.back_mut() {
+                write.value = Some(value);
+            }
+        }
+
+        let registers = machine.registers();
+        let pc = registers.get_pc();
+        let instruction = match machine.bus().fetch(pc) {
+            Some(instruction) => instruction,
+            None => return,
+        };
+        let quirks = machine.quirks();
+        let l = registers.get_l();
+        let v = |r: u8| Source::Location(Location::V(r));
+        let memory = |addr: u16| Source::Location(Location::Memory(addr));
+
+        type Inst = Instruction;
+        let mut writes: Vec<(Location, Vec<Source>)> = Vec::new();
+        match instruction {
+            Inst::SetByte{ r, val } => writes.push((Location::V(r), vec![Source::Immediate(val as u16)])),
+            Inst::AddByte{ r, val } => writes.push((Location::V(r), vec![v(r), Source::Immediate(val as u16)])),
+            Inst::SetRegV{ r, f } => writes.push((Location::V(r), vec![v(f)])),
+            Inst::OrRegV{ r, f } | Inst::AndRegV{ r, f } | Inst::XorRegV{ r, f } => {
+                writes.push((Location::V(r), vec![v(r), v(f)]));
+                if quirks.vf_reset {
+                    writes.push((Location::V(VF), vec![Source::Immediate(0)]));
+                }
+            },
+            Inst::AddRegV{ r, f } | Inst::SubRegV{ r, f } | Inst::SubNRegV{ r, f } => {
+                writes.push((Location::V(r), vec![v(r), v(f)]));
+                writes.push((Location::V(VF), vec![v(r), v(f)]));
+            },
+            Inst::ShrRegV{ r, f } | Inst::ShlRegV{ r, f } => {
+                let src = if quirks.shift_vy { f } else { r };
+                writes.push((Location::V(r), vec![v(src)]));
+                writes.push((Location::V(VF), vec![v(src)]));
+            },
+            Inst::SetRegL(addr) => writes.push((Location::I, vec![Source::Immediate(addr)])),
+            Inst::RndAnd{ r, val } => writes.push((Location::V(r), vec![Source::Random, Source::Immediate(val as u16)])),
+            Inst::DispSpr{ rp, n } => {
+                let mut sources = vec![v(rp.0), v(rp.1), Source::Location(Location::I)];
+                sources.extend((l..l.saturating_add(n as u16)).filter(|&addr| (addr as usize) < MEMORY_SIZE).map(memory));
+                sources.push(Source::Screen);
+                writes.push((Location::V(VF), sources));
+            },
+            Inst::SetDelayToReg{ r } => writes.push((Location::V(r), vec![Source::DelayTimer])),
+            Inst::WaitKeyPress{ r } => writes.push((Location::V(r), vec![Source::Key])),
+            Inst::AddRegL{ r } => writes.push((Location::I, vec![Source::Location(Location::I), v(r)])),
+            Inst::SetRegLFontAddrFromReg{ r } => writes.push((Location::I, vec![v(r)])),
+            Inst::MemDumpBcdFromReg{ r } => {
+                for addr in (l..l.saturating_add(3)).filter(|&addr| (addr as usize) < MEMORY_SIZE) {
+                    writes.push((Location::Memory(addr), vec![v(r)]));
+                }
+            },
+            Inst::MemDump{ endr } => {
+                for r in 0..=endr {
+                    let addr = l.saturating_add(r as u16);
+                    if (addr as usize) < MEMORY_SIZE {
+                        writes.push((Location::Memory(addr), vec![v(r), Source::Location(Location::I)]));
+                    }
+                }
+                if quirks.memory_increment {
+                    writes.push((Location::I, vec![Source::Location(Location::I)]));
+                }
+            },
+            Inst::MemRead{ endr } => {
+                for r in 0..=endr {
+                    let addr = l.saturating_add(r as u16);
+                    if (addr as usize) < MEMORY_SIZE {
+                        writes.push((Location::V(r), vec![memory(addr), Source::Location(Location::I)]));
+                    }
+                }
+                if quirks.memory_increment {
+                    writes.push((Location::I, vec![Source::Location(Location::I)]));
+                }
+            },
+            _ => (),
+        }
+
+        let cycle = machine.cycles();
+        for (location, sources) in writes {
+            self.record(location, Write{ pc, cycle, instruction, value: None, sources });
+        }
+    }
+
+    /// Explain where the current value of location came from, following sources up to `depth` writes back.
+    /// Each line is one write, and sources of it are indented below.
+    pub fn explain<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&self, machine: &Machine<B, D, I, T, H>, location: Location, depth: usize) -> String {
+        let mut text = format!("{} = 0x{:02X}\n", location, location.read(machine));
+        let mut shown = HashSet::new();
+        // Writing into String never fails.
+        self.explain_origin(&mut text, location, self.last_write(location), 1, depth, &mut shown).unwrap();
+        text
+    }
+
+    fn explain_origin(&self, text: &mut String, location: Location, origin: Origin<'_>, indent: usize, depth: usize, shown: &mut HashSet<(Location, u64)>) -> fmt::Result {
+        let pad = "  ".repeat(indent);
+        let write = match origin {
+            Origin::Initial => return writeln!(text, "{}{} has initial value", pad, location),
+            Origin::Forgotten => return writeln!(text, "{}{} was written before kept history", pad, location),
+            Origin::Written(write) => write,
+        };
+
+        write!(text, "{}{}", pad, location)?;
+        if let Some(value) = write.value {
+            write!(text, " = 0x{:02X}", value)?;
+        }
+        let sources: Vec<String> = write.sources.iter().map(|source| source.to_string()).collect();
+        write!(text, " written by {} at 0x{:03X} (cycle {}) from {}", write.instruction, write.pc, write.cycle, sources.join(", "))?;
+        if !shown.insert((location, write.cycle)) {
+            return writeln!(text, " (shown above)");
+        }
+        writeln!(text)?;
+        if indent >= depth {
+            return Ok(());
+        }
+
+        for source in &write.sources {
+            if let Source::Location(source) = *source {
+                self.explain_origin(text, source, self.origin_before(source, write.cycle), indent + 1, depth, shown)?;
+            }
+        }
+        Ok(())
+    }
+}
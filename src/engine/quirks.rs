@@ -0,0 +1,103 @@
+use std::fmt;
+
+/// Provides behavior switches for instructions whose semantics differ between
+/// CHIP-8 platforms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quirks {
+    /// 0x8xy1, 0x8xy2, 0x8xy3 reset VF to 0.
+    pub vf_reset: bool,
+    /// 0xFx55, 0xFx65 advance L past the last accessed address.
+    pub memory_increment: bool,
+    /// 0x8xy6, 0x8xyE shift Vy into Vx. Otherwise Vx is shifted in place.
+    pub shift_vy: bool,
+    /// 0xBxnn jumps to xnn + Vx instead of nnn + V0.
+    pub jump_vx: bool,
+    /// Sprites are clipped at the screen edges instead of wrapping around.
+    pub clip_sprites: bool,
+    /// 0xDxyn waits for the next 60Hz frame before execution continues.
+    pub display_wait: bool,
+}
+
+/// Provides predefined quirks sets of well-known platforms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuirksProfile {
+    Chipmunk,   // Behavior this interpreter has always had.
+    CosmacVip,  // Original COSMAC VIP interpreter.
+    Chip48,     // CHIP-48 on HP-48 calculators.
+    SuperChip,  // SUPER-CHIP 1.1 (modern interpretation).
+}
+
+impl QuirksProfile {
+    /// All predefined profiles.
+    pub const ALL: [QuirksProfile; 4] = [
+        QuirksProfile::Chipmunk,
+        QuirksProfile::CosmacVip,
+        QuirksProfile::Chip48,
+        QuirksProfile::SuperChip,
+    ];
+
+    /// Get short name of profile which is also accepted by `from_name`.
+    pub fn name(self) -> &'static str {
+        match self {
+            QuirksProfile::Chipmunk => "chipmunk",
+            QuirksProfile::CosmacVip => "vip",
+            QuirksProfile::Chip48 => "chip48",
+            QuirksProfile::SuperChip => "schip",
+        }
+    }
+
+    /// Get profile from given short name.
+    pub fn from_name(name: &str) -> Option<QuirksProfile> {
+        QuirksProfile::ALL.iter().copied().find(|profile| profile.name() == name)
+    }
+
+    /// Get quirks set of this profile.
+    pub fn quirks(self) -> Quirks {
+        match self {
+            QuirksProfile::Chipmunk => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                shift_vy: true,
+                jump_vx: false,
+                clip_sprites: false,
+                display_wait: false,
+            },
+            QuirksProfile::CosmacVip => Quirks {
+                vf_reset: true,
+                memory_increment: true,
+                shift_vy: true,
+                jump_vx: false,
+                clip_sprites: true,
+                display_wait: true,
+            },
+            QuirksProfile::Chip48 => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                shift_vy: false,
+                jump_vx: true,
+                clip_sprites: true,
+                display_wait: false,
+            },
+            QuirksProfile::SuperChip => Quirks {
+                vf_reset: false,
+                memory_increment: false,
+                shift_vy: false,
+                jump_vx: true,
+                clip_sprites: true,
+                display_wait: false,
+            },
+        }
+    }
+}
+
+impl fmt::Display for QuirksProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        QuirksProfile::Chipmunk.quirks()
+    }
+}
@@ -0,0 +1,303 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Write,
+};
+
+use super::isa::{Flow, Instruction};
+use super::machine::{Machine, Output, Fault};
+use super::memory::{Memory, MEMORY_SIZE};
+use super::peripheral::{Bus, Display, Input, Timers};
+use super::hook::Hooks;
+use super::quirks::Quirks;
+use super::register::INIT_PROGRAM_COUNTER_VAL;
+use super::state::MachineState;
+
+/// Maximum count of instructions recompiled into one block.
+const MAX_BLOCK_LEN: usize = 64;
+
+/// Registers which recompiled blocks read and write.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct State {
+    pub v: [u8; 16],
+    pub l: u16,
+    pub pc: u16,
+}
+
+/// Provides one recompiled basic block. `run` processes `cycles` instructions and sets PC to the next one.
+pub struct Block {
+    /// Byte range [start, end) of instructions recompiled into block.
+    pub start: u16,
+    pub end: u16,
+    pub cycles: u64,
+    pub run: fn(&mut State),
+}
+
+/// Provides recompiled ROM, which is emitted by `recompile`.
+pub struct Program {
+    pub rom: &'static [u8],
+    pub quirks: Quirks,
+    pub blocks: &'static [Block],
+}
+
+/// Provides dispatcher of recompiled blocks.
+/// Address without block (target of 0xBnnn, code found only at runtime) is processed by interpreter
+/// of the machine, and so is every draw, key, timer, memory, stack and random instruction.
+///
+/// Blocks which the interpreter writes over by 0xFx33 and 0xFx55 are disabled, so self-modifying
+/// code is interpreted from then on. Machine with active hooks or different quirks is always interpreted.
+pub struct Runtime {
+    program: &'static Program,
+    /// Index of block starting from each address.
+    table: Vec<Option<usize>>,
+}
+
+impl Runtime {
+    pub fn new(program: &'static Program) -> Runtime {
+        let mut table = vec![None; MEMORY_SIZE];
+        for (index, block) in program.blocks.iter().enumerate() {
+            table[block.start as usize] = Some(index);
+        }
+        Runtime { program, table }
+    }
+
+    /// Get count of blocks which are not disabled.
+    pub fn block_count(&self) -> usize {
+        self.table.iter().filter(|index| index.is_some()).count()
+    }
+
+    /// Disable blocks overlapping with memory range [from, from + count).
+    pub fn invalidate(&mut self, from: usize, count: usize) {
+        let end = from + count;
+        for block in self.program.blocks {
+            if (block.start as usize) < end && from < block.end as usize {
+                self.table[block.start as usize] = None;
+            }
+        }
+    }
+
+    /// Process recompiled blocks in a row as long as they fit in `budget` instructions,
+    /// or one instruction by interpreter. Count of processed instructions is added to `Machine::cycles`.
+    pub fn step<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&mut self, machine: &mut Machine<B, D, I, T, H>, budget: u64) -> Result<Output, Fault> {
+        if *machine.state() != MachineState::Normal || machine.hooks().is_active() || *machine.quirks() != self.program.quirks {
+            return machine.step();
+        }
+
+        let registers = machine.registers();
+        let mut state = State {
+            v: *registers.general_registers(),
+            l: registers.get_l(),
+            pc: registers.get_pc(),
+        };
+        let mut cycles = 0;
+        while let Some(index) = self.table.get(state.pc as usize).copied().flatten() {
+            let block = &self.program.blocks[index];
+            if cycles + block.cycles > budget {
+                break;
+            }
+            (block.run)(&mut state);
+            cycles += block.cycles;
+        }
+        if cycles > 0 {
+            machine.native_registers(cycles).load(state.v, state.l, state.pc);
+            return Ok(Output::None);
+        }
+
+        let l = state.l as usize;
+        match machine.bus().fetch(state.pc) {
+            Some(Instruction::MemDump{ endr }) => self.invalidate(l, endr as usize + 1),
+            Some(Instruction::MemDumpBcdFromReg{ .. }) => self.invalidate(l, 3),
+            _ => (),
+        }
+        machine.step()
+    }
+}
+
+/// Get Rust statement of instruction which does not change control flow.
+/// Return None if it can not be recompiled.
+fn straight(instruction: Instruction, quirks: &Quirks) -> Option<String> {
+    type Inst = Instruction;
+    let reset_vf = if quirks.vf_reset { " s.v[0xF] = 0;" } else { "" };
+    let shift_src = |r: u8, f: u8| if quirks.shift_vy { f } else { r };
+    let code = match instruction {
+        Inst::Ignore(_) => String::new(),
+        Inst::SetByte{ r, val } => format!("s.v[0x{:X}] = 0x{:02X};", r, val),
+        Inst::AddByte{ r, val } => format!("s.v[0x{:X}] = s.v[0x{:X}].wrapping_add(0x{:02X});", r, r, val),
+        Inst::SetRegV{ r, f } => format!("s.v[0x{:X}] = s.v[0x{:X}];", r, f),
+        Inst::OrRegV{ r, f } => format!("s.v[0x{:X}] |= s.v[0x{:X}];{}", r, f, reset_vf),
+        Inst::AndRegV{ r, f } => format!("s.v[0x{:X}] &= s.v[0x{:X}];{}", r, f, reset_vf),
+        Inst::XorRegV{ r, f } => format!("s.v[0x{:X}] ^= s.v[0x{:X}];{}", r, f, reset_vf),
+        Inst::AddRegV{ r, f } => format!(
+            "let (val, carry) = s.v[0x{:X}].overflowing_add(s.v[0x{:X}]); s.v[0x{:X}] = val; s.v[0xF] = carry as u8;",
+            r, f, r),
+        Inst::SubRegV{ r, f } => format!(
+            "let (val, borrow) = s.v[0x{:X}].overflowing_sub(s.v[0x{:X}]); s.v[0x{:X}] = val; s.v[0xF] = !borrow as u8;",
+            r, f, r),
+        Inst::SubNRegV{ r, f } => format!(
+            "let (val, borrow) = s.v[0x{:X}].overflowing_sub(s.v[0x{:X}]); s.v[0x{:X}] = val; s.v[0xF] = !borrow as u8;",
+            f, r, r),
+        // Flag is stored first, so result wins when Vx is VF.
+        Inst::ShrRegV{ r, f } => format!(
+            "let src = s.v[0x{:X}]; s.v[0xF] = src & 1; s.v[0x{:X}] = src >> 1;", shift_src(r, f), r),
+        Inst::ShlRegV{ r, f } => format!(
+            "let src = s.v[0x{:X}]; s.v[0xF] = src >> 7; s.v[0x{:X}] = src << 1;", shift_src(r, f), r),
+        Inst::SetRegL(addr) => format!("s.l = 0x{:03X};", addr),
+        Inst::AddRegL{ r } => format!("s.l = s.l.wrapping_add(s.v[0x{:X}] as u16);", r),
+        Inst::SetRegLFontAddrFromReg{ r } => format!("s.l = s.v[0x{:X}] as u16 * 5;", r),
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Get Rust statement of instruction which ends block. Return None if it can not be recompiled.
+fn terminator(pc: u16, instruction: Instruction, quirks: &Quirks) -> Option<String> {
+    type Inst = Instruction;
+    let skip = |condition: String| format!("s.pc = if {} {{ 0x{:03X} }} else {{ 0x{:03X} }};", condition, pc + 4, pc + 2);
+    let code = match instruction {
+        Inst::JmpAddr(addr) => format!("s.pc = 0x{:03X};", addr),
+        Inst::JmpAddrOffReg0(addr) => {
+            let r = if quirks.jump_vx { (addr >> 8) as u8 } else { 0 };
+            format!("s.pc = s.v[0x{:X}] as u16 + 0x{:03X};", r, addr)
+        },
+        Inst::SkipEq{ r, val } => skip(format!("s.v[0x{:X}] == 0x{:02X}", r, val)),
+        Inst::SkipNeq{ r, val } => skip(format!("s.v[0x{:X}] != 0x{:02X}", r, val)),
+        Inst::SkipRegEq{ r, f } => skip(format!("s.v[0x{:X}] == s.v[0x{:X}]", r, f)),
+        Inst::SkipRegNeq{ r, f } => skip(format!("s.v[0x{:X}] != s.v[0x{:X}]", r, f)),
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Find addresses where blocks start, by following control flow from the entry point.
+/// Targets of jumps, calls and skips, return addresses, and instructions following interpreted
+/// ones are block leaders. Targets of 0xBnnn are unknown, and left to the dispatcher.
+fn find_leaders(memory: &Memory, rom_end: u16, quirks: &Quirks) -> BTreeSet<u16> {
+    let mut leaders = BTreeSet::new();
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![INIT_PROGRAM_COUNTER_VAL];
+    leaders.insert(INIT_PROGRAM_COUNTER_VAL);
+
+    while let Some(pc) = pending.pop() {
+        if pc < INIT_PROGRAM_COUNTER_VAL || pc + 2 > rom_end || !visited.insert(pc) {
+            continue;
+        }
+        let instruction = match memory.parse_instruction(pc) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        let (successors, is_branch): (Vec<u16>, bool) = match instruction.flow() {
+            Flow::Jump(addr) => (vec![addr], true),
+            Flow::IndirectJump | Flow::Return => (vec![], true),
+            Flow::Call(addr) => (vec![addr, pc + 2], true),
+            Flow::Skip => (vec![pc + 2, pc + 4], true),
+            Flow::Next => (vec![pc + 2], straight(instruction, quirks).is_none()),
+        };
+        if is_branch {
+            leaders.extend(successors.iter().copied());
+        }
+        pending.extend(successors);
+    }
+    leaders.retain(|&pc| visited.contains(&pc));
+    leaders
+}
+
+/// Translate ROM into Rust source, which defines `PROGRAM` and `main` running it on terminal.
+/// Each block found by control flow analysis becomes one function. `name` is written in header comment.
+pub fn recompile(rom: &[u8], quirks: Quirks, name: &str) -> String {
+    let memory = Memory::from_rom(rom);
+    let rom_end = INIT_PROGRAM_COUNTER_VAL + rom.len().min(MEMORY_SIZE - INIT_PROGRAM_COUNTER_VAL as usize) as u16;
+    let leaders = find_leaders(&memory, rom_end, &quirks);
+
+    let mut functions = String::new();
+    let mut table = String::new();
+    for &start in &leaders {
+        let mut body = String::new();
+        let mut pc = start;
+        let mut cycles = 0;
+        loop {
+            let instruction = memory.parse_instruction(pc).filter(|_| pc + 2 <= rom_end);
+            let opcode: String = memory.get_data_bytes(pc as usize, 2).iter().map(|byte| format!("{:02X}", byte)).collect();
+            let comment = format!("// 0x{:03X} {}", pc, opcode);
+            let is_leader = pc != start && leaders.contains(&pc);
+            match instruction {
+                Some(instruction) if !is_leader && cycles < MAX_BLOCK_LEN => {
+                    if let Some(code) = straight(instruction, &quirks) {
+                        writeln!(body, "    {}", format!("{} {}", code, comment).trim_start()).unwrap();
+                        cycles += 1;
+                        pc += 2;
+                        continue;
+                    }
+                    if let Some(code) = terminator(pc, instruction, &quirks) {
+                        writeln!(body, "    {} {}", code, comment).unwrap();
+                        cycles += 1;
+                        pc += 2;
+                        break;
+                    }
+                },
+                _ => (),
+            }
+            // Following instruction is processed by another block or by interpreter.
+            writeln!(body, "    s.pc = 0x{:03X};", pc).unwrap();
+            break;
+        }
+        if cycles == 0 {
+            continue;
+        }
+
+        writeln!(functions, "\nfn block_{:03x}(s: &mut State) {{\n{}}}", start, body).unwrap();
+        writeln!(table, "        Block {{ start: 0x{:03X}, end: 0x{:03X}, cycles: {}, run: block_{:03x} }},",
+            start, pc, cycles, start).unwrap();
+    }
+
+    let mut out = String::new();
+    writeln!(out, "//! Recompiled from {} by `chipmunk recompile`. Build it against the chipmunk library.\n", name).unwrap();
+    out.push_str("\
+use chipmunk::engine::device::Device;
+use chipmunk::engine::frontend::{self, Exit, Pacing};
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::recompile::{Block, Program, Runtime, State};
+
+");
+    writeln!(out, "const ROM: [u8; {}] = [", rom.len()).unwrap();
+    for line in rom.chunks(16) {
+        let bytes: Vec<String> = line.iter().map(|byte| format!("0x{:02X},", byte)).collect();
+        writeln!(out, "    {}", bytes.join(" ")).unwrap();
+    }
+    out.push_str("];\n");
+    out.push_str(&functions);
+    writeln!(out, "
+pub static PROGRAM: Program = Program {{
+    rom: &ROM,
+    quirks: Quirks {{
+        vf_reset: {},
+        memory_increment: {},
+        shift_vy: {},
+        jump_vx: {},
+        clip_sprites: {},
+        display_wait: {},
+    }},
+    blocks: &[
+{}    ],
+}};",
+        quirks.vf_reset, quirks.memory_increment, quirks.shift_vy, quirks.jump_vx, quirks.clip_sprites, quirks.display_wait,
+        table).unwrap();
+    out.push_str("
+/// Run program on terminal. The first argument is count of instructions per frame.
+fn main() {
+    let cycles_per_frame = std::env::args().nth(1).and_then(|arg| arg.parse().ok()).unwrap_or(15);
+    let mut machine = Machine::new(PROGRAM.rom, PROGRAM.quirks);
+    let mut runtime = Runtime::new(&PROGRAM);
+    let mut device = Device::new().expect(\"Failed to initialize terminal\");
+
+    let pacing = Pacing::Frames{ cycles_per_frame };
+    let exit = frontend::run_with(&mut machine, &mut device, pacing, |_| Ok(()), |machine, budget| runtime.step(machine, budget));
+    drop(device);
+    match exit {
+        Ok(Exit::Quit) => (),
+        Ok(Exit::Fault(fault)) => println!(\"{}\", fault),
+        Err(err) => println!(\"{}\", err),
+    }
+}
+");
+    out
+}
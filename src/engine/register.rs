@@ -1,24 +1,29 @@
 use std::fmt;
 
 extern crate rand;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use super::isa;
+use super::quirks::Quirks;
 
-/// @brief
-const GENERAL_REGISTERS_CNT: usize = 16usize;
-const STACK_POINTER_CNT: usize = 16usize;
-const INIT_PROGRAM_COUNTER_VAL: u16 = 0x200u16;
+pub const GENERAL_REGISTERS_CNT: usize = 16usize;
+pub const STACK_POINTER_CNT: usize = 16usize;
+pub const INIT_PROGRAM_COUNTER_VAL: u16 = 0x200u16;
 
 pub enum SideEffect {
     Draw{ pos: (u8, u8), n: u8, l: u16 },   // 
     ClearDisplay,                           // 
-    MemDump{ dump_vals: Vec<u8>, l: u16 },  //
+    MemDump{ dump_vals: [u8; 16], count: u8, l: u16 }, // Store first `count` values into [l, l + count).
     MemRead{ count: u8, l: u16 },           //
     WaitKeyPress{ r: u8 },                  // Machine should until new key press.
     CheckKeyPressed{ key: u8 },             // Check whether key is pressed (true), or not (false).
     CheckKeyReleased{ key: u8 },            // Check whether key is pressed (false), or not (true).
+    ReadDelay{ r: u8 },                     // Store delay timer value into Vr.
+    SetDelay{ val: u8 },                    // Set delay timer.
+    SetSound{ val: u8 },                    // Set sound timer.
 }
 
 /// Provides the side effect from timer registers update procedure.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TimerSideEffect {
     /// Do nothing.
     None,   
@@ -26,14 +31,20 @@ pub enum TimerSideEffect {
     Beep,   
 }
 
+#[derive(Clone)]
 pub struct Registers {
     g: [u8; GENERAL_REGISTERS_CNT], // General purpose registers
                                     // Flag instruction register (carry & borrow, collision).
     sl: u16,                        // Memory address register from SL.
     pc: u16,                        // Program counter register.
     spst: Vec<u16>,                 // Stack pointer stack.
-    dt: u8,                         // Delay timer register.
-    st: u8,                         // Sound timer register.
+    rng: StdRng,                    // Random number generator of 0xCxkk.
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Registers::new()
+    }
 }
 
 impl Registers {
@@ -43,17 +54,40 @@ impl Registers {
             sl: 0,
             pc: INIT_PROGRAM_COUNTER_VAL,
             spst: Vec::<u16>::with_capacity(STACK_POINTER_CNT),
-            dt: 0,
-            st: 0,
+            rng: StdRng::from_entropy(),
         }
     }
 
+    /// Reset random number generator with given seed, so that 0xCxkk gives the same sequence.
+    pub fn seed_random(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
     pub fn get_pc(&self) -> u16 { self.pc }
 
+    pub fn get_l(&self) -> u16 { self.sl }
+
+    /// Get count of return addresses pushed into stack.
+    pub fn get_sp(&self) -> u8 { self.spst.len() as u8 }
+
+    /// Get return addresses pushed into stack, from the bottom.
+    /// Each address is the address of CALL instruction.
+    pub fn stack(&self) -> &[u16] { &self.spst }
+
+    /// Get all general registers from V0 to VF.
+    pub fn general_registers(&self) -> &[u8; GENERAL_REGISTERS_CNT] { &self.g }
+
     fn set_pc(&mut self, new_pc: u16) {
         self.pc = new_pc;
     }
 
+    /// Overwrite general registers, L and PC at once. Used by JIT and recompiled code.
+    pub(crate) fn load(&mut self, g: [u8; GENERAL_REGISTERS_CNT], l: u16, pc: u16) {
+        self.g = g;
+        self.sl = l;
+        self.pc = pc;
+    }
+
     pub fn increase_pc(&mut self, inst_count: u16) {
         self.pc += inst_count << 1;
     }
@@ -62,14 +96,14 @@ impl Registers {
         self.set_general_register(0xFu8, if is_set { 1 } else { 0 });
     }
 
-    pub fn update_registers(&mut self, instruction: isa::Instruction) -> Option<SideEffect> {
+    pub fn update_registers(&mut self, instruction: isa::Instruction, quirks: &Quirks) -> Option<SideEffect> {
         type Inst = isa::Instruction;
 
         let (pc_increment, side_effect) = match instruction {
-            Inst::Ignore => (1, None), // 0x0___
+            Inst::Ignore(_) => (1, None), // 0x0___
             Inst::ClearDisplay => (1, Some(SideEffect::ClearDisplay)), // 0x00E0
             Inst::ReturnSubroutine => { // 0x00EE
-                assert!(self.spst.is_empty() == false);
+                assert!(!self.spst.is_empty());
                 let new_pc = self.spst.pop().unwrap();
                 self.set_pc(new_pc);
                 (1, None)
@@ -109,14 +143,17 @@ impl Registers {
             },
             Inst::OrRegV{ r, f } => { // 0x8xy1
                 self.g[r as usize] |= self.general_register(f);
+                if quirks.vf_reset { self.update_vf(false); }
                 (1, None)
             },
             Inst::AndRegV{ r, f } => { // 0x8xy2
                 self.g[r as usize] &= self.general_register(f);
+                if quirks.vf_reset { self.update_vf(false); }
                 (1, None)
             },
             Inst::XorRegV{ r, f } => { // 0x8xy3
                 self.g[r as usize] ^= self.general_register(f);
+                if quirks.vf_reset { self.update_vf(false); }
                 (1, None)
             },
             Inst::AddRegV{ r, f } => { // 0x8xy4
@@ -132,8 +169,9 @@ impl Registers {
                 (1, None)
             },
             Inst::ShrRegV{ r, f } => { // 0x8xy6
-                self.update_vf((self.general_register(f) & 0b01) != 0);
-                let new_value = self.general_register(f) >> 1;
+                let src = if quirks.shift_vy { self.general_register(f) } else { self.general_register(r) };
+                self.update_vf((src & 0b01) != 0);
+                let new_value = src >> 1;
                 self.set_general_register(r, new_value);
                 (1, None)
             },
@@ -144,8 +182,9 @@ impl Registers {
                 (1, None)
             },
             Inst::ShlRegV{ r, f } => { // 0x8x_E
-                self.update_vf((self.general_register(f) & 0x80) != 0);
-                let new_value = self.general_register(f) << 1;
+                let src = if quirks.shift_vy { self.general_register(f) } else { self.general_register(r) };
+                self.update_vf((src & 0x80) != 0);
+                let new_value = src << 1;
                 self.set_general_register(r, new_value);
                 (1, None)
             },
@@ -158,11 +197,14 @@ impl Registers {
                 (1, None)
             },
             Inst::JmpAddrOffReg0(new_pc) => { // 0xBnnn
-                self.set_pc((self.general_register(0) as u16) + new_pc);
+                // With jump quirk, 0xBxnn uses Vx as offset register.
+                let r = if quirks.jump_vx { (new_pc >> 8) as u8 } else { 0 };
+                self.set_pc((self.general_register(r) as u16) + new_pc);
                 (0, None)
             },
             Inst::RndAnd{ r, val } => { // 0xCxkk
-                self.set_general_register(r, rand::random::<u8>() & val);
+                let random = self.rng.gen::<u8>();
+                self.set_general_register(r, random & val);
                 (1, None)
             },
             Inst::DispSpr{ rp, n } => { // 0xDxyn
@@ -180,21 +222,16 @@ impl Registers {
                 // checking key is pressed or not, so leave it not to proceed pc.
                 (0, Some(SideEffect::CheckKeyReleased{ key: self.general_register(r) }))
             },
-            Inst::SetDelayToReg{ r } => { // 0xFx07
-                self.set_general_register(r, self.dt);
-                (1, None)
-            },
+            Inst::SetDelayToReg{ r } => (1, Some(SideEffect::ReadDelay{ r })), // 0xFx07
             Inst::WaitKeyPress{ r } => (1, Some(SideEffect::WaitKeyPress{ r })), // 0xFx0A
             Inst::SetDelayFromReg{ r } => { // 0xFx15
-                self.dt = self.general_register(r);
-                (1, None)
+                (1, Some(SideEffect::SetDelay{ val: self.general_register(r) }))
             },
             Inst::SetSoundFromReg{ r } => { // 0xFx18
-                self.st = self.general_register(r);
-                (1, None)
+                (1, Some(SideEffect::SetSound{ val: self.general_register(r) }))
             },
             Inst::AddRegL{ r } => { // 0xFx1E
-                self.sl += self.general_register(r) as u16;
+                self.sl = self.sl.wrapping_add(self.general_register(r) as u16);
                 (1, None)
             },
             Inst::SetRegLFontAddrFromReg{ r } => { // 0xFx29
@@ -204,21 +241,20 @@ impl Registers {
             Inst::MemDumpBcdFromReg{ r } => { // 0xFx33
                 // Convert value from register Vr into BCD code.
                 // MSB must be in L[0], LSB is L[2].
-                let bcd_code = {
-                    let value = self.general_register(r);
-                    let quotient = value / 10;
-                    vec![quotient / 10, quotient % 10, value % 10]
-                };
-                (1, Some(SideEffect::MemDump{ dump_vals: bcd_code, l: self.sl }))
+                let mut bcd_code = [0u8; 16];
+                let value = self.general_register(r);
+                let quotient = value / 10;
+                bcd_code[..3].copy_from_slice(&[quotient / 10, quotient % 10, value % 10]);
+                (1, Some(SideEffect::MemDump{ dump_vals: bcd_code, count: 3, l: self.sl }))
             },
             Inst::MemDump{ endr } => { // 0xFx55
                 let l = self.sl;
-                self.sl += (endr as u16) + 1u16;
-                (1, Some(SideEffect::MemDump{ dump_vals: self.g[0..=(endr as usize)].to_vec(), l }))
+                if quirks.memory_increment { self.sl += (endr as u16) + 1u16; }
+                (1, Some(SideEffect::MemDump{ dump_vals: self.g, count: endr + 1, l }))
             },
             Inst::MemRead{ endr } => { // 0xFx65
                 let l = self.sl;
-                self.sl += (endr as u16) + 1u16;
+                if quirks.memory_increment { self.sl += (endr as u16) + 1u16; }
                 (1, Some(SideEffect::MemRead{ count: endr + 1, l }))
             }
         };
@@ -234,21 +270,6 @@ impl Registers {
         }
     }
 
-    /// Update timer registers.
-    pub fn update_timers(&mut self) -> TimerSideEffect {
-        if self.dt > 0 {
-            self.dt -= 1;
-        }
-
-        // If sound timer register is not 0, signal beep to device.
-        if self.st > 0 { 
-            self.st -= 1;
-            TimerSideEffect::Beep
-        } else {
-            TimerSideEffect::None
-        }
-    }
-
     /// Set new value into general register.
     pub fn set_general_register(&mut self, r: u8, value: u8) {
         if r > 0x0Fu8 { return; } 
@@ -257,7 +278,7 @@ impl Registers {
 
     /// Get general register value. from V0 to VF.
     /// given value `r` must be ranged in [0, F]. Otherwise, the program will be panic.
-    fn general_register(&self, r: u8) -> u8 {
+    pub fn general_register(&self, r: u8) -> u8 {
         self.g[r as usize]
     }
 }
@@ -273,8 +294,8 @@ impl fmt::Display for Registers {
             self.g[8], self.g[9], self.g[10], self.g[11], 
             self.g[12], self.g[13], self.g[14], self.g[15]);
         let others = format!(
-            "L:{:4},PC:{:4},DT:{:4},ST:{:4}",
-            self.sl, self.pc, self.dt, self.st);
+            "L:{:4},PC:{:4},SP:{:4}",
+            self.sl, self.pc, self.spst.len());
 
         write!(f, "{}, {}\n{}", general_registers0, general_registers1, others)
     }
@@ -0,0 +1,149 @@
+use std::{
+    fs,
+    io::{self, Write},
+};
+
+use super::frontend::{Event, Headless};
+use super::machine::{Machine, Fault};
+use super::quirks::QuirksProfile;
+use super::state::MachineState;
+
+/// Count of frames written in one `keys` line.
+const KEYS_PER_LINE: usize = 32;
+
+/// Provides key input of every frame, which reproduces a run from the start.
+///
+/// Replay file is a text file which consists of lines below. `#` starts a comment.
+/// `keys` lines are appended in order, and each token is the key held through one frame, or `-` for none.
+///
+/// ``` text
+/// quirks chipmunk
+/// seed 0
+/// cycles 15
+/// keys - - 5 5 5 - A
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub profile: QuirksProfile,
+    /// Seed of random number generator.
+    pub seed: u64,
+    /// Count of instructions per frame.
+    pub cycles: usize,
+    /// Key held in each frame.
+    pub keys: Vec<Option<u8>>,
+}
+
+impl Replay {
+    pub fn new(profile: QuirksProfile, seed: u64, cycles: usize) -> Replay {
+        Replay { profile, seed, cycles, keys: Vec::new() }
+    }
+
+    /// Load replay file from given path.
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{} : {}", path, err))?;
+        Replay::parse(&text).map_err(|err| format!("{} : {}", path, err))
+    }
+
+    /// Parse text of replay file.
+    pub fn parse(text: &str) -> Result<Replay, String> {
+        let mut replay = Replay::new(QuirksProfile::Chipmunk, 0, 15);
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() { continue; }
+
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let invalid = |token: &str| format!("line {} : invalid value {}", line_no + 1, token);
+            match tokens[..] {
+                ["quirks", name] => replay.profile = QuirksProfile::from_name(name).ok_or_else(|| invalid(name))?,
+                ["seed", seed] => replay.seed = seed.parse().map_err(|_| invalid(seed))?,
+                ["cycles", cycles] => replay.cycles = cycles.parse().map_err(|_| invalid(cycles))?,
+                ["keys", ref keys @ ..] => {
+                    for &key in keys {
+                        replay.keys.push(match key {
+                            "-" => None,
+                            _ => match u8::from_str_radix(key, 16) {
+                                Ok(key) if key < 16 => Some(key),
+                                _ => return Err(invalid(key)),
+                            },
+                        });
+                    }
+                },
+                _ => return Err(format!("line {} : unknown entry {}", line_no + 1, line)),
+            }
+        }
+
+        Ok(replay)
+    }
+
+    /// Write replay as text which `parse` accepts.
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "quirks {}", self.profile)?;
+        writeln!(out, "seed {}", self.seed)?;
+        writeln!(out, "cycles {}", self.cycles)?;
+        for keys in self.keys.chunks(KEYS_PER_LINE) {
+            write!(out, "keys")?;
+            for key in keys {
+                match key {
+                    Some(key) => write!(out, " {:X}", key)?,
+                    None => write!(out, " -")?,
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Create machine of given ROM with quirks and seed of replay.
+    pub fn machine(&self, rom: &[u8]) -> Machine {
+        let mut machine = Machine::new(rom, self.profile.quirks());
+        machine.seed_random(self.seed);
+        machine
+    }
+
+    /// Play all frames on given machine, or stop right after the instruction which satisfies `stop`.
+    /// Return true if stopped.
+    pub fn play(&self, machine: &mut Machine, mut stop: impl FnMut(&Machine) -> bool) -> Result<bool, Fault> {
+        for &key in &self.keys {
+            if play_frame(machine, key, self.cycles, &mut |_| (), &mut stop)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Schedule key events of replay into given headless front end, which is run with the same cycles per frame.
+    pub fn schedule(&self, headless: &mut Headless) {
+        // Emulation loop releases keys after each instruction until any key up is reported,
+        // so report one first to hold keys through frames.
+        headless.schedule(0, Event::KeyUp(0));
+        for (frame, key) in self.keys.iter().enumerate() {
+            if let Some(key) = *key {
+                headless.schedule(frame as u64, Event::KeyDown(key));
+                headless.schedule(frame as u64 + 1, Event::KeyUp(key));
+            }
+        }
+    }
+}
+
+/// Process one frame as `Machine::run_frame` does, holding given key. `observe` is called right before,
+/// and `stop` right after every instruction.
+/// Return true right after the instruction which satisfies `stop`, leaving the frame unfinished.
+pub fn play_frame(machine: &mut Machine, key: Option<u8>, cycles: usize, observe: &mut impl FnMut(&Machine), stop: &mut impl FnMut(&Machine) -> bool) -> Result<bool, Fault> {
+    if let Some(key) = key {
+        machine.press_key(key);
+    }
+    let end = machine.cycles() + cycles as u64;
+    while machine.cycles() < end && *machine.state() == MachineState::Normal {
+        observe(machine);
+        machine.step()?;
+        if stop(machine) {
+            return Ok(true);
+        }
+    }
+    machine.tick_timers();
+    if let Some(key) = key {
+        machine.release_key(key);
+    }
+    Ok(false)
+}
@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+
+use super::isa::Instruction;
+use super::machine::Machine;
+use super::memory::PROGRAM_START;
+use super::quirks::Quirks;
+
+/// Provides general register Vx.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reg(pub u8);
+
+pub const V0: Reg = Reg(0x0);
+pub const V1: Reg = Reg(0x1);
+pub const V2: Reg = Reg(0x2);
+pub const V3: Reg = Reg(0x3);
+pub const V4: Reg = Reg(0x4);
+pub const V5: Reg = Reg(0x5);
+pub const V6: Reg = Reg(0x6);
+pub const V7: Reg = Reg(0x7);
+pub const V8: Reg = Reg(0x8);
+pub const V9: Reg = Reg(0x9);
+pub const VA: Reg = Reg(0xA);
+pub const VB: Reg = Reg(0xB);
+pub const VC: Reg = Reg(0xC);
+pub const VD: Reg = Reg(0xD);
+pub const VE: Reg = Reg(0xE);
+pub const VF: Reg = Reg(0xF);
+
+/// Provides second operand of instructions which take either byte or register.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Byte(u8),
+    Reg(Reg),
+}
+
+impl From<u8> for Operand {
+    fn from(val: u8) -> Operand { Operand::Byte(val) }
+}
+
+impl From<Reg> for Operand {
+    fn from(reg: Reg) -> Operand { Operand::Reg(reg) }
+}
+
+/// Provides address operand, which is either label or address.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Label(String),
+    Addr(u16),
+}
+
+impl From<&str> for Target {
+    fn from(label: &str) -> Target { Target::Label(label.to_string()) }
+}
+
+impl From<u16> for Target {
+    fn from(addr: u16) -> Target { Target::Addr(addr) }
+}
+
+/// Provides builder of ROM, which is loaded at 0x200.
+///
+/// Methods are named after mnemonics, and take labels or addresses as targets.
+/// Labels may be used before they are defined, and are resolved by `build`.
+///
+/// ```
+/// # use chipmunk::engine::rom::{Rom, V0, V1};
+/// # fn main() -> Result<(), String> {
+/// let rom = Rom::new()
+///     .ld(V0, 5)
+///     .label("loop")
+///     .ld_i("ball")
+///     .drw(V0, V1, 5)
+///     .jp("loop")
+///     .sprite("ball", &[0x20, 0x70, 0xF8, 0x70, 0x20])
+///     .build()?;
+/// assert_eq!(rom, [0x60, 0x05, 0xA2, 0x08, 0xD0, 0x15, 0x12, 0x02, 0x20, 0x70, 0xF8, 0x70, 0x20]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Rom {
+    bytes: Vec<u8>,
+    labels: BTreeMap<String, u16>,
+    /// Offset of instruction whose address is given label.
+    fixups: Vec<(usize, String)>,
+    /// First error found while appending, reported by `build`.
+    error: Option<String>,
+}
+
+impl Rom {
+    pub fn new() -> Rom {
+        Rom::default()
+    }
+
+    /// Get address which the next byte is placed at.
+    pub fn here(&self) -> u16 {
+        (PROGRAM_START + self.bytes.len()) as u16
+    }
+
+    /// Define label at the current address.
+    pub fn label(mut self, name: &str) -> Rom {
+        let addr = self.here();
+        if self.labels.insert(name.to_string(), addr).is_some() {
+            self.error.get_or_insert_with(|| format!("Label {} is defined more than once", name));
+        }
+        self
+    }
+
+    /// Append raw bytes.
+    pub fn bytes(mut self, bytes: &[u8]) -> Rom {
+        self.bytes.extend_from_slice(bytes);
+        self
+    }
+
+    /// Define label of sprite, and append its rows.
+    pub fn sprite(self, name: &str, rows: &[u8]) -> Rom {
+        self.label(name).bytes(rows)
+    }
+
+    /// Append given instruction.
+    pub fn instruction(self, instruction: Instruction) -> Rom {
+        self.bytes(&instruction.encode())
+    }
+
+    /// Append instruction whose 12 bit address is given target.
+    fn with_target(mut self, instruction: fn(u16) -> Instruction, target: Target) -> Rom {
+        match target {
+            Target::Addr(addr) => {
+                if addr > 0xFFF {
+                    self.error.get_or_insert_with(|| format!("Address 0x{:X} is out of range", addr));
+                }
+                self.instruction(instruction(addr))
+            },
+            Target::Label(label) => {
+                self.fixups.push((self.bytes.len(), label));
+                self.instruction(instruction(0))
+            },
+        }
+    }
+
+    pub fn cls(self) -> Rom { self.instruction(Instruction::ClearDisplay) }
+    pub fn ret(self) -> Rom { self.instruction(Instruction::ReturnSubroutine) }
+    pub fn jp(self, target: impl Into<Target>) -> Rom { self.with_target(Instruction::JmpAddr, target.into()) }
+    pub fn call(self, target: impl Into<Target>) -> Rom { self.with_target(Instruction::CallSub, target.into()) }
+    /// JP V0, addr
+    pub fn jp_v0(self, target: impl Into<Target>) -> Rom { self.with_target(Instruction::JmpAddrOffReg0, target.into()) }
+    /// LD I, addr
+    pub fn ld_i(self, target: impl Into<Target>) -> Rom { self.with_target(Instruction::SetRegL, target.into()) }
+
+    pub fn se(self, x: Reg, operand: impl Into<Operand>) -> Rom {
+        self.instruction(match operand.into() {
+            Operand::Byte(val) => Instruction::SkipEq{ r: x.0, val },
+            Operand::Reg(y) => Instruction::SkipRegEq{ r: x.0, f: y.0 },
+        })
+    }
+
+    pub fn sne(self, x: Reg, operand: impl Into<Operand>) -> Rom {
+        self.instruction(match operand.into() {
+            Operand::Byte(val) => Instruction::SkipNeq{ r: x.0, val },
+            Operand::Reg(y) => Instruction::SkipRegNeq{ r: x.0, f: y.0 },
+        })
+    }
+
+    pub fn ld(self, x: Reg, operand: impl Into<Operand>) -> Rom {
+        self.instruction(match operand.into() {
+            Operand::Byte(val) => Instruction::SetByte{ r: x.0, val },
+            Operand::Reg(y) => Instruction::SetRegV{ r: x.0, f: y.0 },
+        })
+    }
+
+    pub fn add(self, x: Reg, operand: impl Into<Operand>) -> Rom {
+        self.instruction(match operand.into() {
+            Operand::Byte(val) => Instruction::AddByte{ r: x.0, val },
+            Operand::Reg(y) => Instruction::AddRegV{ r: x.0, f: y.0 },
+        })
+    }
+
+    pub fn or(self, x: Reg, y: Reg) -> Rom { self.instruction(Instruction::OrRegV{ r: x.0, f: y.0 }) }
+    pub fn and(self, x: Reg, y: Reg) -> Rom { self.instruction(Instruction::AndRegV{ r: x.0, f: y.0 }) }
+    pub fn xor(self, x: Reg, y: Reg) -> Rom { self.instruction(Instruction::XorRegV{ r: x.0, f: y.0 }) }
+    pub fn sub(self, x: Reg, y: Reg) -> Rom { self.instruction(Instruction::SubRegV{ r: x.0, f: y.0 }) }
+    pub fn shr(self, x: Reg, y: Reg) -> Rom { self.instruction(Instruction::ShrRegV{ r: x.0, f: y.0 }) }
+    pub fn subn(self, x: Reg, y: Reg) -> Rom { self.instruction(Instruction::SubNRegV{ r: x.0, f: y.0 }) }
+    pub fn shl(self, x: Reg, y: Reg) -> Rom { self.instruction(Instruction::ShlRegV{ r: x.0, f: y.0 }) }
+    pub fn rnd(self, x: Reg, val: u8) -> Rom { self.instruction(Instruction::RndAnd{ r: x.0, val }) }
+    pub fn drw(self, x: Reg, y: Reg, n: u8) -> Rom { self.instruction(Instruction::DispSpr{ rp: (x.0, y.0), n }) }
+    pub fn skp(self, x: Reg) -> Rom { self.instruction(Instruction::SkipKeyPressed{ r: x.0 }) }
+    pub fn sknp(self, x: Reg) -> Rom { self.instruction(Instruction::SkipKeyReleased{ r: x.0 }) }
+    /// LD Vx, DT
+    pub fn ld_from_dt(self, x: Reg) -> Rom { self.instruction(Instruction::SetDelayToReg{ r: x.0 }) }
+    /// LD Vx, K
+    pub fn ld_key(self, x: Reg) -> Rom { self.instruction(Instruction::WaitKeyPress{ r: x.0 }) }
+    /// LD DT, Vx
+    pub fn ld_dt(self, x: Reg) -> Rom { self.instruction(Instruction::SetDelayFromReg{ r: x.0 }) }
+    /// LD ST, Vx
+    pub fn ld_st(self, x: Reg) -> Rom { self.instruction(Instruction::SetSoundFromReg{ r: x.0 }) }
+    /// ADD I, Vx
+    pub fn add_i(self, x: Reg) -> Rom { self.instruction(Instruction::AddRegL{ r: x.0 }) }
+    /// LD F, Vx
+    pub fn ld_f(self, x: Reg) -> Rom { self.instruction(Instruction::SetRegLFontAddrFromReg{ r: x.0 }) }
+    /// LD B, Vx
+    pub fn ld_b(self, x: Reg) -> Rom { self.instruction(Instruction::MemDumpBcdFromReg{ r: x.0 }) }
+    /// LD [I], Vx
+    pub fn save(self, x: Reg) -> Rom { self.instruction(Instruction::MemDump{ endr: x.0 }) }
+    /// LD Vx, [I]
+    pub fn load(self, x: Reg) -> Rom { self.instruction(Instruction::MemRead{ endr: x.0 }) }
+
+    /// Resolve labels, and get bytes of ROM.
+    pub fn build(&self) -> Result<Vec<u8>, String> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        let mut bytes = self.bytes.clone();
+        for (offset, label) in &self.fixups {
+            let addr = *self.labels.get(label).ok_or_else(|| format!("Unknown label {}", label))?;
+            if addr > 0xFFF {
+                return Err(format!("Label {} at 0x{:X} is out of range", label, addr));
+            }
+            bytes[*offset] |= (addr >> 8) as u8 & 0x0F;
+            bytes[*offset + 1] = addr as u8;
+        }
+        Ok(bytes)
+    }
+
+    /// Get address of label.
+    pub fn addr(&self, label: &str) -> Option<u16> {
+        self.labels.get(label).copied()
+    }
+
+    /// Build ROM and load it into a new machine.
+    pub fn machine(&self, quirks: Quirks) -> Result<Machine, String> {
+        Ok(Machine::new(&self.build()?, quirks))
+    }
+}
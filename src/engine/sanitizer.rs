@@ -0,0 +1,250 @@
+use std::{
+    collections::HashSet,
+    fmt,
+};
+
+use super::isa::Instruction;
+use super::machine::Machine;
+use super::peripheral::{Bus, Display, Input, Timers};
+use super::hook::Hooks;
+use super::memory::{FONT_SIZE, PROGRAM_START, MEMORY_SIZE};
+use super::state::MachineState;
+use super::register::STACK_POINTER_CNT;
+
+const VF: u8 = 0xF;
+
+/// Provides kind of problem found by sanitizer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Issue {
+    /// Memory never initialized by font, ROM or program is read or executed.
+    UninitializedRead,
+    /// Bytes which were read as data (sprite, 0xFx65) are executed.
+    ExecuteData,
+    /// Instruction is executed from odd address.
+    ExecuteOdd,
+    /// Interpreter / font area below 0x200 is written.
+    WriteReserved,
+    /// Memory accessed from L goes past the end of memory.
+    IndexOverflow,
+    /// CALL goes over the stack limit of platform.
+    StackOverflow,
+    /// Value stored into VF is overwritten by flag before it is used.
+    VfClobbered,
+}
+
+/// Provides one problem found by sanitizer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub issue: Issue,
+    pub pc: u16,
+    pub cycle: u64,
+    pub message: String,
+    /// Addresses of CALL instructions which lead to `pc`, innermost first.
+    pub backtrace: Vec<u16>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} at 0x{:03X} (cycle {}) : {}", self.issue, self.pc, self.cycle, self.message)?;
+        for addr in &self.backtrace {
+            write!(f, "\n    called from 0x{:03X}", addr)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check whether given instruction reads VF as a value.
+fn reads_vf(instruction: &Instruction) -> bool {
+    type Inst = Instruction;
+    match *instruction {
+        Inst::SkipEq{ r, .. } | Inst::SkipNeq{ r, .. } | Inst::AddByte{ r, .. }
+        | Inst::SkipKeyPressed{ r } | Inst::SkipKeyReleased{ r }
+        | Inst::SetDelayFromReg{ r } | Inst::SetSoundFromReg{ r } | Inst::AddRegL{ r }
+        | Inst::SetRegLFontAddrFromReg{ r } | Inst::MemDumpBcdFromReg{ r } => r == VF,
+        Inst::SetRegV{ f, .. } => f == VF,
+        Inst::SkipRegEq{ r, f } | Inst::SkipRegNeq{ r, f }
+        | Inst::OrRegV{ r, f } | Inst::AndRegV{ r, f } | Inst::XorRegV{ r, f }
+        | Inst::AddRegV{ r, f } | Inst::SubRegV{ r, f } | Inst::SubNRegV{ r, f }
+        | Inst::ShrRegV{ r, f } | Inst::ShlRegV{ r, f } => r == VF || f == VF,
+        Inst::DispSpr{ rp, .. } => rp.0 == VF || rp.1 == VF,
+        Inst::MemDump{ endr } => endr == VF,
+        _ => false,
+    }
+}
+
+/// Check whether given instruction stores a working value (not a flag) into VF.
+fn writes_vf_value(instruction: &Instruction) -> bool {
+    type Inst = Instruction;
+    match *instruction {
+        Inst::SetByte{ r, .. } | Inst::AddByte{ r, .. } | Inst::RndAnd{ r, .. }
+        | Inst::SetDelayToReg{ r } | Inst::WaitKeyPress{ r }
+        | Inst::SetRegV{ r, .. } | Inst::OrRegV{ r, .. } | Inst::AndRegV{ r, .. } | Inst::XorRegV{ r, .. } => r == VF,
+        Inst::MemRead{ endr } => endr == VF,
+        _ => false,
+    }
+}
+
+/// Check whether given instruction overwrites VF with flag.
+fn writes_vf_flag(instruction: &Instruction, vf_reset: bool) -> bool {
+    type Inst = Instruction;
+    match instruction {
+        Inst::AddRegV{ .. } | Inst::SubRegV{ .. } | Inst::SubNRegV{ .. }
+        | Inst::ShrRegV{ .. } | Inst::ShlRegV{ .. } | Inst::DispSpr{ .. } => true,
+        Inst::OrRegV{ .. } | Inst::AndRegV{ .. } | Inst::XorRegV{ .. } => vf_reset,
+        _ => false,
+    }
+}
+
+/// Provides runtime sanitizer for ROM developers.
+/// `observe` must be called right before every `Machine::step`.
+/// Each issue is reported only once per address.
+pub struct Sanitizer {
+    initialized: Vec<bool>,
+    read_as_data: Vec<bool>,
+    stack_limit: usize,
+    /// Address of instruction which stored working value into VF, not yet used.
+    vf_pending: Option<u16>,
+    reported: HashSet<(Issue, u16)>,
+    reports: Vec<Report>,
+}
+
+impl Sanitizer {
+    /// Create sanitizer for ROM of given length.
+    /// `stack_limit` is count of nested calls allowed by platform (12 for COSMAC VIP).
+    pub fn new(rom_len: usize, stack_limit: usize) -> Sanitizer {
+        let mut initialized = vec![false; MEMORY_SIZE];
+        for (addr, item) in initialized.iter_mut().enumerate() {
+            *item = addr < FONT_SIZE || (PROGRAM_START..PROGRAM_START + rom_len).contains(&addr);
+        }
+
+        Sanitizer {
+            initialized,
+            read_as_data: vec![false; MEMORY_SIZE],
+            stack_limit,
+            vf_pending: None,
+            reported: HashSet::new(),
+            reports: Vec::new(),
+        }
+    }
+
+    /// Create sanitizer with stack limit of this interpreter.
+    pub fn with_default_limit(rom_len: usize) -> Sanitizer {
+        Sanitizer::new(rom_len, STACK_POINTER_CNT)
+    }
+
+    /// Get all reports found so far.
+    pub fn reports(&self) -> &[Report] { &self.reports }
+
+    fn report<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&mut self, machine: &Machine<B, D, I, T, H>, issue: Issue, message: String) {
+        let pc = machine.registers().get_pc();
+        if !self.reported.insert((issue, pc)) {
+            return;
+        }
+
+        let backtrace = machine.registers().stack().iter().rev().copied().collect();
+        self.reports.push(Report{ issue, pc, cycle: machine.cycles(), message, backtrace });
+    }
+
+    /// Check memory range [from, from + count) which is read as data.
+    fn check_read<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&mut self, machine: &Machine<B, D, I, T, H>, from: usize, count: usize) {
+        let end = (from + count).min(MEMORY_SIZE);
+        if let Some(addr) = (from..end).find(|&addr| !self.initialized[addr]) {
+            self.report(machine, Issue::UninitializedRead, format!("data at 0x{:03X} is never initialized", addr));
+        }
+        for addr in from..end {
+            self.read_as_data[addr] = true;
+        }
+    }
+
+    /// Check memory range [from, from + count) which is written.
+    fn check_write<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&mut self, machine: &Machine<B, D, I, T, H>, from: usize, count: usize) {
+        if from < PROGRAM_START {
+            self.report(machine, Issue::WriteReserved, format!("0x{:03X} is in interpreter area", from));
+        }
+        for addr in from..(from + count).min(MEMORY_SIZE) {
+            self.initialized[addr] = true;
+        }
+    }
+
+    /// Check L and count of accessed bytes fit in memory.
+    fn check_index<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&mut self, machine: &Machine<B, D, I, T, H>, l: usize, count: usize) {
+        if l + count > MEMORY_SIZE {
+            self.report(machine, Issue::IndexOverflow,
+                format!("L = 0x{:03X} with {} bytes goes past the end of memory", l, count));
+        }
+    }
+
+    /// Check the instruction that machine is about to process.
+    pub fn observe<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&mut self, machine: &Machine<B, D, I, T, H>) {
+        if *machine.state() != MachineState::Normal {
+            return;
+        }
+
+        let registers = machine.registers();
+        let pc = registers.get_pc();
+        let pc_usize = pc as usize;
+        if pc_usize + 1 < MEMORY_SIZE {
+            if pc % 2 == 1 {
+                self.report(machine, Issue::ExecuteOdd, "instruction is not aligned".to_string());
+            }
+            if !self.initialized[pc_usize] || !self.initialized[pc_usize + 1] {
+                self.report(machine, Issue::UninitializedRead, "executing never initialized memory".to_string());
+            }
+            if self.read_as_data[pc_usize] || self.read_as_data[pc_usize + 1] {
+                self.report(machine, Issue::ExecuteData, "executing bytes which were read as data".to_string());
+            }
+        }
+
+        let instruction = match machine.bus().fetch(pc) {
+            Some(instruction) => instruction,
+            None => return,
+        };
+
+        let l = registers.get_l() as usize;
+        match instruction {
+            Instruction::DispSpr{ n, .. } => {
+                self.check_index(machine, l, n as usize);
+                self.check_read(machine, l, n as usize);
+            },
+            Instruction::MemRead{ endr } => {
+                self.check_index(machine, l, endr as usize + 1);
+                self.check_read(machine, l, endr as usize + 1);
+            },
+            Instruction::MemDump{ endr } => {
+                self.check_index(machine, l, endr as usize + 1);
+                self.check_write(machine, l, endr as usize + 1);
+            },
+            Instruction::MemDumpBcdFromReg{ .. } => {
+                self.check_index(machine, l, 3);
+                self.check_write(machine, l, 3);
+            },
+            Instruction::CallSub(_) if registers.stack().len() >= self.stack_limit => {
+                self.report(machine, Issue::StackOverflow,
+                    format!("call depth goes over the limit {}", self.stack_limit));
+            },
+            _ => (),
+        }
+
+        // VF working register tracking.
+        if reads_vf(&instruction) {
+            self.vf_pending = None;
+        }
+        if writes_vf_flag(&instruction, machine.quirks().vf_reset) {
+            let clobbers_result = match instruction {
+                Instruction::AddRegV{ r, .. } | Instruction::SubRegV{ r, .. } | Instruction::SubNRegV{ r, .. }
+                | Instruction::ShrRegV{ r, .. } | Instruction::ShlRegV{ r, .. }
+                | Instruction::OrRegV{ r, .. } | Instruction::AndRegV{ r, .. } | Instruction::XorRegV{ r, .. } => r == VF,
+                _ => false,
+            };
+            if clobbers_result {
+                self.report(machine, Issue::VfClobbered, "result stored into VF is overwritten by flag".to_string());
+            } else if let Some(set_pc) = self.vf_pending {
+                self.report(machine, Issue::VfClobbered,
+                    format!("VF stored at 0x{:03X} is overwritten by flag before use", set_pc));
+            }
+            self.vf_pending = None;
+        } else if writes_vf_value(&instruction) {
+            self.vf_pending = Some(pc);
+        }
+    }
+}
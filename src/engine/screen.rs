@@ -1,71 +1,130 @@
+use super::peripheral::Display;
+
 
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
 
-#[derive(PartialEq)]
-pub enum PixelState {
-    Drawn,
-    Erased,
-}
+/// Largest resolution of a screen, which is hi-res mode of SUPER-CHIP and XO-CHIP.
+pub const MAX_WIDTH: usize = 128;
+pub const MAX_HEIGHT: usize = 64;
 
-pub struct DrawMessage {
-    pub pos: (u8, u8),
-    pub state: PixelState,
-}
+/// Provides one packed row of pixels. Pixel `x` is bit `127 - x`, so the leftmost pixel is
+/// the most significant bit regardless of screen width.
+pub type Row = u128;
+
+/// Provides set of rows changed by drawing. Bit `y` is set if row `y` is changed.
+pub type DirtyRows = u64;
 
+/// Provides monochrome screen, stored as one packed row per line.
+/// Drawing one sprite row is one shift, one XOR and one collision test.
+/// Hi-res screen is `Screen::with_resolution(128, 64)`, and XO-CHIP planes are one `Screen` per plane.
+#[derive(Clone)]
 pub struct Screen {
-    screen_buffer: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    rows: [Row; MAX_HEIGHT],
+    width: usize,
+    height: usize,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Screen::new()
+    }
 }
 
 impl Screen {
     pub fn new() -> Screen {
+        Screen::with_resolution(SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+
+    /// Create screen of given resolution. Width must be in [8, 128], and height in [1, 64].
+    pub fn with_resolution(width: usize, height: usize) -> Screen {
+        assert!((8..=MAX_WIDTH).contains(&width) && (1..=MAX_HEIGHT).contains(&height));
         Screen {
-            screen_buffer: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            rows: [0; MAX_HEIGHT],
+            width,
+            height,
         }
     }
 
-    fn draw_xor(&mut self, (x, y): (u8, u8)) -> PixelState {
-        let px = &mut self.screen_buffer[(y as usize) * SCREEN_WIDTH + (x as usize)];
-        if *px == true {
-            *px = false;
-            PixelState::Erased
+    pub fn width(&self) -> usize { self.width }
+
+    pub fn height(&self) -> usize { self.height }
+
+    /// Get mask of pixels inside the screen width.
+    fn width_mask(&self) -> Row {
+        !0 << (MAX_WIDTH - self.width)
+    }
+
+    /// Place sprite byte at column `x` of row. Pixels going over the right edge are
+    /// discarded if `clip` is true, or wrap around to the left edge otherwise.
+    fn sprite_row(&self, byte: u8, x: usize, clip: bool) -> Row {
+        let bits = (byte as Row) << (MAX_WIDTH - 8);
+        if clip {
+            (bits >> x) & self.width_mask()
+        } else if self.width == MAX_WIDTH {
+            bits.rotate_right(x as u32)
         } else {
-            *px = true;
-            PixelState::Drawn
+            let shifted = bits >> x;
+            (shifted | shifted << self.width) & self.width_mask()
         }
     }
 
-    pub fn draw(&mut self, (mut x, mut y): (u8, u8), bytes: &[u8]) -> (Vec<DrawMessage>, bool) {
-        let mut result = Vec::<DrawMessage>::new();
+    /// Draw sprite `bytes` at given position with XOR, and return dirty rows
+    /// and whether any pixel is erased.
+    ///
+    /// Start position always wraps around the screen. If `clip` is true, sprite pixels
+    /// going over the screen edges are discarded. Otherwise they wrap around.
+    pub fn draw(&mut self, (x, y): (u8, u8), bytes: &[u8], clip: bool) -> (DirtyRows, bool) {
+        let x = x as usize % self.width;
+        let mut y = y as usize % self.height;
+        let mut dirty = 0;
         let mut is_any_erased = false;
 
-        x %= SCREEN_WIDTH as u8;
-        y %= SCREEN_HEIGHT as u8;
-
-        let origx = x;
-        for byte in bytes {
-            for i in (0..8).rev() {
-                if byte & (0b01 << i) != 0x00 { // XORDraw flag
-                    let state = self.draw_xor((x, y));
-                    is_any_erased |= state == PixelState::Erased;
-                    result.push(DrawMessage{pos: (x, y), state});
-                } 
-
-                x = (x + 1) % (SCREEN_WIDTH as u8);
+        for &byte in bytes {
+            let sprite = self.sprite_row(byte, x, clip);
+            is_any_erased |= self.rows[y] & sprite != 0;
+            self.rows[y] ^= sprite;
+            if sprite != 0 {
+                dirty |= 1 << y;
             }
 
-            x = origx;
-            y = (y + 1) % (SCREEN_HEIGHT as u8);
+            y = (y + 1) % self.height;
+            if clip && y == 0 { break; }
         }
 
-        (result, is_any_erased)
+        (dirty, is_any_erased)
+    }
+
+    /// Get packed pixels of given row.
+    pub fn row(&self, y: u8) -> Row {
+        self.rows[y as usize]
+    }
+
+    /// Check whether pixel of given position is drawn or not. Positions outside the screen are not drawn.
+    pub fn is_drawn(&self, (x, y): (u8, u8)) -> bool {
+        let (x, y) = (x as usize, y as usize);
+        x < self.width && y < self.height && self.rows[y] & (1 << (MAX_WIDTH - 1 - x)) != 0
     }
 
     pub fn clear(&mut self) {
-        for pixel in self.screen_buffer.iter_mut() {
-            *pixel = false;
-        }
+        self.rows = [0; MAX_HEIGHT];
     }
 }
 
+impl Display for Screen {
+    fn clear(&mut self) {
+        Screen::clear(self)
+    }
 
+    fn draw(&mut self, pos: (u8, u8), bytes: &[u8], clip: bool) -> (DirtyRows, bool) {
+        Screen::draw(self, pos, bytes, clip)
+    }
+
+    fn row(&self, y: u8) -> Row {
+        Screen::row(self, y)
+    }
+
+    fn is_drawn(&self, pos: (u8, u8)) -> bool {
+        Screen::is_drawn(self, pos)
+    }
+}
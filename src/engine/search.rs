@@ -0,0 +1,168 @@
+use std::{
+    collections::{HashSet, hash_map::DefaultHasher},
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use super::machine::Machine;
+use super::memory::MEMORY_SIZE;
+use super::peripheral::Timers;
+use super::quirks::QuirksProfile;
+use super::replay::{Replay, play_frame};
+
+/// Provides state which search tries to reach.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    /// PC becomes given address.
+    Pc(u16),
+    /// Byte of given address becomes given value.
+    Memory{ addr: u16, value: u8 },
+}
+
+impl Target {
+    pub fn is_reached(&self, machine: &Machine) -> bool {
+        self.distance(machine) == 0
+    }
+
+    /// Get how far machine is from target, which beam search keeps the nearest states by.
+    pub fn distance(&self, machine: &Machine) -> u32 {
+        match *self {
+            Target::Pc(addr) => (machine.registers().get_pc() as i32 - addr as i32).unsigned_abs(),
+            Target::Memory{ addr, value } => {
+                let byte = machine.memory().get_data_bytes(addr as usize, 1)[0];
+                (byte as i32 - value as i32).unsigned_abs()
+            },
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Pc(addr) => write!(f, "PC = 0x{:03X}", addr),
+            Target::Memory{ addr, value } => write!(f, "memory[0x{:03X}] = 0x{:02X}", addr, value),
+        }
+    }
+}
+
+/// Provides how states of each frame are expanded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Expand every state, so found input is the shortest.
+    BreadthFirst,
+    /// Expand only given count of states nearest to target per frame.
+    Beam{ width: usize },
+}
+
+/// Provides options of search.
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub target: Target,
+    pub strategy: Strategy,
+    pub profile: QuirksProfile,
+    /// Seed of random number generator.
+    pub seed: u64,
+    /// Count of instructions per frame.
+    pub cycles: usize,
+    /// Maximum count of frames of found input.
+    pub max_frames: u64,
+    /// Inputs tried in every frame. `None` presses no key.
+    pub keys: Vec<Option<u8>>,
+    /// Search gives up after this count of distinct states.
+    pub max_states: usize,
+}
+
+impl SearchOptions {
+    /// Create options trying no key and every key per frame.
+    pub fn new(target: Target) -> SearchOptions {
+        SearchOptions {
+            target,
+            strategy: Strategy::BreadthFirst,
+            profile: QuirksProfile::Chipmunk,
+            seed: 0,
+            cycles: 15,
+            max_frames: 600,
+            keys: std::iter::once(None).chain((0..16).map(Some)).collect(),
+            max_states: 100_000,
+        }
+    }
+}
+
+/// Provides result of search.
+#[derive(Debug)]
+pub struct SearchResult {
+    /// Input which reaches target, if found.
+    pub replay: Option<Replay>,
+    /// Count of distinct states visited.
+    pub states: usize,
+    /// Count of searched frames.
+    pub frames: u64,
+}
+
+/// Get hash of registers, timers, memory and screen of machine, which decides whether states are the same.
+/// Random number generator and counters are not included.
+pub fn state_hash(machine: &Machine) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let registers = machine.registers();
+    registers.general_registers().hash(&mut hasher);
+    (registers.get_l(), registers.get_pc(), registers.stack()).hash(&mut hasher);
+    (machine.timers().delay(), machine.timers().sound(), machine.state()).hash(&mut hasher);
+    machine.memory().get_data_bytes(0, MEMORY_SIZE).hash(&mut hasher);
+    let screen = machine.screen();
+    for y in 0..screen.height() as u8 {
+        screen.row(y).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Search key input per frame which reaches target from the start of ROM.
+/// States are cloned per input, and states already visited are pruned. Inputs which fault are dropped.
+/// Target is checked after every instruction, so it may be reached in the middle of the last frame.
+pub fn search(rom: &[u8], options: &SearchOptions) -> SearchResult {
+    let mut replay = Replay::new(options.profile, options.seed, options.cycles);
+    let root = replay.machine(rom);
+    let mut result = SearchResult { replay: None, states: 1, frames: 0 };
+    if options.target.is_reached(&root) {
+        result.replay = Some(replay);
+        return result;
+    }
+
+    let mut visited = HashSet::new();
+    visited.insert(state_hash(&root));
+    let mut frontier = vec![(root, Vec::new())];
+    while result.frames < options.max_frames && !frontier.is_empty() {
+        result.frames += 1;
+        let mut next = Vec::new();
+        for (machine, keys) in &frontier {
+            for &key in &options.keys {
+                let mut child = machine.clone();
+                let mut child_keys: Vec<Option<u8>> = keys.clone();
+                child_keys.push(key);
+                match play_frame(&mut child, key, options.cycles, &mut |_| (), &mut |machine| options.target.is_reached(machine)) {
+                    Ok(true) => {
+                        replay.keys = child_keys;
+                        result.replay = Some(replay);
+                        result.states = visited.len();
+                        return result;
+                    },
+                    Ok(false) => (),
+                    Err(_) => continue,
+                }
+                if visited.insert(state_hash(&child)) {
+                    if visited.len() >= options.max_states {
+                        result.states = visited.len();
+                        return result;
+                    }
+                    next.push((child, child_keys));
+                }
+            }
+        }
+        if let Strategy::Beam{ width } = options.strategy {
+            next.sort_by_key(|(machine, _)| options.target.distance(machine));
+            next.truncate(width);
+        }
+        frontier = next;
+    }
+    result.states = visited.len();
+    result
+}
@@ -1,6 +1,7 @@
 /// Provides global state of CHIP-8 machine.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MachineState {
     Normal,                 // Process machine normally.
     WaitKeyPress{ r: u8 },  // Wait for key press, processing instruction should be paused.
-}
\ No newline at end of file
+    WaitDisplay,            // Wait for next 60Hz frame after drawing (display wait quirk).
+}
@@ -0,0 +1,188 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+};
+
+use super::memory::{MEMORY_SIZE, PROGRAM_START};
+
+/// Provides address to label and source line mapping of ROM.
+///
+/// Symbol file is a text file which consists of lines below. `#` starts a comment.
+///
+/// ``` text
+/// label 0x2A4 draw_player     # Address 0x2A4 is labeled `draw_player`.
+/// line 0x2A4 game.8o 120      # Address 0x2A4 is assembled from line 120 of `game.8o`.
+/// ```
+///
+/// Symbol output of Octo, which has `:const name value` for every label, is also accepted.
+/// Values inside program memory are taken as labels, and other constants and Octo directives are ignored.
+/// Such a value never replaces a `label` entry or a label-like name, so that a numeric constant
+/// which happens to be in program memory, such as `:const SCORE_MAX 1000`, does not shadow a label.
+#[derive(Debug, Default, Clone)]
+pub struct SymbolTable {
+    labels: BTreeMap<u16, String>,
+    lines: BTreeMap<u16, (String, usize)>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    /// Load symbol file from given path.
+    pub fn load(path: &str) -> Result<SymbolTable, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{} : {}", path, err))?;
+        SymbolTable::parse(&text).map_err(|err| format!("{} : {}", path, err))
+    }
+
+    /// Parse text of symbol file.
+    pub fn parse(text: &str) -> Result<SymbolTable, String> {
+        let mut table = SymbolTable::new();
+        let mut consts = BTreeMap::<u16, &str>::new();
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() { continue; }
+
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let parse_addr = |token: &str| {
+                u16::from_str_radix(strip_hex_prefix(token).unwrap_or(token), 16)
+                    .map_err(|_| format!("line {} : invalid address {}", line_no + 1, token))
+            };
+            match tokens[..] {
+                ["label", addr, name] => {
+                    table.add_label(parse_addr(addr)?, name);
+                },
+                ["line", addr, file, source_line] => {
+                    let source_line = source_line.parse()
+                        .map_err(|_| format!("line {} : invalid line number {}", line_no + 1, source_line))?;
+                    table.add_line(parse_addr(addr)?, file, source_line);
+                },
+                [":const", name, value] => {
+                    let value = match strip_hex_prefix(value) {
+                        Some(hex) => u16::from_str_radix(hex, 16),
+                        None => value.parse(),
+                    }.map_err(|_| format!("line {} : invalid value {}", line_no + 1, value))?;
+                    if (PROGRAM_START as u16..MEMORY_SIZE as u16).contains(&value) {
+                        let existing = consts.entry(value).or_insert(name);
+                        if is_constant_name(existing) && !is_constant_name(name) {
+                            *existing = name;
+                        }
+                    }
+                },
+                [directive, ..] if directive.starts_with(':') => {},
+                _ => return Err(format!("line {} : unknown entry {}", line_no + 1, line)),
+            }
+        }
+
+        for (addr, name) in consts {
+            table.labels.entry(addr).or_insert_with(|| name.to_string());
+        }
+        Ok(table)
+    }
+
+    pub fn add_label(&mut self, addr: u16, name: &str) {
+        self.labels.insert(addr, name.to_string());
+    }
+
+    pub fn add_line(&mut self, addr: u16, file: &str, line: usize) {
+        self.lines.insert(addr, (file.to_string(), line));
+    }
+
+    /// Get label of exactly given address.
+    pub fn label(&self, addr: u16) -> Option<&str> {
+        self.labels.get(&addr).map(|name| name.as_str())
+    }
+
+    /// Get source file and line of exactly given address.
+    pub fn line(&self, addr: u16) -> Option<(&str, usize)> {
+        self.lines.get(&addr).map(|(file, line)| (file.as_str(), *line))
+    }
+
+    /// Get address of given label.
+    pub fn addr(&self, name: &str) -> Option<u16> {
+        self.labels.iter().find(|(_, label)| *label == name).map(|(&addr, _)| addr)
+    }
+
+    /// Get label of given address, or the nearest label before it with offset such as `draw_player+4`.
+    pub fn locate(&self, addr: u16) -> Option<String> {
+        let (&start, name) = self.labels.range(..=addr).next_back()?;
+        if start == addr {
+            Some(name.clone())
+        } else {
+            Some(format!("{}+{}", name, addr - start))
+        }
+    }
+
+    /// Describe address with its label and source line, such as `0x2A4 draw_player (game.8o:120)`.
+    pub fn describe(&self, addr: u16) -> String {
+        let mut text = format!("0x{:03X}", addr);
+        if let Some(name) = self.locate(addr) {
+            text.push_str(&format!(" {}", name));
+        }
+        if let Some((file, line)) = self.line(addr) {
+            text.push_str(&format!(" ({}:{})", file, line));
+        }
+        text
+    }
+
+    /// Get address of decimal or `0x` prefixed number, label, or label with offset such as `draw_player+4`.
+    pub fn resolve(&self, token: &str) -> Result<u16, String> {
+        let number = |text: &str| match strip_hex_prefix(text) {
+            Some(hex) => u16::from_str_radix(hex, 16).ok(),
+            None => text.parse().ok(),
+        };
+        if let Some(addr) = number(token) {
+            return Ok(addr);
+        }
+        let (name, offset) = match token.rsplit_once('+') {
+            Some((name, offset)) => (name, number(offset).ok_or_else(|| format!("Invalid address {}", token))?),
+            None => (token, 0),
+        };
+        self.addr(name)
+            .and_then(|addr| addr.checked_add(offset))
+            .ok_or_else(|| format!("Unknown symbol {}", token))
+    }
+
+    /// Check whether any source line is known.
+    pub fn has_lines(&self) -> bool {
+        !self.lines.is_empty()
+    }
+
+    /// Check whether neither label nor source line is known.
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.lines.is_empty()
+    }
+
+    /// Get labels in address order.
+    pub fn labels(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.labels.iter().map(|(&addr, name)| (addr, name.as_str()))
+    }
+
+    /// Get source file and line of addresses in address order.
+    pub fn lines(&self) -> impl Iterator<Item = (u16, &str, usize)> {
+        self.lines.iter().map(|(&addr, (file, line))| (addr, file.as_str(), *line))
+    }
+
+    /// Write symbol file, which `parse` reads back.
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        for (addr, name) in &self.labels {
+            writeln!(out, "label 0x{:03X} {}", addr, name)?;
+        }
+        for (addr, (file, line)) in &self.lines {
+            writeln!(out, "line 0x{:03X} {} {}", addr, file, line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Strip `0x` or `0X` prefix of hexadecimal number.
+fn strip_hex_prefix(token: &str) -> Option<&str> {
+    token.strip_prefix("0x").or_else(|| token.strip_prefix("0X"))
+}
+
+/// Check whether name is written like a constant such as `SCORE_MAX`, rather than a label.
+fn is_constant_name(name: &str) -> bool {
+    name.chars().any(|c| c.is_ascii_uppercase()) && !name.chars().any(|c| c.is_ascii_lowercase())
+}
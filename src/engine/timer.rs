@@ -1,5 +1,8 @@
 use std::time;
 
+use super::peripheral::Timers;
+use super::register::TimerSideEffect;
+
 pub struct Timer {
     duration: time::Duration,
     previous_time: time::Instant,
@@ -14,6 +17,11 @@ impl Timer {
         }
     }
 
+    /// Get remaining time until the next tick.
+    pub fn remaining(&self) -> time::Duration {
+        self.duration.saturating_sub(self.previous_time.elapsed())
+    }
+
     /// Tick timer and update variables, return true if ticked.
     /// Otherwise, return false.
     pub fn tick(&mut self) -> bool {
@@ -28,3 +36,40 @@ impl Timer {
         }
     }
 }
+
+/// Provides delay timer and sound timer registers of CHIP-8, which count down in 60Hz.
+#[derive(Debug, Default, Clone)]
+pub struct CountdownTimers {
+    dt: u8, // Delay timer register.
+    st: u8, // Sound timer register.
+}
+
+impl CountdownTimers {
+    pub fn new() -> CountdownTimers {
+        CountdownTimers::default()
+    }
+}
+
+impl Timers for CountdownTimers {
+    fn delay(&self) -> u8 { self.dt }
+
+    fn set_delay(&mut self, value: u8) { self.dt = value; }
+
+    fn sound(&self) -> u8 { self.st }
+
+    fn set_sound(&mut self, value: u8) { self.st = value; }
+
+    fn tick(&mut self) -> TimerSideEffect {
+        if self.dt > 0 {
+            self.dt -= 1;
+        }
+
+        // If sound timer register is not 0, signal beep to device.
+        if self.st > 0 { 
+            self.st -= 1;
+            TimerSideEffect::Beep
+        } else {
+            TimerSideEffect::None
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    fmt,
+    io::{self, Write, BufRead},
+};
+
+use super::isa;
+use super::machine::Machine;
+use super::peripheral::{Bus, Display, Input, Timers};
+use super::hook::Hooks;
+use super::state::MachineState;
+use super::symbols::SymbolTable;
+
+/// Magic bytes of binary trace file. Last byte is format version.
+const BINARY_MAGIC: [u8; 5] = *b"C8TR\x01";
+/// Byte size of one binary trace record.
+const BINARY_RECORD_LEN: usize = 8 + 8 + 2 + 2 + 16 + 2 + 3;
+
+/// Provides machine state right before one instruction is processed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    pub cycle: u64,
+    pub frame: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub v: [u8; 16],
+    pub l: u16,
+    pub sp: u8,
+    pub dt: u8,
+    pub st: u8,
+}
+
+impl TraceRecord {
+    /// Capture the instruction that machine is about to process.
+    /// If machine is waiting for something, no instruction will be processed so return None.
+    pub fn capture<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(machine: &Machine<B, D, I, T, H>) -> Option<TraceRecord> {
+        if *machine.state() != MachineState::Normal {
+            return None;
+        }
+
+        let registers = machine.registers();
+        let pc = registers.get_pc();
+        let bytes = machine.bus().fetch_opcode(pc)?;
+
+        Some(TraceRecord {
+            cycle: machine.cycles(),
+            frame: machine.frames(),
+            pc,
+            opcode: ((bytes[0] as u16) << 8) | bytes[1] as u16,
+            v: *registers.general_registers(),
+            l: registers.get_l(),
+            sp: registers.get_sp(),
+            dt: machine.timers().delay(),
+            st: machine.timers().sound(),
+        })
+    }
+
+    /// Get mnemonic of opcode.
+    pub fn mnemonic(&self) -> String {
+        match isa::parse_instruction(&self.opcode.to_be_bytes()) {
+            Some(instruction) => instruction.to_string(),
+            None => "???".to_string(),
+        }
+    }
+
+    /// Get text line written by `Display`, followed by label of PC if `symbols` has any.
+    pub fn to_text(&self, symbols: &SymbolTable) -> String {
+        match symbols.locate(self.pc) {
+            Some(name) => format!("{}  ; {}", self, name),
+            None => self.to_string(),
+        }
+    }
+
+    /// Get names of fields which are different from given record.
+    /// Cycle and frame numbers are not compared. Timers are compared only when `timers` is true.
+    pub fn diff_fields(&self, other: &TraceRecord, timers: bool) -> Vec<String> {
+        let mut result = Vec::new();
+        if self.pc != other.pc { result.push("PC".to_string()); }
+        if self.opcode != other.opcode { result.push("OP".to_string()); }
+        for (i, (a, b)) in self.v.iter().zip(other.v.iter()).enumerate() {
+            if a != b { result.push(format!("V{:X}", i)); }
+        }
+        if self.l != other.l { result.push("I".to_string()); }
+        if self.sp != other.sp { result.push("SP".to_string()); }
+        if timers && self.dt != other.dt { result.push("DT".to_string()); }
+        if timers && self.st != other.st { result.push("ST".to_string()); }
+        result
+    }
+
+    fn write_binary(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut buffer = [0u8; BINARY_RECORD_LEN];
+        buffer[0..8].copy_from_slice(&self.cycle.to_le_bytes());
+        buffer[8..16].copy_from_slice(&self.frame.to_le_bytes());
+        buffer[16..18].copy_from_slice(&self.pc.to_le_bytes());
+        buffer[18..20].copy_from_slice(&self.opcode.to_le_bytes());
+        buffer[20..36].copy_from_slice(&self.v);
+        buffer[36..38].copy_from_slice(&self.l.to_le_bytes());
+        buffer[38] = self.sp;
+        buffer[39] = self.dt;
+        buffer[40] = self.st;
+        out.write_all(&buffer)
+    }
+
+    fn from_binary(buffer: &[u8; BINARY_RECORD_LEN]) -> TraceRecord {
+        let u64_at = |i: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&buffer[i..i + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        let u16_at = |i: usize| u16::from_le_bytes([buffer[i], buffer[i + 1]]);
+
+        let mut v = [0u8; 16];
+        v.copy_from_slice(&buffer[20..36]);
+        TraceRecord {
+            cycle: u64_at(0),
+            frame: u64_at(8),
+            pc: u16_at(16),
+            opcode: u16_at(18),
+            v,
+            l: u16_at(36),
+            sp: buffer[38],
+            dt: buffer[39],
+            st: buffer[40],
+        }
+    }
+
+    /// Parse one line of text trace which is written by `Display`.
+    pub fn from_text(line: &str) -> Option<TraceRecord> {
+        let mut tokens = line.split_whitespace();
+        let cycle = tokens.next()?.parse().ok()?;
+        let frame = tokens.next()?.parse().ok()?;
+        let pc = u16::from_str_radix(tokens.next()?, 16).ok()?;
+        let opcode = u16::from_str_radix(tokens.next()?, 16).ok()?;
+
+        let mut v = [0u8; 16];
+        for item in v.iter_mut() {
+            *item = u8::from_str_radix(tokens.next()?, 16).ok()?;
+        }
+        let mut field = |name: &str| tokens.next()?.strip_prefix(name).map(|value| value.to_string());
+        let l = u16::from_str_radix(&field("I:")?, 16).ok()?;
+        let sp = u8::from_str_radix(&field("SP:")?, 16).ok()?;
+        let dt = u8::from_str_radix(&field("DT:")?, 16).ok()?;
+        let st = u8::from_str_radix(&field("ST:")?, 16).ok()?;
+
+        Some(TraceRecord { cycle, frame, pc, opcode, v, l, sp, dt, st })
+    }
+}
+
+impl fmt::Display for TraceRecord {
+    /// Write record as one fixed-width line, so that two text traces can be diffed line by line.
+    /// `cycle frame PC OP V0 .. VF I:l SP:sp DT:dt ST:st mnemonic`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:010} {:08} {:04X} {:04X}", self.cycle, self.frame, self.pc, self.opcode)?;
+        for v in &self.v {
+            write!(f, " {:02X}", v)?;
+        }
+        write!(f, " I:{:04X} SP:{:02X} DT:{:02X} ST:{:02X} {}",
+            self.l, self.sp, self.dt, self.st, self.mnemonic())
+    }
+}
+
+/// Provides output format of trace file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    Text,
+    Binary,
+}
+
+/// Provides which records are written into trace.
+/// Range values are inclusive, and `None` means no restriction.
+#[derive(Debug, Clone, Default)]
+pub struct TraceFilter {
+    pub addr: Option<(u16, u16)>,
+    pub frames: Option<(u64, u64)>,
+}
+
+impl TraceFilter {
+    /// Check whether given record passes this filter.
+    pub fn accepts(&self, record: &TraceRecord) -> bool {
+        let addr_ok = self.addr.is_none_or(|(from, to)| from <= record.pc && record.pc <= to);
+        let frame_ok = self.frames.is_none_or(|(from, to)| from <= record.frame && record.frame <= to);
+        addr_ok && frame_ok
+    }
+}
+
+/// Provides trace writer which writes filtered records into given output.
+pub struct TraceWriter {
+    out: Box<dyn Write>,
+    format: TraceFormat,
+    filter: TraceFilter,
+    /// Labels written after records of text trace.
+    symbols: SymbolTable,
+}
+
+impl TraceWriter {
+    /// Create new trace writer. Binary header is written immediately.
+    pub fn new(mut out: Box<dyn Write>, format: TraceFormat, filter: TraceFilter) -> io::Result<Self> {
+        if format == TraceFormat::Binary {
+            out.write_all(&BINARY_MAGIC)?;
+        }
+        Ok(TraceWriter { out, format, filter, symbols: SymbolTable::new() })
+    }
+
+    /// Write labels of given symbols after records of text trace.
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = symbols;
+    }
+
+    /// Write the instruction that machine is about to process, if it passes the filter.
+    pub fn trace<B: Bus, D: Display, I: Input, T: Timers, H: Hooks>(&mut self, machine: &Machine<B, D, I, T, H>) -> io::Result<()> {
+        match TraceRecord::capture(machine) {
+            Some(record) => self.write(&record),
+            None => Ok(()),
+        }
+    }
+
+    /// Write given record if it passes the filter.
+    pub fn write(&mut self, record: &TraceRecord) -> io::Result<()> {
+        if !self.filter.accepts(record) {
+            return Ok(());
+        }
+
+        match self.format {
+            TraceFormat::Text => writeln!(self.out, "{}", record.to_text(&self.symbols)),
+            TraceFormat::Binary => record.write_binary(&mut self.out),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Read all records from trace, detecting binary or text format from the header.
+/// Lines of text trace which could not be parsed (e.g. comments) are skipped.
+pub fn read_trace(mut input: impl BufRead) -> io::Result<Vec<TraceRecord>> {
+    let is_binary = input.fill_buf()?.starts_with(&BINARY_MAGIC);
+    let mut result = Vec::new();
+
+    if is_binary {
+        input.consume(BINARY_MAGIC.len());
+        let mut buffer = [0u8; BINARY_RECORD_LEN];
+        loop {
+            match input.read_exact(&mut buffer) {
+                Ok(()) => result.push(TraceRecord::from_binary(&buffer)),
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            }
+        }
+    } else {
+        for line in input.lines() {
+            if let Some(record) = TraceRecord::from_text(&line?) {
+                result.push(record);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Provides how records of two traces are paired.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alignment {
+    /// Pair records of same cycle number. Records without partner are skipped.
+    Cycle,
+    /// Pair records in order, ignoring cycle numbers.
+    Index,
+}
+
+/// Provides the first point where two traces diverge.
+#[derive(Debug)]
+pub enum Divergence {
+    /// Paired records have different fields.
+    Record{ index: usize, left: TraceRecord, right: TraceRecord, fields: Vec<String> },
+    /// One trace ended before the other one. `index` is the count of paired records.
+    Length{ index: usize, left_len: usize, right_len: usize },
+}
+
+/// Find first divergent instruction of two traces.
+/// Returns `None` if traces are identical under given alignment.
+pub fn first_divergence(left: &[TraceRecord], right: &[TraceRecord], alignment: Alignment, timers: bool) -> Option<Divergence> {
+    let (mut i, mut j, mut index) = (0usize, 0usize, 0usize);
+    while i < left.len() && j < right.len() {
+        if alignment == Alignment::Cycle {
+            if left[i].cycle < right[j].cycle { i += 1; continue; }
+            if left[i].cycle > right[j].cycle { j += 1; continue; }
+        }
+
+        let fields = left[i].diff_fields(&right[j], timers);
+        if !fields.is_empty() {
+            return Some(Divergence::Record{ index, left: left[i].clone(), right: right[j].clone(), fields });
+        }
+        i += 1;
+        j += 1;
+        index += 1;
+    }
+
+    // Loop ends when either trace is exhausted, so records left in the other one are past its end.
+    let (left_rest, right_rest) = (left.len() - i, right.len() - j);
+    if left_rest != right_rest {
+        Some(Divergence::Length{ index, left_len: left.len(), right_len: right.len() })
+    } else {
+        None
+    }
+}
@@ -0,0 +1,37 @@
+use chipmunk::engine::asm;
+use chipmunk::engine::rom::{Rom, V0, V1};
+
+#[test]
+fn source_is_assembled() {
+    let source = "\
+; Draw ball forever.
+start:  LD V0, 5
+loop:   LD I, ball   ; Labels may be used before they are defined.
+        DRW V0, V1, 5
+        JP loop
+ball:   DB 0x20, 0x70, 0xF8
+        DW 0x7020
+";
+    let program = asm::assemble(source, "ball.asm").unwrap();
+    let expected = Rom::new()
+        .ld(V0, 5)
+        .label("loop")
+        .ld_i("ball")
+        .drw(V0, V1, 5)
+        .jp("loop")
+        .sprite("ball", &[0x20, 0x70, 0xF8, 0x70, 0x20])
+        .build()
+        .unwrap();
+    assert_eq!(program.rom, expected);
+    assert_eq!(program.symbols.label(0x202), Some("loop"));
+    assert_eq!(program.symbols.line(0x204), Some(("ball.asm", 4)));
+    assert_eq!(program.symbols.line(0x208), None);
+}
+
+#[test]
+fn errors_have_line_numbers() {
+    assert_eq!(asm::assemble("JP nowhere", "a.asm").unwrap_err(), "line 1 : undefined name nowhere");
+    assert_eq!(asm::assemble("a: CLS\na: RET", "a.asm").unwrap_err(), "line 2 : label a is defined more than once");
+    assert_eq!(asm::assemble("\nLD V0, 300", "a.asm").unwrap_err(), "line 2 : invalid instruction LD V0, 300");
+    assert_eq!(asm::assemble("DB 256", "a.asm").unwrap_err(), "line 1 : invalid value 256");
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use chipmunk::engine::batch::{self, Job, Halt};
+use chipmunk::engine::machine::Fault;
+use chipmunk::engine::quirks::QuirksProfile;
+
+fn sweep(name: &str, rom: &[u8], frames: u64) -> Vec<Job> {
+    let rom: Arc<[u8]> = rom.into();
+    let mut jobs = Vec::new();
+    for &profile in QuirksProfile::ALL.iter() {
+        for seed in 0..3 {
+            jobs.push(Job { name: name.to_string(), rom: rom.clone(), profile, seed, frames, cycles: 15 });
+        }
+    }
+    jobs
+}
+
+#[test]
+fn threads_do_not_change_reports() {
+    let rom = std::fs::read("tests/conformance/roms/opcodes.ch8").unwrap();
+    let mut jobs = sweep("opcodes.ch8", &rom, 100);
+    // Random ROM draws differently per seed.
+    jobs.extend(sweep("random", &[0xC0, 0x0F, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06], 3));
+
+    let reports = batch::run(&jobs, 4);
+    assert_eq!(reports, batch::run(&jobs, 1));
+    assert!(reports[..12].iter().all(|report| report.halt == Halt::Spin));
+    assert_ne!(reports[12].screen_hash, reports[13].screen_hash);
+    assert_eq!(reports[12].halt, Halt::Spin);
+}
+
+#[test]
+fn faults_and_key_waits_are_reported() {
+    let jobs = [
+        Job { name: "fault".to_string(), rom: [0x60, 0x01, 0xFF, 0xFF].as_ref().into(), profile: QuirksProfile::Chipmunk, seed: 0, frames: 10, cycles: 15 },
+        Job { name: "key, wait".to_string(), rom: [0xF0, 0x0A].as_ref().into(), profile: QuirksProfile::Chipmunk, seed: 0, frames: 10, cycles: 15 },
+        Job { name: "loop".to_string(), rom: [0x70, 0x01, 0x12, 0x00].as_ref().into(), profile: QuirksProfile::Chipmunk, seed: 0, frames: 10, cycles: 15 },
+    ];
+    let reports = batch::run(&jobs, 2);
+    assert_eq!(reports[0].halt, Halt::Fault(Fault::InvalidInstruction{ pc: 0x202 }));
+    assert_eq!((reports[1].halt == Halt::KeyWait, reports[1].frames, reports[1].cycles), (true, 1, 1));
+    assert_eq!((reports[2].halt == Halt::FrameLimit, reports[2].frames), (true, 10));
+
+    let mut csv = Vec::new();
+    batch::write_csv(&reports, &mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "rom,profile,seed,frames,cycles,screen_hash,halt,fault");
+    assert!(lines[1].ends_with(",fault,Invalid instruction at 0x202"), "{}", lines[1]);
+    assert!(lines[2].starts_with("\"key, wait\",chipmunk,0,1,1,"), "{}", lines[2]);
+
+    let mut json = Vec::new();
+    batch::write_json(&reports, &mut json).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    assert!(json.contains("\"halt\": \"key-wait\", \"fault\": null},"));
+    assert!(json.contains("\"fault\": \"Invalid instruction at 0x202\"}"));
+}
+
+#[test]
+fn stack_underflow_and_invalid_key_are_faults() {
+    let job = |name: &str, rom: &[u8]| Job {
+        name: name.to_string(), rom: rom.into(), profile: QuirksProfile::Chipmunk, seed: 0, frames: 10, cycles: 15,
+    };
+    let jobs = [
+        // RET without CALL.
+        job("ret", &[0x60, 0x01, 0x00, 0xEE]),
+        // SKP V0 with V0 = 0x10.
+        job("skp", &[0x60, 0x10, 0xD0, 0x01, 0xE0, 0x9E]),
+        job("sknp", &[0x61, 0xFF, 0xE1, 0xA1]),
+    ];
+    let reports = batch::run(&jobs, 2);
+    assert_eq!(reports[0].halt, Halt::Fault(Fault::StackUnderflow{ pc: 0x202 }));
+    assert_eq!(reports[1].halt, Halt::Fault(Fault::InvalidKey{ pc: 0x204, key: 0x10 }));
+    assert_eq!(reports[2].halt, Halt::Fault(Fault::InvalidKey{ pc: 0x202, key: 0xFF }));
+
+    // Counters and screen are of the faulted machine.
+    assert_eq!((reports[0].frames, reports[0].cycles), (0, 2));
+    assert_eq!((reports[1].frames, reports[1].cycles), (0, 3));
+    assert_ne!(reports[1].screen_hash, reports[2].screen_hash);
+}
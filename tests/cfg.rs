@@ -0,0 +1,74 @@
+use chipmunk::engine::cfg::{Cfg, Edge};
+
+const ROM: [u8; 24] = [
+    0x60, 0x00,             // 0x200 LD V0, 0
+    0x22, 0x0C,             // 0x202 CALL 0x20C
+    0x30, 0x05,             // 0x204 SE V0, 5
+    0x12, 0x02,             // 0x206 JP 0x202
+    0x12, 0x08,             // 0x208 JP 0x208
+    0x00, 0x00,
+    0x70, 0x01,             // 0x20C ADD V0, 1
+    0x22, 0x14,             // 0x20E CALL 0x214
+    0x00, 0xEE,             // 0x210 RET
+    0x00, 0x00,
+    0xB2, 0x00,             // 0x214 JP V0, 0x200
+    0xFF, 0xFF,
+];
+
+#[test]
+fn functions_are_split_at_calls() {
+    let cfg = Cfg::build(&ROM);
+    let entries: Vec<u16> = cfg.functions.keys().copied().collect();
+    assert_eq!(entries, [0x200, 0x20C, 0x214]);
+
+    let main = &cfg.functions[&0x200];
+    assert_eq!(main.name(), "main");
+    assert_eq!(main.calls.iter().copied().collect::<Vec<_>>(), [0x20C]);
+    let starts: Vec<u16> = main.blocks.keys().copied().collect();
+    assert_eq!(starts, [0x200, 0x202, 0x206, 0x208]);
+    // Call does not end block, and skip makes two-way branch.
+    assert_eq!(main.blocks[&0x202].instructions.len(), 2);
+    assert_eq!(main.blocks[&0x202].successors, [(0x206, Edge::Next), (0x208, Edge::Skip)]);
+    assert_eq!(main.blocks[&0x200].successors, [(0x202, Edge::Next)]);
+    assert_eq!(main.blocks[&0x206].successors, [(0x202, Edge::Jump)]);
+    assert!(!main.has_indirect_jump());
+
+    let sub = &cfg.functions[&0x20C];
+    assert_eq!((sub.blocks.len(), sub.blocks[&0x20C].end()), (1, 0x212));
+    assert!(sub.blocks[&0x20C].successors.is_empty());
+
+    let jump = &cfg.functions[&0x214];
+    assert!(jump.has_indirect_jump());
+    assert!(!jump.blocks[&0x214].invalid_end);
+}
+
+#[test]
+fn graphs_are_written_as_dot() {
+    let cfg = Cfg::build(&ROM);
+    let mut dot = Vec::new();
+    cfg.write_function_dot(&cfg.functions[&0x200], &mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.starts_with("digraph \"main\" {\n"));
+    assert!(dot.contains("    \"0x202\" [label=\"0x202  CALL sub_20C\\l0x204  SE V0, 0x05\\l\"];\n"), "{}", dot);
+    assert!(dot.contains("    \"0x202\" -> \"0x208\" [label=\"skip\"];\n"));
+
+    let mut dot = Vec::new();
+    cfg.write_function_dot(&cfg.functions[&0x214], &mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.contains("    \"0x214\" -> \"0x214?\" [label=\"indirect\", style=dashed];\n"));
+
+    let mut dot = Vec::new();
+    cfg.write_call_graph_dot(&mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.contains("    \"main\" -> \"sub_20C\";\n    \"sub_20C\""));
+    assert!(dot.contains("    \"sub_214\" [label=\"sub_214\\n0x214\", style=dashed];\n"));
+}
+
+#[test]
+fn jumps_out_of_rom_end_with_invalid_block() {
+    let cfg = Cfg::build(&[0x60, 0x01, 0x13, 0x00]);
+    let main = &cfg.functions[&0x200];
+    assert_eq!(main.blocks[&0x200].successors, [(0x300, Edge::Jump)]);
+    assert!(main.blocks[&0x300].invalid_end);
+    assert!(main.blocks[&0x300].instructions.is_empty());
+}
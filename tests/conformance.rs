@@ -0,0 +1,143 @@
+mod harness;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use harness::{Case, Verdict};
+use chipmunk::engine::quirks::QuirksProfile;
+use chipmunk::engine::screen::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+fn cases_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("conformance")
+}
+
+/// Verify every profile of cases whose ROM is `external` or not, and fail on any mismatch.
+fn verify_cases(external: bool) {
+    let mut failures = Vec::<String>::new();
+    let mut count = 0;
+
+    for path in harness::find_cases(&cases_dir()) {
+        let case = Case::load(&path).unwrap();
+        if case.external != external { continue; }
+        count += 1;
+        for &profile in &case.profiles {
+            match case.verify(profile) {
+                Verdict::Pass | Verdict::Blessed => (),
+                Verdict::Fail(msg) => failures.push(format!("{} [{}]\n{}", case.name, profile, msg)),
+            }
+        }
+    }
+
+    assert!(count > 0, "No conformance case is found");
+    assert!(failures.is_empty(), "{} conformance failure(s)\n\n{}", failures.len(), failures.join("\n"));
+}
+
+#[test]
+fn conformance_roms() {
+    verify_cases(false);
+}
+
+#[test]
+#[ignore = "needs Timendus' chip8-test-suite ROMs in tests/conformance/roms"]
+fn external_conformance_roms() {
+    verify_cases(true);
+}
+
+/// Standard 4x5 CHIP-8 hexadecimal font.
+const FONT: [[u8; 5]; 16] = [
+    [0xF0, 0x90, 0x90, 0x90, 0xF0], [0x20, 0x60, 0x20, 0x20, 0x70],
+    [0xF0, 0x10, 0xF0, 0x80, 0xF0], [0xF0, 0x10, 0xF0, 0x10, 0xF0],
+    [0x90, 0x90, 0xF0, 0x10, 0x10], [0xF0, 0x80, 0xF0, 0x10, 0xF0],
+    [0xF0, 0x80, 0xF0, 0x90, 0xF0], [0xF0, 0x10, 0x20, 0x40, 0x40],
+    [0xF0, 0x90, 0xF0, 0x90, 0xF0], [0xF0, 0x90, 0xF0, 0x10, 0xF0],
+    [0xF0, 0x90, 0xF0, 0x90, 0x90], [0xE0, 0x90, 0xE0, 0x90, 0xE0],
+    [0xF0, 0x80, 0x80, 0x80, 0xF0], [0xE0, 0x90, 0x90, 0x90, 0xE0],
+    [0xF0, 0x80, 0xF0, 0x80, 0xF0], [0xF0, 0x80, 0xF0, 0x80, 0x80],
+];
+
+type Pixels = [[bool; SCREEN_WIDTH]; SCREEN_HEIGHT];
+
+/// Draw hex digit of font at given position.
+fn draw_digit(pixels: &mut Pixels, digit: u8, x: usize, y: usize) {
+    for (dy, row) in FONT[digit as usize].iter().enumerate() {
+        for dx in 0..4 {
+            pixels[y + dy][x + dx] |= row & (0x80 >> dx) != 0;
+        }
+    }
+}
+
+fn to_text(pixels: &Pixels) -> String {
+    pixels.iter()
+        .map(|row| row.iter().map(|&on| if on { '#' } else { '.' }).chain(Some('\n')).collect::<String>())
+        .collect()
+}
+
+/// Check that golden image of every profile of case is given screen.
+fn assert_goldens(case: &str, profiles: &[QuirksProfile], expected: impl Fn(QuirksProfile) -> String) {
+    for &profile in profiles {
+        let expected = expected(profile);
+        let path = cases_dir().join("golden").join(format!("{}.{}.txt", case, profile));
+        let golden = fs::read_to_string(&path).unwrap();
+        assert!(golden.lines().eq(expected.lines()),
+            "{} differs from expected\n{}", path.display(), harness::ascii_diff(&expected, &golden));
+    }
+}
+
+/// Render expected `opcodes` screen from `(result, VF)` of each cell.
+/// Cells are 16x6 pixels, and show two hex digits of result and one of VF.
+fn opcodes_screen(cells: &[&[(u8, u8)]], wrap: bool) -> String {
+    let mut pixels = [[false; SCREEN_WIDTH]; SCREEN_HEIGHT];
+    for (row, cells) in cells.iter().enumerate() {
+        for (col, &(result, vf)) in cells.iter().enumerate() {
+            let (x, y) = (col * 16, row * 6);
+            draw_digit(&mut pixels, result >> 4, x, y);
+            draw_digit(&mut pixels, result & 0xF, x + 5, y);
+            draw_digit(&mut pixels, vf, x + 10, y);
+        }
+    }
+
+    // 8 pixel line drawn at (60, 30).
+    for x in 60..68 {
+        if x < SCREEN_WIDTH || wrap {
+            pixels[30][x % SCREEN_WIDTH] = true;
+        }
+    }
+    to_text(&pixels)
+}
+
+#[test]
+fn opcodes_goldens_match_results_worked_out_by_hand() {
+    assert_goldens("opcodes", &QuirksProfile::ALL, |profile| {
+        let quirks = profile.quirks();
+        let cells: [&[(u8, u8)]; 3] = [
+            // 5+3, 255+2, 5-3, 3-5
+            &[(0x08, 0), (0x01, 1), (0x02, 1), (0xFE, 0)],
+            // SUBN 5-3, 0xF0|0x0F with VF=5, SHR of V0=1/V1=6, SHL of V0=0x81/V1=1
+            &[
+                (0x02, 1),
+                (0xFF, if quirks.vf_reset { 0 } else { 5 }),
+                if quirks.shift_vy { (0x03, 0) } else { (0x00, 1) },
+                if quirks.shift_vy { (0x02, 0) } else { (0x02, 1) },
+            ],
+            // Reload V0 after saving 0xAB,0xCD, JP 0x2B6 with V0=0/V2=2 (0x2B8 skips loading 0x11),
+            // BCD of 156 as 5+6 with VF=6
+            &[
+                (if quirks.memory_increment { 0x00 } else { 0xAB }, 0),
+                (if quirks.jump_vx { 0x00 } else { 0x11 }, 0),
+                (0x0B, 6),
+            ],
+        ];
+        opcodes_screen(&cells, !quirks.clip_sprites)
+    });
+}
+
+#[test]
+fn keypad_echo_goldens_are_digits_of_echoed_keys() {
+    // Keys 1, A and 7 are echoed from x = 8, and key B pressed while 1 is held is not.
+    assert_goldens("keypad-echo", &QuirksProfile::ALL, |_| {
+        let mut pixels = [[false; SCREEN_WIDTH]; SCREEN_HEIGHT];
+        for (i, &key) in [0x1, 0xA, 0x7].iter().enumerate() {
+            draw_digit(&mut pixels, key, 8 + 5 * i, 0);
+        }
+        to_text(&pixels)
+    });
+}
@@ -0,0 +1,60 @@
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::coverage::Coverage;
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::symbols::SymbolTable;
+
+/// Writes `JP 0x20A` into 0x20A with 0xFx55, and jumps into it.
+const SELF_MODIFYING_ROM: [u8; 12] = [0xA2, 0x0A, 0x60, 0x12, 0x61, 0x0A, 0xF1, 0x55, 0x12, 0x0A, 0x00, 0x00];
+
+fn run(rom: &[u8], cycles: usize) -> Coverage {
+    let mut machine = Machine::new(rom, Quirks::default());
+    let mut coverage = Coverage::new();
+    for _ in 0..cycles {
+        coverage.observe(&machine);
+        machine.step().unwrap();
+    }
+    coverage
+}
+
+#[test]
+fn runtime_written_code_is_reported_once() {
+    let coverage = run(&SELF_MODIFYING_ROM, 10);
+
+    assert_eq!(coverage.self_modifications().len(), 1);
+    assert_eq!(coverage.self_modifications()[0].pc, 0x20A);
+    assert_eq!(coverage.access(0x20A).mark(), 'X');
+    assert!(coverage.access(0x20B).written);
+    assert_eq!(coverage.access(0x208).executed, 1);
+}
+
+#[test]
+fn code_written_after_execution_is_reported() {
+    let rom = [
+        0xA2, 0x0E,             // 0x200 LD I, 0x20E
+        0x22, 0x0E,             // 0x202 CALL 0x20E
+        0x60, 0x72,             // 0x204 LD V0, 0x72
+        0x61, 0x05,             // 0x206 LD V1, 0x05
+        0xF1, 0x55,             // 0x208 LD [I], V1 : rewrites 0x20E into ADD V2, 5
+        0x22, 0x0E,             // 0x20A CALL 0x20E
+        0x12, 0x0C,             // 0x20C JP 0x20C
+        0x62, 0x01,             // 0x20E LD V2, 1
+        0x00, 0xEE,             // 0x210 RET
+    ];
+    let coverage = run(&rom, 12);
+
+    assert_eq!(coverage.self_modifications().len(), 1);
+    assert_eq!(coverage.self_modifications()[0].pc, 0x20E);
+    assert_eq!(coverage.self_modifications()[0].cycle, 8);
+    assert_eq!(coverage.access(0x20E).executed, 2);
+}
+
+#[test]
+fn lcov_uses_listing_lines_when_given() {
+    let coverage = run(&SELF_MODIFYING_ROM, 4);
+    let symbols = SymbolTable::parse("line 0x200 a.8o 1\nline 0x202 a.8o 2\nline 0x208 a.8o 5\n").unwrap();
+
+    let mut lcov = Vec::new();
+    coverage.write_lcov(&mut lcov, &SELF_MODIFYING_ROM, &symbols, "a.asm").unwrap();
+    assert_eq!(String::from_utf8(lcov).unwrap(),
+        "TN:chipmunk\nSF:a.8o\nDA:1,1\nDA:2,1\nDA:5,0\nLH:2\nLF:3\nend_of_record\n");
+}
@@ -0,0 +1,109 @@
+use chipmunk::engine::decompile::{decompile, decompile_with_symbols};
+use chipmunk::engine::symbols::SymbolTable;
+
+const ROM: [u8; 47] = [
+    0x00, 0xE0,             // 0x200 CLS
+    0x61, 0x00,             // 0x202 LD V1, 0
+    0xA2, 0x2A,             // 0x204 LD I, 0x22A
+    0xD1, 0x25,             // 0x206 DRW V1, V2, 5
+    0x71, 0x08,             // 0x208 ADD V1, 8
+    0x41, 0x40,             // 0x20A SNE V1, 0x40
+    0x12, 0x10,             // 0x20C JP 0x210
+    0x12, 0x04,             // 0x20E JP 0x204
+    0x22, 0x16,             // 0x210 CALL 0x216
+    0x12, 0x12,             // 0x212 JP 0x212
+    0x00, 0x00,
+    0xC3, 0x01,             // 0x216 RND V3, 1
+    0x33, 0x00,             // 0x218 SE V3, 0
+    0x12, 0x22,             // 0x21A JP 0x222
+    0x64, 0x05,             // 0x21C LD V4, 5
+    0x74, 0x01,             // 0x21E ADD V4, 1
+    0x12, 0x24,             // 0x220 JP 0x224
+    0x64, 0x06,             // 0x222 LD V4, 6
+    0x44, 0x00,             // 0x224 SNE V4, 0
+    0x65, 0x01,             // 0x226 LD V5, 1
+    0x00, 0xEE,             // 0x228 RET
+    0x3C, 0x42, 0x42, 0x42, 0x3C,
+];
+
+#[test]
+fn structure_is_recovered() {
+    let source = decompile(&ROM);
+    let expected = "\
+: main
+\tclear
+\tx := 0
+\tloop
+\t\ti := sprite_22A
+\t\tsprite x y 5
+\t\tx += 8
+\t\twhile x != 64
+\tagain
+\tsub_216
+\tloop
+\tagain
+
+: sub_216
+\trand := random 0x01
+\tif rand == 0 begin
+\t\tv4 := 5
+\t\tv4 += 1
+\telse
+\t\tv4 := 6
+\tend
+\tif v4 == 0 then v5 := 1
+\treturn
+";
+    assert!(source.contains(expected), "{}", source);
+}
+
+#[test]
+fn registers_are_aliased_by_use() {
+    let source = decompile(&ROM);
+    assert!(source.contains(":alias x v1\n:alias y v2\n:alias rand v3\n"), "{}", source);
+    assert!(!source.contains("v4\n:alias"));
+}
+
+#[test]
+fn sprites_are_written_with_bitmap() {
+    let source = decompile(&ROM);
+    assert!(source.ends_with(": sprite_22A\n\t0x3C  # ..####..\n\t0x42  # .#....#.\n\t0x42  # .#....#.\n\t0x42  # .#....#.\n\t0x3C  # ..####..\n"), "{}", source);
+}
+
+#[test]
+fn unstructured_jumps_get_labels() {
+    let rom = [
+        0x12, 0x04,         // 0x200 JP 0x204
+        0x00, 0x00,
+        0x12, 0x04,         // 0x204 JP 0x204
+    ];
+    let source = decompile(&rom);
+    assert!(source.contains(": main\n\tjump label_204\n: label_204\n\tloop\n\tagain\n"), "{}", source);
+}
+
+#[test]
+fn labels_of_symbols_are_used() {
+    let symbols = SymbolTable::parse("label 0x200 start\nlabel 0x216 roll\nlabel 0x22A ball\n").unwrap();
+    let source = decompile_with_symbols(&ROM, &symbols);
+    assert!(source.contains(": start\n\tclear\n"), "{}", source);
+    assert!(source.contains("\t\ti := ball\n"), "{}", source);
+    assert!(source.contains("\troll\n\tloop\n"), "{}", source);
+    assert!(source.contains("\n: roll\n\trand := random 0x01\n"), "{}", source);
+    assert!(source.contains("\n: ball\n\t0x3C"), "{}", source);
+}
+
+#[test]
+fn function_between_blocks_of_caller_is_not_inlined() {
+    let rom = [
+        0x12, 0x06,             // 0x200 JP 0x206
+        0x00, 0xE0,             // 0x202 CLS
+        0x00, 0xEE,             // 0x204 RET
+        0x22, 0x02,             // 0x206 CALL 0x202
+        0x12, 0x08,             // 0x208 JP 0x208
+    ];
+    let source = decompile(&rom);
+    let main = &source[source.find(": main\n").unwrap()..source.find(": sub_202\n").unwrap()];
+    assert!(!main.contains("clear"), "{}", source);
+    assert!(main.contains("\tsub_202\n"), "{}", source);
+    assert!(source.contains(": sub_202\n\tclear\n\treturn\n"), "{}", source);
+}
@@ -0,0 +1,116 @@
+use chipmunk::engine::env::{Env, GameSpec, Observation, Value, ACTION_COUNT};
+use chipmunk::engine::screen::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+/// Game which runs one iteration per frame. Holding key 5 scores, holding key 7 loses lives,
+/// and a random digit is drawn every frame.
+const GAME: [u8; 40] = [
+    0x60, 0x03,             // 0x200 LD V0, 3
+    0x61, 0x00,             // 0x202 LD V1, 0
+    0x62, 0x05,             // 0x204 LD V2, 5
+    0x63, 0x07,             // 0x206 LD V3, 7
+    0x64, 0x01,             // 0x208 LD V4, 1
+    0xF5, 0x07,             // 0x20A LD V5, DT
+    0x35, 0x00,             // 0x20C SE V5, 0
+    0x12, 0x0A,             // 0x20E JP 0x20A
+    0xF4, 0x15,             // 0x210 LD DT, V4
+    0xE2, 0xA1,             // 0x212 SKNP V2
+    0x71, 0x01,             // 0x214 ADD V1, 1
+    0xE3, 0xA1,             // 0x216 SKNP V3
+    0x70, 0xFF,             // 0x218 ADD V0, -1
+    0xC6, 0x0F,             // 0x21A RND V6, 0x0F
+    0xF6, 0x29,             // 0x21C LD F, V6
+    0x00, 0xE0,             // 0x21E CLS
+    0xD7, 0x75,             // 0x220 DRW V7, V7, 5
+    0xA3, 0x00,             // 0x222 LD I, 0x300
+    0xF1, 0x33,             // 0x224 LD B, V1
+    0x12, 0x0A,             // 0x226 JP 0x20A
+];
+
+const SPEC: &str = "
+score-bcd 0x300 3   # Score is stored by Fx33.
+lives V0
+game-over 0
+frame-skip 4
+";
+
+#[test]
+fn spec_is_parsed() {
+    let spec = GameSpec::parse(SPEC).unwrap();
+    assert_eq!(spec.score, Some(Value::Bcd{ addr: 0x300, len: 3 }));
+    assert_eq!(spec.lives, Some(Value::Register(0)));
+    assert_eq!((spec.frame_skip, spec.observation), (4, Observation::Screen));
+
+    let spec = GameSpec::parse("score 0x2F0 2\nobservation ram\nquirks vip").unwrap();
+    assert_eq!(spec.score, Some(Value::Memory{ addr: 0x2F0, len: 2 }));
+    assert_eq!(spec.observation, Observation::Ram);
+    assert!(spec.quirks.display_wait);
+
+    assert_eq!(GameSpec::parse("score VG").unwrap_err(), "line 1 : invalid value VG");
+    assert_eq!(GameSpec::parse("\nlives 0xFFF 2").unwrap_err(), "line 2 : invalid value lives 0xFFF 2");
+    assert_eq!(GameSpec::parse("lives 0xFFFFFFFFFFFFFFFF 2").unwrap_err(), "line 1 : invalid value lives 0xFFFFFFFFFFFFFFFF 2");
+    assert_eq!(GameSpec::parse("speed 10").unwrap_err(), "line 1 : unknown entry speed 10");
+}
+
+#[test]
+fn reward_and_done_follow_score_and_lives() {
+    let mut env = Env::new(&GAME, GameSpec::parse(SPEC).unwrap());
+    assert_eq!(env.reset(0).len(), SCREEN_WIDTH * SCREEN_HEIGHT / 8);
+
+    let (_, reward, done) = env.step(1 + 5);
+    assert_eq!((reward, done), (4, false));
+    assert_eq!(env.step(0).1, 0);
+    assert_eq!(env.step(1 + 5).1, 4);
+    assert_eq!(env.score(), 8);
+
+    // Lives become 0 at the third frame of step, and rest of frames are not processed.
+    let frames = env.machine().frames();
+    let (_, reward, done) = env.step(1 + 7);
+    assert_eq!((reward, done, env.lives()), (0, true, Some(0)));
+    assert_eq!(env.machine().frames(), frames + 3);
+    assert_eq!(env.step(1 + 5).1, 0);
+
+    env.reset(0);
+    assert_eq!((env.score(), env.is_done()), (0, false));
+    env.step(0);
+    assert_eq!(env.lives(), Some(3));
+}
+
+#[test]
+fn clones_and_seeds_are_deterministic() {
+    let actions: Vec<usize> = (0..40).map(|i| (i * 7) % (ACTION_COUNT - 8)).collect();
+    let play = |env: &mut Env, actions: &[usize]| -> Vec<(Vec<u8>, i64, bool)> {
+        actions.iter().map(|&action| env.step(action)).collect()
+    };
+
+    let mut env = Env::new(&GAME, GameSpec::parse(SPEC).unwrap());
+    env.reset(1);
+    let first = play(&mut env, &actions);
+    env.reset(1);
+    assert_eq!(play(&mut env, &actions), first);
+    env.reset(2);
+    assert_ne!(play(&mut env, &actions), first);
+
+    // Clone continues from the same point independently of the original.
+    env.reset(1);
+    play(&mut env, &actions[..20]);
+    let mut clone = env.clone();
+    play(&mut env, &[6, 6, 6]);
+    assert_eq!(play(&mut clone, &actions[20..]), first[20..]);
+}
+
+#[test]
+fn ram_observation_and_spin_end_episode() {
+    let mut spec = GameSpec::new();
+    spec.observation = Observation::Ram;
+    let mut env = Env::new(&[0x60, 0x2A, 0x12, 0x02], spec);
+    let (observation, reward, done) = env.step(0);
+    assert_eq!((&observation[0x200..0x204], reward, done), (&[0x60, 0x2A, 0x12, 0x02][..], 0, true));
+    assert_eq!(env.machine().frames(), 1);
+
+    let mut spec = GameSpec::new();
+    spec.max_frames = 10;
+    let mut env = Env::new(&[0x70, 0x01, 0x12, 0x00], spec);
+    let dones: Vec<bool> = (0..3).map(|_| env.step(0).2).collect();
+    assert_eq!(dones, [false, false, true]);
+    assert_eq!(env.machine().frames(), 10);
+}
@@ -0,0 +1,53 @@
+use chipmunk::engine::frontend::{self, Event, Exit, Headless, Recording, Pacing};
+use chipmunk::engine::machine::{Machine, Fault};
+use chipmunk::engine::quirks::Quirks;
+
+const ROM: [u8; 12] = [
+    0xF0, 0x0A,             // 0x200 LD V0, K
+    0xF0, 0x18,             // 0x202 LD ST, V0
+    0xF0, 0x29,             // 0x204 LD F, V0
+    0xD1, 0x15,             // 0x206 DRW V1, V1, 5
+    0x12, 0x08,             // 0x208 JP 0x208
+    0xFF, 0xFF,             // 0x20A invalid
+];
+
+#[test]
+fn headless_input_is_recorded_per_frame() {
+    let mut machine = Machine::new(&ROM, Quirks::default());
+    let mut headless = Headless::with_frame_limit(8);
+    headless.schedule(2, Event::KeyDown(3));
+    let mut recording = Recording::new(headless);
+
+    let mut observed = 0;
+    let exit = frontend::run(&mut machine, &mut recording, Pacing::Fixed{ cycles_per_frame: 10 }, |_| {
+        observed += 1;
+        Ok(())
+    }).unwrap();
+    assert_eq!(exit, Exit::Quit);
+    assert_eq!(observed, 80);
+
+    let frames = recording.frames();
+    assert_eq!(frames.len(), 8);
+    assert_eq!(frames[2].events, vec![Event::KeyDown(3)]);
+    let beeps: Vec<bool> = frames.iter().map(|frame| frame.beep).collect();
+    assert_eq!(beeps, vec![false, false, true, true, true, false, false, false]);
+
+    // Font of key 3 is drawn at the top left corner.
+    assert!(!frames[1].pixels[0]);
+    assert!(frames[2].pixels[..4].iter().all(|&drawn| drawn));
+
+    let mut text = Vec::new();
+    recording.write(&mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.starts_with("frame 0\n"));
+    assert!(text.contains("frame 2 beep key-down 3\n####"));
+    assert_eq!(recording.into_inner().frames(), 8);
+}
+
+#[test]
+fn fault_ends_emulation() {
+    let rom = [0x12, 0x02, 0xFF, 0xFF];   // JP 0x202, then invalid instruction.
+    let mut machine = Machine::new(&rom, Quirks::default());
+    let exit = frontend::run(&mut machine, &mut Headless::new(), Pacing::Fixed{ cycles_per_frame: 10 }, |_| Ok(())).unwrap();
+    assert_eq!(exit, Exit::Fault(Fault::InvalidInstruction{ pc: 0x202 }));
+}
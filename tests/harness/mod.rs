@@ -0,0 +1,242 @@
+//! Headless conformance test harness.
+//!
+//! Each test case is described by a `.case` file which consists of `key = value`
+//! settings and scripted events, one per line. `#` starts a comment.
+//!
+//! ``` text
+//! rom = roms/5-quirks.ch8             # ROM path, relative to the case file.
+//! frames = 300                        # 60Hz frames to run. (default 120)
+//! cycles = 20                         # Instructions per frame. (default 15)
+//! profiles = chipmunk vip             # Quirks profiles to run. (default all)
+//! external = true                     # ROM is not vendored. (default false)
+//! poke 0x1FF 1                        # Store byte into memory before start.
+//! press 10 0x1                        # Press key 1 at the start of frame 10.
+//! release 14 0x1                      # Release key 1 at the start of frame 14.
+//! ```
+//!
+//! The final screen of each profile is compared with golden image
+//! `golden/<case>.<profile>.txt`. Golden images are 32 lines of 64 `#` or `.`.
+//! Set `CHIPMUNK_BLESS=1` to (re)write golden images from actual results.
+//! A missing ROM or golden image fails the case.
+
+use std::{fs, env};
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::memory::Memory;
+use chipmunk::engine::quirks::QuirksProfile;
+use chipmunk::engine::screen::{Screen, SCREEN_WIDTH, SCREEN_HEIGHT};
+
+/// Scripted input or setup event of case.
+#[derive(Debug)]
+pub enum Event {
+    Press{ frame: usize, key: u8 },
+    Release{ frame: usize, key: u8 },
+}
+
+/// Parsed `.case` file.
+#[derive(Debug)]
+pub struct Case {
+    pub name: String,
+    pub dir: PathBuf,
+    pub rom: PathBuf,
+    pub frames: usize,
+    pub cycles: usize,
+    pub profiles: Vec<QuirksProfile>,
+    /// ROM has to be provided by user, so the case is run by ignored test only.
+    pub external: bool,
+    pub pokes: Vec<(u16, u8)>,
+    pub events: Vec<Event>,
+}
+
+/// Result of running one case under one profile.
+pub enum Verdict {
+    Pass,
+    Blessed,
+    Fail(String),
+}
+
+fn parse_number<T: TryFrom<u32>>(token: &str) -> Result<T, String> {
+    let parsed = match token.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => token.parse::<u32>().ok(),
+    };
+    parsed
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| format!("Invalid number `{}`", token))
+}
+
+impl Case {
+    /// Load case from given `.case` file path.
+    pub fn load(path: &Path) -> Result<Case, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let dir = path.parent().unwrap().to_path_buf();
+        let mut case = Case {
+            name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+            rom: PathBuf::new(),
+            dir,
+            frames: 120,
+            cycles: 15,
+            profiles: QuirksProfile::ALL.to_vec(),
+            external: false,
+            pokes: Vec::new(),
+            events: Vec::new(),
+        };
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() { continue; }
+
+            let error = |msg: String| format!("{}:{}: {}", path.display(), line_no + 1, msg);
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim();
+                match key.trim() {
+                    "rom" => case.rom = case.dir.join(value),
+                    "frames" => case.frames = parse_number(value).map_err(error)?,
+                    "cycles" => case.cycles = parse_number(value).map_err(error)?,
+                    "profiles" => {
+                        case.profiles = value.split_whitespace()
+                            .map(|name| QuirksProfile::from_name(name)
+                                .ok_or_else(|| error(format!("Unknown profile `{}`", name))))
+                            .collect::<Result<_, _>>()?;
+                    },
+                    "external" => {
+                        case.external = value.parse()
+                            .map_err(|_| error(format!("Invalid flag `{}`", value)))?;
+                    },
+                    other => return Err(error(format!("Unknown setting `{}`", other))),
+                }
+                continue;
+            }
+
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens[..] {
+                ["poke", addr, val] => {
+                    let addr = parse_number(addr).map_err(error)?;
+                    let val = parse_number(val).map_err(error)?;
+                    case.pokes.push((addr, val));
+                },
+                ["press", frame, key] | ["release", frame, key] => {
+                    let frame = parse_number(frame).map_err(error)?;
+                    let key: u8 = parse_number(key).map_err(error)?;
+                    if key > 0xF { return Err(error(format!("Invalid key `{}`", key))); }
+                    case.events.push(match tokens[0] {
+                        "press" => Event::Press{ frame, key },
+                        _ => Event::Release{ frame, key },
+                    });
+                },
+                _ => return Err(error(format!("Unknown line `{}`", line))),
+            }
+        }
+
+        if case.rom.as_os_str().is_empty() {
+            return Err(format!("{}: `rom` is not specified", path.display()));
+        }
+        Ok(case)
+    }
+
+    /// Get golden image path of given profile.
+    pub fn golden_path(&self, profile: QuirksProfile) -> PathBuf {
+        self.dir.join("golden").join(format!("{}.{}.txt", self.name, profile))
+    }
+
+    /// Run case headlessly with given profile and return final screen.
+    pub fn run(&self, rom: &[u8], profile: QuirksProfile) -> Result<Screen, String> {
+        let mut memory = Memory::from_rom(rom);
+        for &(addr, val) in &self.pokes {
+            memory.store_from(&[val], addr);
+        }
+
+        let mut machine = Machine::from_memory(memory, profile.quirks());
+        for frame in 0..self.frames {
+            for event in &self.events {
+                match *event {
+                    Event::Press{ frame: at, key } if at == frame => machine.press_key(key),
+                    Event::Release{ frame: at, key } if at == frame => machine.release_key(key),
+                    _ => (),
+                }
+            }
+
+            if let Err(fault) = machine.run_frame(self.cycles) {
+                return Err(format!("{} at frame {}\n{}", fault, frame, machine.registers()));
+            }
+        }
+
+        Ok(machine.screen().clone())
+    }
+
+    /// Run case with given profile and compare result with golden image.
+    pub fn verify(&self, profile: QuirksProfile) -> Verdict {
+        let rom = match fs::read(&self.rom) {
+            Ok(rom) => rom,
+            Err(_) => return Verdict::Fail(format!("ROM `{}` is not found", self.rom.display())),
+        };
+
+        let actual = match self.run(&rom, profile) {
+            Ok(screen) => to_ascii(&screen),
+            Err(msg) => return Verdict::Fail(msg),
+        };
+
+        let golden_path = self.golden_path(profile);
+        if env::var_os("CHIPMUNK_BLESS").is_some() {
+            fs::create_dir_all(golden_path.parent().unwrap()).unwrap();
+            fs::write(&golden_path, &actual).unwrap();
+            return Verdict::Blessed;
+        }
+
+        let expected = match fs::read_to_string(&golden_path) {
+            Ok(expected) => expected,
+            Err(_) => return Verdict::Fail(format!(
+                "Golden image `{}` is not found. Run with CHIPMUNK_BLESS=1 to create it.",
+                golden_path.display())),
+        };
+
+        if expected.lines().eq(actual.lines()) {
+            Verdict::Pass
+        } else {
+            Verdict::Fail(ascii_diff(&expected, &actual))
+        }
+    }
+}
+
+/// Find all `.case` files in given directory, sorted by name.
+pub fn find_cases(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "case"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Convert screen into golden image text.
+pub fn to_ascii(screen: &Screen) -> String {
+    let mut result = String::with_capacity((SCREEN_WIDTH + 1) * SCREEN_HEIGHT);
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            result.push(if screen.is_drawn((x as u8, y as u8)) { '#' } else { '.' });
+        }
+        result.push('\n');
+    }
+    result
+}
+
+/// Make side-by-side diff of expected and actual golden image.
+/// Mismatched pixels are marked with `^` in third column.
+pub fn ascii_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let mut result = format!("{:<w$} | {:<w$} | diff\n", "expected", "actual", w = SCREEN_WIDTH);
+
+    for y in 0..expected.len().max(actual.len()) {
+        let e = expected.get(y).copied().unwrap_or("");
+        let a = actual.get(y).copied().unwrap_or("");
+        let marks: String = (0..SCREEN_WIDTH)
+            .map(|x| if e.chars().nth(x) == a.chars().nth(x) { ' ' } else { '^' })
+            .collect();
+        result.push_str(&format!("{:<w$} | {:<w$} | {}\n", e, a, marks.trim_end(), w = SCREEN_WIDTH));
+    }
+    result
+}
@@ -0,0 +1,98 @@
+use chipmunk::engine::hook::Hooks;
+use chipmunk::engine::isa::Instruction;
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::register::Registers;
+
+/// Hooks which record every callback as text.
+#[derive(Default)]
+struct Log {
+    lines: Vec<String>,
+}
+
+impl Hooks for Log {
+    fn before_instruction(&mut self, pc: u16, instruction: &Instruction, _registers: &Registers) {
+        self.lines.push(format!("before 0x{:03X} {}", pc, instruction));
+    }
+
+    fn after_instruction(&mut self, pc: u16, _instruction: &Instruction, registers: &Registers) {
+        self.lines.push(format!("after 0x{:03X} pc 0x{:03X}", pc, registers.get_pc()));
+    }
+
+    fn on_draw(&mut self, pos: (u8, u8), sprite: &[u8], collision: bool) {
+        self.lines.push(format!("draw {:?} {:?} {}", pos, sprite, collision));
+    }
+
+    fn on_memory_write(&mut self, addr: u16, old: u8, new: u8) {
+        self.lines.push(format!("write 0x{:03X} {} -> {}", addr, old, new));
+    }
+
+    fn on_key_wait(&mut self, r: u8) {
+        self.lines.push(format!("key wait V{:X}", r));
+    }
+
+    fn on_frame(&mut self, frame: u64) {
+        self.lines.push(format!("frame {}", frame));
+    }
+
+    fn on_beep(&mut self, on: bool) {
+        self.lines.push(format!("beep {}", on));
+    }
+}
+
+/// Hooks which count draws only.
+#[derive(Default)]
+struct DrawCount(usize);
+
+impl Hooks for DrawCount {
+    fn on_draw(&mut self, _pos: (u8, u8), _sprite: &[u8], _collision: bool) {
+        self.0 += 1;
+    }
+}
+
+const ROM: [u8; 18] = [
+    0x60, 0x01,             // 0x200 LD V0, 1
+    0xA3, 0x00,             // 0x202 LD I, 0x300
+    0xF0, 0x55,             // 0x204 LD [I], V0
+    0xA3, 0x00,             // 0x206 LD I, 0x300
+    0xD1, 0x11,             // 0x208 DRW V1, V1, 1
+    0xD1, 0x11,             // 0x20A DRW V1, V1, 1 erases
+    0xF0, 0x18,             // 0x20C LD ST, V0
+    0xF2, 0x0A,             // 0x20E LD V2, K
+    0x12, 0x10,             // 0x210 JP 0x210
+];
+
+#[test]
+fn every_callback_is_called() {
+    let mut machine = Machine::new(&ROM, Quirks::default()).with_hooks(Log::default());
+    machine.run_frame(9).unwrap();
+    machine.run_frame(1).unwrap();
+
+    let expected = [
+        "before 0x200 LD V0, 0x01", "after 0x200 pc 0x202",
+        "before 0x202 LD I, 0x300", "after 0x202 pc 0x204",
+        "before 0x204 LD [I], V0", "write 0x300 0 -> 1", "after 0x204 pc 0x206",
+        "before 0x206 LD I, 0x300", "after 0x206 pc 0x208",
+        "before 0x208 DRW V1, V1, 1", "draw (0, 0) [1] false", "after 0x208 pc 0x20A",
+        "before 0x20A DRW V1, V1, 1", "draw (0, 0) [1] true", "after 0x20A pc 0x20C",
+        "before 0x20C LD ST, V0", "after 0x20C pc 0x20E",
+        "before 0x20E LD V2, K", "key wait V2", "after 0x20E pc 0x210",
+        "frame 1", "beep true",
+        "frame 2", "beep false",
+    ];
+    assert_eq!(machine.hooks().lines, expected);
+}
+
+#[test]
+fn hooks_are_combined_and_replaced() {
+    let machine = Machine::new(&ROM, Quirks::default());
+    let mut machine = machine.with_hooks((DrawCount::default(), DrawCount::default()));
+    machine.run_frame(6).unwrap();
+    assert_eq!((machine.hooks().0).0, 2);
+    assert_eq!((machine.hooks().1).0, 2);
+
+    let mut machine = machine.with_hooks(vec![Box::new(DrawCount::default()) as Box<dyn Hooks>]);
+    machine.run_frame(5).unwrap();
+    assert!(machine.hooks().is_active());
+    assert_eq!(machine.cycles(), 8);
+}
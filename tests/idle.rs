@@ -0,0 +1,165 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use chipmunk::engine::machine::{Machine, Fault};
+use chipmunk::engine::state::MachineState;
+use chipmunk::engine::memory::MEMORY_SIZE;
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::screen::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+/// Process `cycles` instructions by `step_fast`, as `Machine::run_frame` does.
+fn run_fast(machine: &mut Machine, cycles: u64) -> Result<(), Fault> {
+    let end = machine.cycles() + cycles;
+    while machine.cycles() < end && *machine.state() == MachineState::Normal {
+        machine.step_fast(end - machine.cycles())?;
+    }
+    Ok(())
+}
+
+/// Run ROM by `step_fast`, which skips idle loops, and by plain `step`. Keys are pressed at given
+/// (frame, cycle in frame) and released at the end of frame. Machines are compared at the end of every frame.
+fn assert_same_as_plain_steps(rom: &[u8], frames: u64, cycles: usize, keys: &[(u64, u64, u8)]) -> Machine {
+    let mut fast = Machine::new(rom, Quirks::default());
+    let mut plain = Machine::new(rom, Quirks::default());
+    fast.seed_random(0);
+    plain.seed_random(0);
+
+    for frame in 0..frames {
+        let mut presses: Vec<(u64, u8)> = keys.iter().filter(|key| key.0 == frame).map(|key| (key.1, key.2)).collect();
+        let fast_result = if presses.is_empty() {
+            fast.run_frame(cycles).map(|_| ())
+        } else {
+            presses.sort_unstable();
+            let mut done = 0;
+            let mut result = Ok(());
+            for &(at, key) in &presses {
+                result = result.and_then(|_| run_fast(&mut fast, at - done));
+                fast.press_key(key);
+                done = at;
+            }
+            let result = result.and_then(|_| run_fast(&mut fast, cycles as u64 - done));
+            fast.tick_timers();
+            result
+        };
+        let mut plain_result = Ok(());
+        for cycle in 0..cycles as u64 {
+            for &(_, key) in presses.iter().filter(|press| press.0 == cycle) {
+                plain.press_key(key);
+            }
+            plain_result = plain_result.and_then(|_| plain.step().map(|_| ()));
+        }
+        assert_eq!(fast_result, plain_result, "result at frame {}", frame);
+        plain.tick_timers();
+        fast.release_all_keys();
+        plain.release_all_keys();
+
+        let (left, right) = (fast.registers(), plain.registers());
+        assert_eq!(left.get_pc(), right.get_pc(), "PC at frame {}", frame);
+        assert_eq!(left.general_registers(), right.general_registers(), "V at frame {}", frame);
+        assert_eq!(left.get_l(), right.get_l(), "I at frame {}", frame);
+        assert_eq!(fast.cycles(), plain.cycles(), "cycles at frame {}", frame);
+        assert_eq!(fast.state(), plain.state(), "state at frame {}", frame);
+        assert_eq!(fast.memory().get_data_bytes(0, MEMORY_SIZE), plain.memory().get_data_bytes(0, MEMORY_SIZE));
+        for y in 0..SCREEN_HEIGHT as u8 {
+            for x in 0..SCREEN_WIDTH as u8 {
+                assert_eq!(fast.screen().is_drawn((x, y)), plain.screen().is_drawn((x, y)));
+            }
+        }
+    }
+    fast
+}
+
+#[test]
+fn delay_timer_polling_loop_is_skipped() {
+    let rom = [
+        0x60, 0x1E,             // 0x200 LD V0, 30
+        0xF0, 0x15,             // 0x202 LD DT, V0
+        0xF1, 0x07,             // 0x204 LD V1, DT
+        0x31, 0x00,             // 0x206 SE V1, 0
+        0x12, 0x04,             // 0x208 JP 0x204
+        0x72, 0x01,             // 0x20A ADD V2, 1
+        0xF2, 0x29,             // 0x20C LD F, V2
+        0xD3, 0x35,             // 0x20E DRW V3, V3, 5
+        0x12, 0x02,             // 0x210 JP 0x202
+    ];
+    let mut machine = Machine::new(&rom, Quirks::default());
+    run_fast(&mut machine, 1000).unwrap();
+    assert!(machine.is_idle());
+    assert_eq!(machine.cycles(), 1000);
+
+    assert_same_as_plain_steps(&rom, 100, 1000, &[]);
+}
+
+#[test]
+fn key_press_breaks_idle_loop() {
+    let rom = [
+        0x60, 0x05,             // 0x200 LD V0, 5
+        0xE0, 0x9E,             // 0x202 SKP V0
+        0x12, 0x02,             // 0x204 JP 0x202
+        0x71, 0x01,             // 0x206 ADD V1, 1
+        0xF2, 0x0A,             // 0x208 LD V2, K
+        0x12, 0x02,             // 0x20A JP 0x202
+    ];
+    let machine = assert_same_as_plain_steps(&rom, 10, 500, &[(3, 100, 5), (5, 0, 7), (8, 499, 5)]);
+    assert_eq!(machine.registers().general_register(1), 2);
+}
+
+/// Make ROM of short loops reading delay timer and keypad, mixed with other instructions.
+/// Registers are kept less than 8, so that they are always valid keys.
+fn random_rom(rng: &mut StdRng, len: usize) -> Vec<u8> {
+    // Memory accesses start from 0x300, not from font.
+    let mut rom = vec![0xA3, 0x00];
+    for _ in 1..len {
+        let (x, y) = (rng.gen_range(0, 4), rng.gen_range(0, 4));
+        let target = 0x200 + 2 * rng.gen_range(0, len as u16);
+        let opcode: u16 = match rng.gen_range(0, 13) {
+            0 => 0x6000 | x << 8 | rng.gen_range(0, 8),
+            1 => 0x8000 | x << 8 | y << 4 | [0, 1, 2, 6][rng.gen_range(0, 4)],
+            2 => 0x9000 | x << 8 | y << 4,
+            3 => 0x3000 | x << 8 | rng.gen_range(0, 4),
+            4 => 0x4000 | x << 8 | rng.gen_range(0, 4),
+            5 => 0x5000 | x << 8 | y << 4,
+            6 => 0xF007 | x << 8,
+            7 => 0xF015 | x << 8,
+            8 => [0xE09E, 0xE0A1][rng.gen_range(0, 2)] | x << 8,
+            9 => 0xA300 | rng.gen_range(0, 0x10),
+            10 => [0xF055, 0xF065, 0xF01E][rng.gen_range(0, 3)] | x << 8,
+            11 => [0xD005 | x << 8 | y << 4, 0xC003 | x << 8][rng.gen_range(0, 2)],
+            _ => 0x1000 | target,
+        };
+        rom.extend_from_slice(&opcode.to_be_bytes());
+    }
+    rom
+}
+
+#[test]
+fn random_roms_behave_as_plain_steps() {
+    let mut rng = StdRng::seed_from_u64(0x1D1E);
+    for _ in 0..200 {
+        let rom = random_rom(&mut rng, 24);
+        let keys: Vec<(u64, u64, u8)> = (0..4).map(|_| (rng.gen_range(0, 20), rng.gen_range(0, 200), rng.gen_range(0, 4))).collect();
+        assert_same_as_plain_steps(&rom, 20, 200, &keys);
+    }
+}
+
+#[test]
+fn key_released_after_every_call_is_seen_as_plain_steps() {
+    use chipmunk::engine::frontend::{self, Event, Headless, Pacing};
+
+    let rom = [
+        0x61, 0x00,             // 0x200 LD V1, 0
+        0xE1, 0xA1,             // 0x202 SKNP V1
+        0x72, 0x01,             // 0x204 ADD V2, 1
+        0x12, 0x02,             // 0x206 JP 0x202
+    ];
+    let run = |fast: bool| {
+        let mut machine = Machine::new(&rom, Quirks::default());
+        let mut headless = Headless::with_frame_limit(4);
+        headless.schedule(1, Event::KeyDown(0));
+        let pacing = Pacing::Fixed{ cycles_per_frame: 100 };
+        frontend::run_with(&mut machine, &mut headless, pacing, |_| Ok(()), |machine, budget| {
+            if fast { machine.step_fast(budget) } else { machine.step() }
+        }).unwrap();
+        (machine.registers().general_register(2), machine.cycles())
+    };
+    assert_eq!(run(true), run(false));
+}
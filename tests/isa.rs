@@ -0,0 +1,86 @@
+use chipmunk::engine::isa::{self, Flow, Instruction, Platform};
+use chipmunk::engine::quirks::QuirksProfile;
+
+#[test]
+fn decoded_instructions_are_encoded_back() {
+    for word in 0..=u16::MAX {
+        let bytes = word.to_be_bytes();
+        let instruction = match isa::parse_instruction(&bytes) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        assert_eq!(instruction.encode(), bytes, "0x{:04X}", word);
+    }
+    // Low nibble of 5xy0 and 9xy0 is not an operand.
+    assert_eq!(isa::parse_instruction(&[0x51, 0x22]), None);
+    assert_eq!(isa::parse_instruction(&[0x91, 0x21]), None);
+    assert_eq!(isa::parse_instruction(&[0x01, 0x23]), Some(Instruction::Ignore(0x123)));
+}
+
+#[test]
+fn mnemonics_are_canonical() {
+    let mnemonics: Vec<String> = [0x00E0u16, 0x0123, 0x1234, 0x3A05, 0x8126, 0xA2F0, 0xB300, 0xD125, 0xE1A1, 0xF307, 0xF055, 0xF265]
+        .iter()
+        .map(|word| isa::parse_instruction(&word.to_be_bytes()).unwrap().to_string())
+        .collect();
+    assert_eq!(mnemonics, [
+        "CLS", "SYS 0x123", "JP 0x234", "SE VA, 0x05", "SHR V1, V2", "LD I, 0x2F0", "JP V0, 0x300",
+        "DRW V1, V2, 5", "SKNP V1", "LD V3, DT", "LD [I], V0", "LD V2, [I]",
+    ]);
+}
+
+#[test]
+fn metadata_follows_quirks() {
+    let vip = QuirksProfile::CosmacVip.quirks();
+    let chip48 = QuirksProfile::Chip48.quirks();
+
+    let shr = Instruction::ShrRegV{ r: 1, f: 2 };
+    assert_eq!(shr.reads(&vip).registers, 1 << 2);
+    assert_eq!(shr.reads(&chip48).registers, 1 << 1);
+    assert_eq!(shr.writes(&vip).registers, 1 << 1 | 1 << 0xF);
+
+    let or = Instruction::OrRegV{ r: 1, f: 2 };
+    assert!(or.writes(&vip).register(0xF));
+    assert!(!or.writes(&chip48).register(0xF));
+
+    let load = Instruction::MemRead{ endr: 3 };
+    let (reads, writes) = (load.reads(&vip), load.writes(&vip));
+    assert!(reads.index && reads.memory == 4);
+    assert_eq!(writes.registers, 0b1111);
+    assert!(writes.index);
+    assert!(!load.writes(&QuirksProfile::SuperChip.quirks()).index);
+
+    let draw = Instruction::DispSpr{ rp: (0, 1), n: 0 };
+    assert!(draw.reads(&vip).screen && draw.writes(&vip).screen);
+    assert_eq!(draw.platform(), Platform::SuperChip);
+    assert_eq!(Instruction::Ignore(0x123).platform(), Platform::CosmacVip);
+
+    assert_eq!(Instruction::SkipKeyPressed{ r: 0 }.flow(), Flow::Skip);
+    assert_eq!(Instruction::CallSub(0x300).flow(), Flow::Call(0x300));
+    assert_eq!(Instruction::JmpAddrOffReg0(0x300).flow(), Flow::IndirectJump);
+    assert_eq!(Instruction::AddRegL{ r: 0 }.flow(), Flow::Next);
+}
+
+#[test]
+fn mnemonics_are_parsed_back() {
+    for word in 0..=u16::MAX {
+        let instruction = match isa::parse_instruction(&word.to_be_bytes()) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        let text = instruction.to_string();
+        assert!(text.starts_with(instruction.mnemonic()), "{}", text);
+        assert_eq!(isa::parse_mnemonic(&text), Ok(instruction), "{}", text);
+    }
+    assert_eq!(isa::parse_mnemonic("ld v1, 5"), Ok(Instruction::SetByte{ r: 1, val: 5 }));
+    assert_eq!(isa::parse_mnemonic("LD V1, 256"), Err("invalid instruction LD V1, 256".to_string()));
+    assert_eq!(isa::parse_mnemonic("DRW V0, V1"), Err("invalid instruction DRW V0, V1".to_string()));
+}
+
+#[test]
+fn caveats_are_given_for_quirky_instructions() {
+    assert!(Instruction::ShrRegV{ r: 1, f: 2 }.caveats()[0].contains("COSMAC VIP"));
+    assert!(Instruction::ShrRegV{ r: 1, f: 1 }.caveats().is_empty());
+    assert_eq!(Instruction::DispSpr{ rp: (0, 1), n: 0 }.caveats().len(), 3);
+    assert!(Instruction::SetByte{ r: 1, val: 5 }.caveats().is_empty());
+}
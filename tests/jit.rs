@@ -0,0 +1,99 @@
+#![cfg(all(feature = "jit", target_arch = "x86_64", unix))]
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use chipmunk::engine::jit::{Jit, verify_lockstep};
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::quirks::{Quirks, QuirksProfile};
+
+#[test]
+fn conformance_rom_matches_interpreter() {
+    let rom = std::fs::read("tests/conformance/roms/opcodes.ch8").unwrap();
+    for profile in QuirksProfile::ALL.iter() {
+        verify_lockstep(&rom, profile.quirks(), 90, 15, 0).unwrap();
+    }
+}
+
+/// Make ROM of random register instructions, skips, memory accesses and jumps inside ROM.
+fn random_rom(rng: &mut StdRng, len: usize) -> Vec<u8> {
+    let mut rom = Vec::with_capacity(len * 2);
+    for i in 0..len {
+        let (x, y) = (rng.gen_range(0, 16), rng.gen_range(0, 16));
+        let nn: u8 = rng.gen();
+        let target = 0x200 + 2 * rng.gen_range(0, len as u16);
+        let opcode: u16 = match rng.gen_range(0, 20) {
+            0 => 0x6000 | x << 8 | nn as u16,
+            1 => 0x7000 | x << 8 | nn as u16,
+            2..=9 => 0x8000 | x << 8 | y << 4 | [0, 1, 2, 3, 4, 5, 6, 7, 0xE][rng.gen_range(0, 9)],
+            10 => 0x3000 | x << 8 | nn as u16,
+            11 => 0x4000 | x << 8 | nn as u16,
+            12 => 0x5000 | x << 8 | y << 4,
+            13 => 0x9000 | x << 8 | y << 4,
+            14 => 0xA300 | nn as u16,
+            15 => 0xF01E | x << 8,
+            16 => 0xF029 | x << 8,
+            17 => [0xF055, 0xF065, 0xF033][rng.gen_range(0, 3)] | x << 8,
+            18 => 0xC000 | x << 8 | nn as u16,
+            // Jumps go forward mostly, so that ROM does not stay in a short loop.
+            _ if i + 1 < len && rng.gen_range(0, 4) == 0 => 0x1000 | target,
+            _ => 0xB000 | (target - 0x10),
+        };
+        rom.extend_from_slice(&opcode.to_be_bytes());
+    }
+    rom
+}
+
+#[test]
+fn random_roms_match_interpreter() {
+    let mut rng = StdRng::seed_from_u64(0x0C8);
+    for seed in 0..200 {
+        let rom = random_rom(&mut rng, 48);
+        for profile in QuirksProfile::ALL.iter() {
+            if let Err(err) = verify_lockstep(&rom, profile.quirks(), 10, 50, seed) {
+                panic!("seed {} with {} : {}\nrom : {:02X?}", seed, profile, err, rom);
+            }
+        }
+    }
+}
+
+#[test]
+fn index_overflow_wraps_like_interpreter() {
+    let rom = [
+        0x60, 0xFF,             // 0x200 LD V0, 0xFF
+        0xF0, 0x1E,             // 0x202 ADD I, V0          <- overflows I after 257 iterations
+        0x12, 0x02,             // 0x204 JP 0x202
+    ];
+    verify_lockstep(&rom, Quirks::default(), 30, 20, 0).unwrap();
+
+    let mut machine = Machine::new(&rom, Quirks::default());
+    let mut jit = Jit::new();
+    for _ in 0..30 {
+        jit.run_frame(&mut machine, 20).unwrap();
+    }
+    // LD and 300 iterations of ADD and JP in 600 cycles.
+    assert_eq!(machine.registers().get_l(), (300u32 * 0xFF % 0x10000) as u16);
+}
+
+#[test]
+fn self_modifying_code_invalidates_block() {
+    let rom = [
+        0x62, 0x00,             // 0x200 LD V2, 0
+        0x60, 0x72,             // 0x202 LD V0, 0x72
+        0x61, 0x10,             // 0x204 LD V1, 0x10
+        0xA2, 0x0A,             // 0x206 LD I, 0x20A
+        0x63, 0x00,             // 0x208 LD V3, 0
+        0x72, 0x01,             // 0x20A ADD V2, 1          <- rewritten into ADD V2, 0x10
+        0x32, 0x02,             // 0x20C SE V2, 2
+        0x12, 0x08,             // 0x20E JP 0x208
+        0xF1, 0x55,             // 0x210 LD [I], V1
+        0x12, 0x08,             // 0x212 JP 0x208
+    ];
+    verify_lockstep(&rom, Quirks::default(), 4, 20, 0).unwrap();
+
+    let mut machine = Machine::new(&rom, Quirks::default());
+    let mut jit = Jit::new();
+    jit.run_frame(&mut machine, 20).unwrap();
+    assert_eq!(machine.registers().general_register(2), 0x22);
+    assert!(jit.block_count() > 0);
+}
+
@@ -0,0 +1,91 @@
+use chipmunk::engine::lint::{self, Rule};
+
+const ROM: [u8; 24] = [
+    0xD0, 0x15,             // 0x200 DRW V0, V1, 5
+    0xAF, 0xF8,             // 0x202 LD I, 0xFF8
+    0xFF, 0x55,             // 0x204 LD [I], VF
+    0xD0, 0x15,             // 0x206 DRW V0, V1, 5
+    0x80, 0x16,             // 0x208 SHR V0, V1
+    0x00, 0xFF,             // 0x20A HIGH
+    0x30, 0x00,             // 0x20C SE V0, 0
+    0x00, 0xEE,             // 0x20E RET
+    0xF0, 0x75,             // 0x210 LD R, V0
+    0x60, 0x01,             // 0x212 LD V0, 1
+    0x12, 0x12,             // 0x214 JP 0x212
+    0x00, 0x00,
+];
+
+fn rules(warnings: &[lint::Warning]) -> Vec<(u16, Rule)> {
+    warnings.iter().map(|warning| (warning.pc, warning.rule)).collect()
+}
+
+#[test]
+fn every_rule_is_checked() {
+    let warnings = lint::lint(&ROM, &Rule::ALL);
+    assert_eq!(rules(&warnings), [
+        (0x200, Rule::UnsetIndex),
+        (0x204, Rule::MemoryOverrun),
+        (0x206, Rule::Quirk),
+        (0x208, Rule::Quirk),
+        (0x20A, Rule::Dialect),
+        (0x20E, Rule::UnmatchedReturn),
+        (0x210, Rule::Dialect),
+        (0x212, Rule::Unreachable),
+    ], "{:#?}", warnings);
+    assert_eq!(warnings[6].to_string(), "dialect at 0x210 : SUPER-CHIP opcode 0xF075 is reached, but is not supported");
+}
+
+#[test]
+fn skips_with_low_nibble_are_not_supported() {
+    let warnings = lint::lint(&[0x51, 0x22, 0x12, 0x02], &[Rule::Dialect]);
+    assert_eq!(warnings.len(), 1, "{:#?}", warnings);
+    assert_eq!(warnings[0].to_string(), "dialect at 0x200 : XO-CHIP opcode 0x5122 is reached, but is not supported");
+
+    let warnings = lint::lint(&[0x91, 0x21, 0x12, 0x02], &[Rule::Dialect]);
+    assert_eq!(warnings.len(), 1, "{:#?}", warnings);
+    assert_eq!(warnings[0].to_string(), "dialect at 0x200 : unknown opcode 0x9121 is reached, but is not supported");
+}
+
+#[test]
+fn return_without_call_is_found() {
+    let rom = [0x60, 0x01, 0x00, 0xEE];
+    let warnings = lint::lint(&rom, &Rule::ALL);
+    assert_eq!(rules(&warnings), [(0x202, Rule::UnmatchedReturn)]);
+}
+
+#[test]
+fn bad_targets_are_found() {
+    let rom = [
+        0x12, 0x06,         // 0x200 JP 0x206
+        0x13, 0x00,         // 0x202 JP 0x300
+        0x12, 0x03,         // 0x204 JP 0x203
+        0x32, 0x00,         // 0x206 SE V2, 0
+        0x12, 0x02,         // 0x208 JP 0x202
+        0x12, 0x04,         // 0x20A JP 0x204
+    ];
+    let warnings: Vec<String> = lint::lint(&rom, &[Rule::BadTarget]).iter().map(|warning| warning.to_string()).collect();
+    assert_eq!(warnings, [
+        "bad-target at 0x202 : Jump target 0x300 is outside of ROM",
+        "bad-target at 0x204 : Jump target 0x203 is odd",
+    ]);
+}
+
+#[test]
+fn warnings_are_written_as_csv_and_json() {
+    let warnings = lint::lint(&ROM, &[Rule::UnsetIndex, Rule::MemoryOverrun]);
+    let mut csv = Vec::new();
+    lint::write_csv(&warnings, &mut csv).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(), "\
+pc,rule,message
+0x200,unset-index,\"DRW V0, V1, 5 reads I, which may be never set\"
+0x204,memory-overrun,\"LD [I], VF accesses 16 byte(s) from 0xFF8, past the end of memory\"
+");
+
+    let mut json = Vec::new();
+    lint::write_json(&warnings[..1], &mut json).unwrap();
+    assert_eq!(String::from_utf8(json).unwrap(), "\
+[
+  {\"pc\": 512, \"rule\": \"unset-index\", \"message\": \"DRW V0, V1, 5 reads I, which may be never set\"}
+]
+");
+}
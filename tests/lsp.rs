@@ -0,0 +1,171 @@
+use std::io::Cursor;
+
+use chipmunk::engine::json::Json;
+use chipmunk::engine::lsp::{self, Language, Server};
+
+fn frame(message: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{}", message.len(), message)
+}
+
+fn request(id: usize, method: &str, params: &str) -> Json {
+    Json::parse(&format!(r#"{{"jsonrpc": "2.0", "id": {}, "method": "{}", "params": {}}}"#, id, method, params)).unwrap()
+}
+
+fn open(server: &mut Server, uri: &str, text: &str) -> Vec<Json> {
+    let params = Json::object(vec![("textDocument", Json::object(vec![
+        ("uri", uri.into()),
+        ("languageId", "".into()),
+        ("text", text.into()),
+    ]))]);
+    server.handle(&Json::object(vec![("method", "textDocument/didOpen".into()), ("params", params)]))
+}
+
+fn at(uri: &str, line: usize, character: usize) -> String {
+    format!(r#"{{"textDocument": {{"uri": "{}"}}, "position": {{"line": {}, "character": {}}}}}"#, uri, line, character)
+}
+
+const SOURCE: &str = "\
+start:  LD V0, 5
+loop:   LD I, ball
+        SHR V0, V1
+        JP loop
+ball:   DB 0x20, 0x70
+";
+
+#[test]
+fn messages_are_served_over_stream() {
+    let input = [
+        frame(r#"{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}"#),
+        frame(r#"{"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {"textDocument": {"uri": "file:///game.8o", "languageId": "octo", "text": ": main\n  v0 := 1\n  again\n"}}}"#),
+        frame(r#"{"jsonrpc": "2.0", "id": 2, "method": "shutdown"}"#),
+        frame(r#"{"jsonrpc": "2.0", "method": "exit"}"#),
+    ].concat();
+    let mut output = Vec::new();
+    lsp::serve(&mut Cursor::new(input), &mut output).unwrap();
+
+    let mut output = Cursor::new(output);
+    let mut messages = Vec::new();
+    while let Some(message) = lsp::read_message(&mut output).unwrap() {
+        messages.push(message.unwrap());
+    }
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0].get("result").get("capabilities").get("hoverProvider"), &Json::Bool(true));
+    let diagnostic = &messages[1].get("params").get("diagnostics").as_array()[0];
+    assert_eq!(diagnostic.get("message").as_str(), Some("again without loop"));
+    assert_eq!(diagnostic.get("range").get("start").get("line").as_usize(), Some(2));
+    assert_eq!(messages[2].get("id").as_usize(), Some(2));
+}
+
+#[test]
+fn malformed_message_is_answered_with_parse_error() {
+    let input = [
+        "Content-Length: 5\r\n\r\n{bad}".to_string(),
+        frame(r#"{"jsonrpc": "2.0", "id": 1, "method": "shutdown"}"#),
+    ].concat();
+    let mut output = Vec::new();
+    lsp::serve(&mut Cursor::new(input), &mut output).unwrap();
+
+    let mut output = Cursor::new(output);
+    let error = lsp::read_message(&mut output).unwrap().unwrap().unwrap();
+    assert_eq!(error.get("error").get("code"), &Json::Number(-32700.0));
+    assert!(error.get("id").is_null());
+    let reply = lsp::read_message(&mut output).unwrap().unwrap().unwrap();
+    assert_eq!(reply.get("id").as_usize(), Some(1));
+}
+
+#[test]
+fn labels_are_navigated() {
+    let mut server = Server::new();
+    let replies = open(&mut server, "file:///ball.asm", SOURCE);
+    assert!(replies[0].get("params").get("diagnostics").as_array().is_empty());
+
+    let definition = &server.handle(&request(1, "textDocument/definition", &at("file:///ball.asm", 1, 16)))[0];
+    let location = &definition.get("result").as_array()[0];
+    assert_eq!(location.get("range").get("start"), &Json::parse(r#"{"line": 4, "character": 0}"#).unwrap());
+
+    let references = &server.handle(&request(2, "textDocument/references", &at("file:///ball.asm", 3, 12)))[0];
+    let lines: Vec<usize> = references.get("result").as_array().iter()
+        .map(|location| location.get("range").get("start").get("line").as_usize().unwrap())
+        .collect();
+    assert_eq!(lines, [1, 3]);
+}
+
+#[test]
+fn positions_are_in_utf16_code_units() {
+    let mut server = Server::new();
+    // U+1D11E is two UTF-16 code units, and four bytes.
+    let text = ": main\n  v0 := \u{1D11E} jump main\n";
+    open(&mut server, "file:///game.8o", text);
+
+    let definition = &server.handle(&request(1, "textDocument/definition", &at("file:///game.8o", 1, 17)))[0];
+    let start = definition.get("result").as_array()[0].get("range").get("start");
+    assert_eq!(start, &Json::parse(r#"{"line": 0, "character": 2}"#).unwrap());
+
+    let references = &server.handle(&request(2, "textDocument/references", &at("file:///game.8o", 0, 3)))[0];
+    let ranges: Vec<String> = references.get("result").as_array().iter().map(|location| location.get("range").to_string()).collect();
+    assert_eq!(ranges, [
+        r#"{"start":{"line":0,"character":2},"end":{"line":0,"character":6}}"#,
+        r#"{"start":{"line":1,"character":16},"end":{"line":1,"character":20}}"#,
+    ]);
+}
+
+#[test]
+fn hover_shows_instruction_and_quirks() {
+    let mut server = Server::new();
+    open(&mut server, "file:///ball.asm", SOURCE);
+    let hover = &server.handle(&request(1, "textDocument/hover", &at("file:///ball.asm", 2, 9)))[0];
+    let text = hover.get("result").get("contents").get("value").as_str().unwrap();
+    assert!(text.starts_with("0x204  `SHR V0, V1`  encoded as `8016`"), "{}", text);
+    assert!(text.contains("- Quirk : shifts Vy into Vx on COSMAC VIP"), "{}", text);
+
+    let hover = &server.handle(&request(2, "textDocument/hover", &at("file:///ball.asm", 1, 16)))[0];
+    let text = hover.get("result").get("contents").get("value").as_str().unwrap();
+    assert!(text.starts_with("`ball` is at 0x208"), "{}", text);
+}
+
+#[test]
+fn completion_has_mnemonics_registers_and_labels() {
+    let mut server = Server::new();
+    open(&mut server, "file:///ball.asm", SOURCE);
+    let completion = &server.handle(&request(1, "textDocument/completion", &at("file:///ball.asm", 0, 0)))[0];
+    let labels: Vec<&str> = completion.get("result").as_array().iter()
+        .filter_map(|item| item.get("label").as_str())
+        .collect();
+    for label in ["CLS", "DRW", "SKNP", "VF", "DT", "loop", "ball"].iter() {
+        assert!(labels.contains(label), "{}", label);
+    }
+    assert!(!labels.contains(&"Ignore"));
+}
+
+#[test]
+fn documents_are_formatted() {
+    let source = "start: ld v0,5   ;  init\n  loop:  jp   loop\n; end\n";
+    assert_eq!(lsp::format(source, Language::Assembly), "\
+start:  LD V0, 5          ; init
+loop:   JP loop
+; end
+");
+
+    let source = ": main\nloop\nif v0 == 1 begin\nv0 := 2\nelse\nv0 := 3\nend\nagain   \n\n: sub # helper\nreturn\n";
+    assert_eq!(lsp::format(source, Language::Octo), "\
+: main
+  loop
+    if v0 == 1 begin
+      v0 := 2
+    else
+      v0 := 3
+    end
+  again
+
+: sub # helper
+  return
+");
+}
+
+#[test]
+fn unknown_requests_are_errors() {
+    let mut server = Server::new();
+    let reply = &server.handle(&request(7, "workspace/symbol", "{}"))[0];
+    assert_eq!(reply.get("error").get("code"), &Json::Number(-32601.0));
+    assert!(server.handle(&Json::parse(r#"{"method": "$/cancelRequest"}"#).unwrap()).is_empty());
+}
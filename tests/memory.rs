@@ -0,0 +1,52 @@
+use chipmunk::engine::isa::Instruction;
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::memory::Memory;
+use chipmunk::engine::peripheral::Bus;
+use chipmunk::engine::quirks::Quirks;
+
+#[test]
+fn cached_instruction_is_invalidated_by_overlapping_write() {
+    let mut memory = Memory::from_rom(&[0x60, 0x41, 0x61, 0x02]);
+    assert_eq!(memory.parse_instruction(0x200), Some(Instruction::SetByte{ r: 0, val: 0x41 }));
+    assert_eq!(memory.parse_instruction(0x201), Some(Instruction::SkipNeq{ r: 1, val: 0x61 }));
+    assert_eq!(memory.parse_instruction(0x202), Some(Instruction::SetByte{ r: 1, val: 2 }));
+
+    // Writing 0x201 changes instructions of both 0x200 and 0x201, but not 0x202.
+    memory.write(0x201, 0x35);
+    assert_eq!(memory.parse_instruction(0x200), Some(Instruction::SetByte{ r: 0, val: 0x35 }));
+    assert_eq!(memory.parse_instruction(0x201), Some(Instruction::SkipEq{ r: 5, val: 0x61 }));
+    assert_eq!(memory.parse_instruction(0x202), Some(Instruction::SetByte{ r: 1, val: 2 }));
+
+    memory.store_from(&[0xFF, 0xFF], 0x202);
+    assert_eq!(memory.parse_instruction(0x202), None);
+    assert_eq!(memory.get_data_bytes(0x200, 4), &[0x60, 0x35, 0xFF, 0xFF]);
+    assert_eq!(memory.get_data_bytes(0xFFE, 4), &[0x00, 0x00]);
+}
+
+#[test]
+fn self_modifying_code_runs_written_instruction() {
+    let rom = [
+        0x60, 0x63,             // 0x200 LD V0, 0x63
+        0x61, 0x07,             // 0x202 LD V1, 0x07
+        0xA2, 0x0A,             // 0x204 LD I, 0x20A
+        0xF1, 0x55,             // 0x206 LD [I], V1 : rewrites 0x20A into LD V3, 0x07
+        0x00, 0x00,             // 0x208 (ignored)
+        0x62, 0x01,             // 0x20A LD V2, 1
+    ];
+    let mut machine = Machine::new(&rom, Quirks::default());
+    // Decode 0x20A before it is rewritten.
+    assert_eq!(machine.memory().parse_instruction(0x20A), Some(Instruction::SetByte{ r: 2, val: 1 }));
+
+    machine.run_frame(6).unwrap();
+    assert_eq!(machine.registers().general_register(2), 0);
+    assert_eq!(machine.registers().general_register(3), 0x07);
+}
+
+#[test]
+fn bytes_past_end_of_memory_are_read_as_zero() {
+    let mut memory = Memory::from_rom(&[]);
+    memory.store_from(&[0xAA, 0xBB], 0xFFE);
+    let mut buffer = [0xFF; 5];
+    memory.read_into(0xFFE, &mut buffer);
+    assert_eq!(buffer, [0xAA, 0xBB, 0, 0, 0]);
+}
@@ -0,0 +1,150 @@
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::octo;
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::rom::{Rom, V0, V1, V2};
+
+fn run(rom: &[u8], steps: usize) -> Machine {
+    let mut machine = Machine::new(rom, Quirks::default());
+    for _ in 0..steps {
+        machine.step().unwrap();
+    }
+    machine
+}
+
+#[test]
+fn statements_are_compiled() {
+    let source = "
+        :alias x v0
+        :const SPEED 2
+        : main
+            x := 5
+            v1 += SPEED
+            v2 -= 1
+            i := ball
+            sprite x v1 5
+            ball_loop
+        : ball_loop
+            jump ball_loop
+        : ball
+            0x20 0x70 0xF8 0x70 0x20
+    ";
+    let program = octo::compile(source, "ball.8o").unwrap();
+    let expected = Rom::new()
+        .ld(V0, 5)
+        .add(V1, 2)
+        .add(V2, 0xFF)
+        .ld_i("ball")
+        .drw(V0, V1, 5)
+        .call("loop")
+        .label("loop")
+        .jp("loop")
+        .sprite("ball", &[0x20, 0x70, 0xF8, 0x70, 0x20])
+        .build()
+        .unwrap();
+    assert_eq!(program.rom, expected);
+
+    assert_eq!(program.symbols.label(0x200), Some("main"));
+    assert_eq!(program.symbols.label(0x20E), Some("ball"));
+    assert_eq!(program.symbols.line(0x200), Some(("ball.8o", 5)));
+    assert_eq!(program.symbols.line(0x20C), Some(("ball.8o", 12)));
+}
+
+#[test]
+fn main_is_jumped_into_unless_first() {
+    let program = octo::compile(": helper return : main helper", "a.8o").unwrap();
+    assert_eq!(program.rom, [0x12, 0x04, 0x00, 0xEE, 0x22, 0x02]);
+    assert_eq!(octo::compile(": start return", "a.8o").unwrap_err(), "line 1 : undefined name main");
+}
+
+#[test]
+fn control_structures_are_compiled() {
+    let source = "
+        : main
+            v0 := 0
+            v1 := 0
+            loop
+                v0 += 1
+                if v0 == 3 then v1 += 10
+                if v0 > 5 begin
+                    v2 := 1
+                else
+                    v2 := 2
+                end
+                while v0 < 7
+            again
+            loop again
+    ";
+    let rom = octo::compile(source, "a.8o").unwrap().rom;
+    let machine = run(&rom, 200);
+    assert_eq!(machine.registers().general_register(0), 7);
+    assert_eq!(machine.registers().general_register(1), 10);
+    assert_eq!(machine.registers().general_register(2), 1);
+}
+
+#[test]
+fn macros_and_calc_are_expanded() {
+    let source = "
+        :macro add-twice reg value { reg += value reg += value }
+        :calc WIDTH { 8 * 4 + 1 }
+        :const BASE 0x300
+        : main
+            add-twice v3 WIDTH
+            :unpack 0xA data
+            i := long data
+            save v1 - v2
+            plane 3
+            :byte { WIDTH - 1 }
+        :org BASE
+        : data
+        :next target
+            v4 := 0
+    ";
+    let program = octo::compile(source, "a.8o").unwrap();
+    // 8 * 4 + 1 is evaluated from right to left as 8 * 5.
+    assert_eq!(program.rom[..21], [
+        0x73, 40, 0x73, 40,
+        0x60, 0xA3, 0x61, 0x00,
+        0xF0, 0x00, 0x03, 0x00,
+        0x51, 0x22,
+        0xF3, 0x01,
+        39, 0, 0, 0, 0,
+    ]);
+    assert_eq!(program.rom.len(), 0x102);
+    assert_eq!(program.rom[0x100..], [0x64, 0x00]);
+    assert_eq!(program.symbols.label(0x301), Some("target"));
+}
+
+#[test]
+fn compiled_program_runs() {
+    let source = "
+        : main
+            v0 := 200
+            v1 := 100
+            if v0 >= v1 then v3 := 1
+            if v1 >= v0 then v3 := 2
+            if v0 <= 200 then v4 := 9
+            digits
+            loop again
+        : digits
+            i := buffer
+            bcd v0
+            load v2
+            return
+        : buffer 0 0 0
+    ";
+    let rom = octo::compile(source, "a.8o").unwrap().rom;
+    let machine = run(&rom, 40);
+    let v = |r| machine.registers().general_register(r);
+    assert_eq!((v(0), v(1), v(2)), (2, 0, 0));
+    assert_eq!((v(3), v(4)), (1, 9));
+}
+
+#[test]
+fn errors_have_line_numbers() {
+    assert_eq!(octo::compile(": main\n  v0 := 300", "a.8o").unwrap_err(), "line 2 : value 300 does not fit in byte");
+    assert_eq!(octo::compile(": main\n  loop\n v0 += 1", "a.8o").unwrap_err(), "line 3 : loop is not closed");
+    assert_eq!(octo::compile(": main\n\n  end", "a.8o").unwrap_err(), "line 3 : end without if");
+    assert_eq!(octo::compile(": main\n  va ~= v1", "a.8o").unwrap_err(), "line 2 : unknown operator ~=");
+    assert_eq!(octo::compile(": main\n:macro m { m }\nm\n", "a.8o").unwrap_err(), "line 2 : macro m is nested too deeply");
+    assert_eq!(octo::compile(":macro a { b }\n:macro b { a }\n: main a", "a.8o").unwrap_err(), "line 2 : macro a is nested too deeply");
+}
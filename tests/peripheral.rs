@@ -0,0 +1,98 @@
+use chipmunk::engine::keypad::Keypad;
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::memory::Memory;
+use chipmunk::engine::peripheral::{Bus, Input, Timers};
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::register::TimerSideEffect;
+use chipmunk::engine::screen::Screen;
+use chipmunk::engine::timer::CountdownTimers;
+
+/// Memory which records every write.
+struct LoggingBus {
+    memory: Memory,
+    writes: Vec<(u16, u8)>,
+}
+
+impl Bus for LoggingBus {
+    fn read(&self, addr: u16) -> u8 { self.memory.read(addr) }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.writes.push((addr, value));
+        self.memory.write(addr, value);
+    }
+}
+
+/// Keypad whose every key is always pressed.
+struct AllPressed;
+
+impl Input for AllPressed {
+    fn is_pressed(&self, _key: u8) -> bool { true }
+    fn press(&mut self, _key: u8) {}
+    fn release(&mut self, _key: u8) {}
+    fn release_all(&mut self) {}
+}
+
+/// Timers which never count down.
+#[derive(Default)]
+struct FrozenTimers {
+    dt: u8,
+    st: u8,
+}
+
+impl Timers for FrozenTimers {
+    fn delay(&self) -> u8 { self.dt }
+    fn set_delay(&mut self, value: u8) { self.dt = value; }
+    fn sound(&self) -> u8 { self.st }
+    fn set_sound(&mut self, value: u8) { self.st = value; }
+    fn tick(&mut self) -> TimerSideEffect { TimerSideEffect::None }
+}
+
+const ROM: [u8; 14] = [
+    0x60, 0x2A,             // 0x200 LD V0, 0x2A
+    0xF0, 0x15,             // 0x202 LD DT, V0
+    0xA3, 0x00,             // 0x204 LD I, 0x300
+    0xF0, 0x55,             // 0x206 LD [I], V0
+    0xE1, 0x9E,             // 0x208 SKP V1
+    0x00, 0xE0,             // 0x20A CLS (skipped when key is pressed)
+    0xF2, 0x07,             // 0x20C LD V2, DT
+];
+
+#[test]
+fn machine_runs_on_custom_peripherals() {
+    let bus = LoggingBus{ memory: Memory::from_rom(&ROM), writes: Vec::new() };
+    let mut machine = Machine::with_peripherals(bus, Screen::new(), AllPressed, FrozenTimers::default(), Quirks::default());
+    for _ in 0..6 {
+        machine.step().unwrap();
+        machine.tick_timers();
+    }
+
+    assert_eq!(machine.bus().writes, vec![(0x300, 0x2A)]);
+    assert_eq!(machine.registers().get_pc(), 0x20E);
+    assert_eq!(machine.registers().general_register(2), 0x2A);
+    assert_eq!(machine.timers().delay(), 0x2A);
+}
+
+#[test]
+fn default_peripherals_count_down() {
+    let mut machine = Machine::with_peripherals(
+        Memory::from_rom(&ROM), Screen::new(), Keypad::new(), CountdownTimers::new(), Quirks::default());
+    for _ in 0..6 {
+        machine.step().unwrap();
+        machine.tick_timers();
+    }
+
+    // CLS is not skipped, so LD V2, DT is not processed yet.
+    assert_eq!(machine.registers().get_pc(), 0x20C);
+    assert_eq!(machine.timers().delay(), 0x2A - 5);
+    assert_eq!(machine.memory().read(0x300), 0x2A);
+}
+
+#[test]
+fn sprite_past_end_of_memory_is_fault() {
+    use chipmunk::engine::machine::Fault;
+
+    // LD I, 0xFFE / DRW V0, V0, 5
+    let mut machine = Machine::new(&[0xAF, 0xFE, 0xD0, 0x05], Quirks::default());
+    machine.step().unwrap();
+    assert_eq!(machine.step().err(), Some(Fault::MemoryOutOfRange{ pc: 0x202, l: 0xFFE }));
+}
@@ -0,0 +1,85 @@
+use std::{fs, path::Path};
+
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::profile::Profiler;
+use chipmunk::engine::quirks::Quirks;
+
+#[test]
+fn subroutine_time_is_attributed_by_call_stack() {
+    let rom = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/roms/opcodes.ch8")).unwrap();
+    let mut machine = Machine::new(&rom, Quirks::default());
+    let mut profiler = Profiler::new();
+    for _ in 0..60 {
+        for _ in 0..15 {
+            profiler.observe(&machine);
+            machine.step().unwrap();
+        }
+        profiler.end_frame();
+        machine.tick_timers();
+    }
+
+    let total = profiler.total_cycles();
+    let functions = profiler.functions();
+    assert_eq!(total, 900);
+    assert_eq!(functions[&0x200].inclusive, total);
+    assert_eq!(functions.values().map(|time| time.exclusive).sum::<u64>(), total);
+
+    // Every entry calls `print` and `digit` leaf subroutines once.
+    let (print, digit) = (&functions[&0x2E8], &functions[&0x306]);
+    assert_eq!((print.calls, digit.calls), (11, 11));
+    assert_eq!(print.inclusive, print.exclusive);
+    assert_eq!(profiler.pc_count(0x2E8), 11);
+    assert_eq!(profiler.instruction_counts()["CALL addr"], 22);
+
+    // ROM never reads DT, so every frame overruns.
+    assert_eq!(profiler.frames().len(), 60);
+    assert!(profiler.frames().iter().all(|frame| !frame.synced && frame.busy_cycles == 15));
+
+    let mut report = Vec::new();
+    profiler.write_report(&mut report, &machine, 3).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    assert!(report.contains(" CALL addr                        22 "), "{}", report);
+    assert!(report.contains(" Cycles per frame : min 15, avg 15.0, max 15\n"), "{}", report);
+    assert!(report.contains("  FRAME   CYCLES     BUSY  SYNC\n      0       15       15  no\n"), "{}", report);
+}
+
+#[test]
+fn instruction_variants_are_counted_separately() {
+    let rom = [
+        0x60, 0x01,             // 0x200 LD V0, 1
+        0xA3, 0x00,             // 0x202 LD I, 0x300
+        0xA3, 0x00,             // 0x204 LD I, 0x300
+        0xF0, 0x55,             // 0x206 LD [I], V0
+        0x12, 0x08,             // 0x208 JP 0x208
+    ];
+    let mut machine = Machine::new(&rom, Quirks::default());
+    let mut profiler = Profiler::new();
+    for _ in 0..5 {
+        profiler.observe(&machine);
+        machine.step().unwrap();
+    }
+
+    let counts = profiler.instruction_counts();
+    assert_eq!((counts["LD Vx, byte"], counts["LD I, addr"], counts["LD [I], Vx"], counts["JP addr"]), (1, 2, 1, 1));
+    assert_eq!(counts.len(), 4);
+}
+
+#[test]
+fn folded_stacks_use_labels_of_symbols() {
+    use chipmunk::engine::symbols::SymbolTable;
+
+    let rom = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/roms/opcodes.ch8")).unwrap();
+    let mut machine = Machine::new(&rom, Quirks::default());
+    let mut profiler = Profiler::new();
+    profiler.set_symbols(SymbolTable::parse("label 0x200 main\nlabel 0x2E8 print\n").unwrap());
+    for _ in 0..100 {
+        profiler.observe(&machine);
+        machine.step().unwrap();
+    }
+
+    let mut folded = Vec::new();
+    profiler.write_folded(&mut folded).unwrap();
+    let folded = String::from_utf8(folded).unwrap();
+    assert!(folded.lines().any(|line| line.starts_with("main;print ")), "{}", folded);
+    assert!(folded.lines().all(|line| line.starts_with("main")), "{}", folded);
+}
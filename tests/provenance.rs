@@ -0,0 +1,113 @@
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::provenance::{Location, Origin, Provenance, Source};
+use chipmunk::engine::quirks::Quirks;
+
+/// Process given count of instructions with provenance tracking.
+fn run(machine: &mut Machine, provenance: &mut Provenance, cycles: usize) {
+    for _ in 0..cycles {
+        provenance.observe(machine);
+        machine.step().unwrap();
+    }
+}
+
+fn sources(origin: Origin<'_>) -> (u16, Vec<Source>) {
+    match origin {
+        Origin::Written(write) => (write.pc, write.sources.clone()),
+        other => panic!("{:?} is not written", other),
+    }
+}
+
+#[test]
+fn writes_record_instruction_and_sources() {
+    let rom = [
+        0x60, 0x05,             // 0x200 LD V0, 5
+        0xC1, 0x03,             // 0x202 RND V1, 3
+        0x80, 0x14,             // 0x204 ADD V0, V1
+        0xA3, 0x00,             // 0x206 LD I, 0x300
+        0xF0, 0x33,             // 0x208 LD B, V0
+        0xF2, 0x65,             // 0x20A LD V2, [I]
+        0xF3, 0x07,             // 0x20C LD V3, DT
+        0xF4, 0x0A,             // 0x20E LD V4, K
+    ];
+    let mut machine = Machine::new(&rom, Quirks::default());
+    let mut provenance = Provenance::new();
+    run(&mut machine, &mut provenance, 8);
+    machine.press_key(9);
+    run(&mut machine, &mut provenance, 1);
+
+    let v = |r| Source::Location(Location::V(r));
+    assert_eq!(sources(provenance.last_write(Location::V(0))), (0x20A, vec![Source::Location(Location::Memory(0x300)), Source::Location(Location::I)]));
+    assert_eq!(sources(provenance.origin_before(Location::V(1), 5)), (0x202, vec![Source::Random, Source::Immediate(3)]));
+    assert_eq!(sources(provenance.last_write(Location::V(0xF))), (0x204, vec![v(0), v(1)]));
+    assert_eq!(sources(provenance.last_write(Location::Memory(0x302))), (0x208, vec![v(0)]));
+    assert_eq!(sources(provenance.last_write(Location::V(3))), (0x20C, vec![Source::DelayTimer]));
+    assert_eq!(sources(provenance.last_write(Location::V(4))), (0x20E, vec![Source::Key]));
+    // 0xFx65 increments I with default quirks.
+    assert_eq!(sources(provenance.last_write(Location::I)), (0x20A, vec![Source::Location(Location::I)]));
+    assert_eq!(provenance.last_write(Location::V(5)), Origin::Initial);
+    // V0 is read by 0xFx33 before 0xFx65 overwrites it.
+    assert_eq!(sources(provenance.origin_before(Location::V(0), 4)), (0x204, vec![v(0), v(1)]));
+
+    let text = provenance.explain(&machine, Location::V(2), 3);
+    let lines: Vec<&str> = text.lines().collect();
+    // 5 + random of 0~3 is a single digit, so V2 gets the whole value from the last BCD digit.
+    let value = machine.registers().general_register(2);
+    assert_eq!(lines[0], format!("V2 = 0x{:02X}", value));
+    assert_eq!(lines[1], format!("  V2 = 0x{:02X} written by LD V2, [I] at 0x20A (cycle 5) from 0x302, I", value));
+    assert_eq!(lines[2], format!("    0x302 = 0x{:02X} written by LD B, V0 at 0x208 (cycle 4) from V0", value));
+    assert_eq!(lines[3], format!("      V0 = 0x{:02X} written by ADD V0, V1 at 0x204 (cycle 2) from V0, V1", value));
+    assert_eq!(lines[4], "    I = 0x300 written by LD I, 0x300 at 0x206 (cycle 3) from immediate 0x300");
+    assert_eq!(lines.len(), 5);
+}
+
+#[test]
+fn old_writes_are_forgotten() {
+    let rom = [
+        0x70, 0x01,             // 0x200 ADD V0, 1
+        0x12, 0x00,             // 0x202 JP 0x200
+    ];
+    let mut machine = Machine::new(&rom, Quirks::default());
+    let mut provenance = Provenance::new();
+    run(&mut machine, &mut provenance, 100);
+
+    let text = provenance.explain(&machine, Location::V(0), 100);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "V0 = 0x32");
+    assert_eq!(lines[1], "  V0 = 0x32 written by ADD V0, 0x01 at 0x200 (cycle 98) from V0, immediate 0x01");
+    assert_eq!(lines[2], "    V0 = 0x31 written by ADD V0, 0x01 at 0x200 (cycle 96) from V0, immediate 0x01");
+    assert_eq!(lines.last(), Some(&"                                  V0 was written before kept history"));
+    assert_eq!(lines.len(), 18);
+}
+
+#[test]
+fn location_written_twice_by_instruction_is_one_write() {
+    let rom = [
+        0x61, 0xFF,             // 0x200 LD V1, 0xFF
+        0x6F, 0x02,             // 0x202 LD VF, 2
+        0x8F, 0x14,             // 0x204 ADD VF, V1 writes sum and then carry
+        0x12, 0x06,             // 0x206 JP 0x206
+    ];
+    let mut machine = Machine::new(&rom, Quirks::default());
+    let mut provenance = Provenance::new();
+    run(&mut machine, &mut provenance, 4);
+
+    match provenance.last_write(Location::V(0xF)) {
+        Origin::Written(write) => assert_eq!((write.pc, write.value), (0x204, Some(1))),
+        other => panic!("{:?} is not written", other),
+    }
+    let text = provenance.explain(&machine, Location::V(0xF), 2);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[1], "  VF = 0x01 written by ADD VF, V1 at 0x204 (cycle 2) from VF, V1");
+    assert_eq!(lines[2], "    VF = 0x02 written by LD VF, 0x02 at 0x202 (cycle 1) from immediate 0x02");
+
+    // Each instruction takes one entry of kept history.
+    let rom = [
+        0x8F, 0x14,             // 0x200 ADD VF, V1
+        0x12, 0x00,             // 0x202 JP 0x200
+    ];
+    let mut machine = Machine::new(&rom, Quirks::default());
+    let mut provenance = Provenance::new();
+    run(&mut machine, &mut provenance, 40);
+    assert_eq!(sources(provenance.origin_before(Location::V(0xF), 9)).0, 0x200);
+    assert_eq!(provenance.origin_before(Location::V(0xF), 8), Origin::Forgotten);
+}
@@ -0,0 +1,75 @@
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::memory::MEMORY_SIZE;
+use chipmunk::engine::quirks::QuirksProfile;
+use chipmunk::engine::recompile::{recompile, Program, Runtime};
+use chipmunk::engine::screen::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+// Sources below are emitted by `chipmunk recompile` with default quirks.
+#[allow(dead_code)]
+#[path = "recompiled/opcodes.rs"]
+mod opcodes;
+#[allow(dead_code)]
+#[path = "recompiled/self_modifying.rs"]
+mod self_modifying;
+#[allow(dead_code)]
+#[path = "recompiled/index_overflow.rs"]
+mod index_overflow;
+
+/// Run program by recompiled blocks and by interpreter, and compare machines at the end of every frame.
+fn run_in_lockstep(program: &'static Program, frames: u64, cycles: usize) -> Runtime {
+    let mut recompiled = Machine::new(program.rom, program.quirks);
+    let mut interpreter = Machine::new(program.rom, program.quirks);
+    let mut runtime = Runtime::new(program);
+
+    for frame in 0..frames {
+        let start = recompiled.cycles();
+        while recompiled.cycles() - start < cycles as u64 {
+            let budget = cycles as u64 - (recompiled.cycles() - start);
+            runtime.step(&mut recompiled, budget).unwrap();
+        }
+        interpreter.run_frame(cycles).unwrap();
+        recompiled.tick_timers();
+
+        let (left, right) = (recompiled.registers(), interpreter.registers());
+        assert_eq!(left.get_pc(), right.get_pc(), "PC at frame {}", frame);
+        assert_eq!(left.general_registers(), right.general_registers(), "V at frame {}", frame);
+        assert_eq!(left.get_l(), right.get_l(), "I at frame {}", frame);
+        assert_eq!(recompiled.cycles(), interpreter.cycles());
+        assert_eq!(recompiled.memory().get_data_bytes(0, MEMORY_SIZE), interpreter.memory().get_data_bytes(0, MEMORY_SIZE));
+        for y in 0..SCREEN_HEIGHT as u8 {
+            for x in 0..SCREEN_WIDTH as u8 {
+                assert_eq!(recompiled.screen().is_drawn((x, y)), interpreter.screen().is_drawn((x, y)));
+            }
+        }
+    }
+    runtime
+}
+
+#[test]
+fn emitted_sources_are_up_to_date() {
+    let quirks = QuirksProfile::Chipmunk.quirks();
+    let rom = std::fs::read("tests/conformance/roms/opcodes.ch8").unwrap();
+    assert_eq!(recompile(&rom, quirks, "opcodes.ch8"), include_str!("recompiled/opcodes.rs"));
+    assert_eq!(recompile(self_modifying::PROGRAM.rom, quirks, "self_modifying.ch8"), include_str!("recompiled/self_modifying.rs"));
+    assert_eq!(recompile(index_overflow::PROGRAM.rom, quirks, "index_overflow.ch8"), include_str!("recompiled/index_overflow.rs"));
+}
+
+#[test]
+fn recompiled_conformance_rom_matches_interpreter() {
+    let runtime = run_in_lockstep(&opcodes::PROGRAM, 90, 15);
+    assert_eq!(runtime.block_count(), opcodes::PROGRAM.blocks.len());
+}
+
+#[test]
+fn self_modifying_code_disables_block() {
+    // 0x20A in block 0x208 is rewritten by 0xF155, so the block is interpreted from then on.
+    let runtime = run_in_lockstep(&self_modifying::PROGRAM, 4, 20);
+    assert_eq!(runtime.block_count(), self_modifying::PROGRAM.blocks.len() - 1);
+}
+
+#[test]
+fn index_overflow_wraps_like_interpreter() {
+    // ADD I, V0 with V0 = 0xFF overflows I after 257 iterations.
+    let runtime = run_in_lockstep(&index_overflow::PROGRAM, 30, 20);
+    assert_eq!(runtime.block_count(), index_overflow::PROGRAM.blocks.len());
+}
@@ -0,0 +1,54 @@
+//! Recompiled from index_overflow.ch8 by `chipmunk recompile`. Build it against the chipmunk library.
+
+use chipmunk::engine::device::Device;
+use chipmunk::engine::frontend::{self, Exit, Pacing};
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::recompile::{Block, Program, Runtime, State};
+
+const ROM: [u8; 6] = [
+    0x60, 0xFF, 0xF0, 0x1E, 0x12, 0x02,
+];
+
+fn block_200(s: &mut State) {
+    s.v[0x0] = 0xFF; // 0x200 60FF
+    s.pc = 0x202;
+}
+
+fn block_202(s: &mut State) {
+    s.l = s.l.wrapping_add(s.v[0x0] as u16); // 0x202 F01E
+    s.pc = 0x202; // 0x204 1202
+}
+
+pub static PROGRAM: Program = Program {
+    rom: &ROM,
+    quirks: Quirks {
+        vf_reset: false,
+        memory_increment: true,
+        shift_vy: true,
+        jump_vx: false,
+        clip_sprites: false,
+        display_wait: false,
+    },
+    blocks: &[
+        Block { start: 0x200, end: 0x202, cycles: 1, run: block_200 },
+        Block { start: 0x202, end: 0x206, cycles: 2, run: block_202 },
+    ],
+};
+
+/// Run program on terminal. The first argument is count of instructions per frame.
+fn main() {
+    let cycles_per_frame = std::env::args().nth(1).and_then(|arg| arg.parse().ok()).unwrap_or(15);
+    let mut machine = Machine::new(PROGRAM.rom, PROGRAM.quirks);
+    let mut runtime = Runtime::new(&PROGRAM);
+    let mut device = Device::new().expect("Failed to initialize terminal");
+
+    let pacing = Pacing::Frames{ cycles_per_frame };
+    let exit = frontend::run_with(&mut machine, &mut device, pacing, |_| Ok(()), |machine, budget| runtime.step(machine, budget));
+    drop(device);
+    match exit {
+        Ok(Exit::Quit) => (),
+        Ok(Exit::Fault(fault)) => println!("{}", fault),
+        Err(err) => println!("{}", err),
+    }
+}
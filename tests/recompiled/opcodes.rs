@@ -0,0 +1,274 @@
+//! Recompiled from opcodes.ch8 by `chipmunk recompile`. Build it against the chipmunk library.
+
+use chipmunk::engine::device::Device;
+use chipmunk::engine::frontend::{self, Exit, Pacing};
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::recompile::{Block, Program, Runtime, State};
+
+const ROM: [u8; 284] = [
+    0x00, 0xE0, 0x6A, 0x00, 0x6B, 0x00, 0x60, 0x05, 0x61, 0x03, 0x80, 0x14, 0x8C, 0xF0, 0x22, 0xE8,
+    0x80, 0xC0, 0x23, 0x06, 0x6A, 0x10, 0x6B, 0x00, 0x60, 0xFF, 0x61, 0x02, 0x80, 0x14, 0x8C, 0xF0,
+    0x22, 0xE8, 0x80, 0xC0, 0x23, 0x06, 0x6A, 0x20, 0x6B, 0x00, 0x60, 0x05, 0x61, 0x03, 0x80, 0x15,
+    0x8C, 0xF0, 0x22, 0xE8, 0x80, 0xC0, 0x23, 0x06, 0x6A, 0x30, 0x6B, 0x00, 0x60, 0x03, 0x61, 0x05,
+    0x80, 0x15, 0x8C, 0xF0, 0x22, 0xE8, 0x80, 0xC0, 0x23, 0x06, 0x6A, 0x00, 0x6B, 0x06, 0x60, 0x03,
+    0x61, 0x05, 0x80, 0x17, 0x8C, 0xF0, 0x22, 0xE8, 0x80, 0xC0, 0x23, 0x06, 0x6A, 0x10, 0x6B, 0x06,
+    0x6F, 0x05, 0x60, 0xF0, 0x61, 0x0F, 0x80, 0x11, 0x8C, 0xF0, 0x22, 0xE8, 0x80, 0xC0, 0x23, 0x06,
+    0x6A, 0x20, 0x6B, 0x06, 0x60, 0x01, 0x61, 0x06, 0x80, 0x16, 0x8C, 0xF0, 0x22, 0xE8, 0x80, 0xC0,
+    0x23, 0x06, 0x6A, 0x30, 0x6B, 0x06, 0x60, 0x81, 0x61, 0x01, 0x80, 0x1E, 0x8C, 0xF0, 0x22, 0xE8,
+    0x80, 0xC0, 0x23, 0x06, 0x6A, 0x00, 0x6B, 0x0C, 0xA3, 0x14, 0x60, 0xAB, 0x61, 0xCD, 0xF1, 0x55,
+    0x60, 0x00, 0xF0, 0x65, 0x8C, 0xF0, 0x22, 0xE8, 0x80, 0xC0, 0x23, 0x06, 0x6A, 0x10, 0x6B, 0x0C,
+    0x60, 0x00, 0x62, 0x02, 0xB2, 0xB6, 0x60, 0x11, 0x12, 0xBC, 0x60, 0x22, 0x8C, 0xF0, 0x22, 0xE8,
+    0x80, 0xC0, 0x23, 0x06, 0x6A, 0x20, 0x6B, 0x0C, 0xA3, 0x18, 0x60, 0x9C, 0xF0, 0x33, 0xF2, 0x65,
+    0x80, 0x10, 0x80, 0x24, 0x8F, 0x20, 0x8C, 0xF0, 0x22, 0xE8, 0x80, 0xC0, 0x23, 0x06, 0xA3, 0x12,
+    0x60, 0x3C, 0x61, 0x1E, 0xD0, 0x11, 0x12, 0xE6, 0x83, 0x00, 0x83, 0x36, 0x83, 0x36, 0x83, 0x36,
+    0x83, 0x36, 0xF3, 0x29, 0xDA, 0xB5, 0x7A, 0x05, 0x83, 0x00, 0x64, 0x0F, 0x83, 0x42, 0xF3, 0x29,
+    0xDA, 0xB5, 0x7A, 0x05, 0x00, 0xEE, 0x64, 0x0F, 0x80, 0x42, 0xF0, 0x29, 0xDA, 0xB5, 0x7A, 0x06,
+    0x00, 0xEE, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+fn block_202(s: &mut State) {
+    s.v[0xA] = 0x00; // 0x202 6A00
+    s.v[0xB] = 0x00; // 0x204 6B00
+    s.v[0x0] = 0x05; // 0x206 6005
+    s.v[0x1] = 0x03; // 0x208 6103
+    let (val, carry) = s.v[0x0].overflowing_add(s.v[0x1]); s.v[0x0] = val; s.v[0xF] = carry as u8; // 0x20A 8014
+    s.v[0xC] = s.v[0xF]; // 0x20C 8CF0
+    s.pc = 0x20E;
+}
+
+fn block_210(s: &mut State) {
+    s.v[0x0] = s.v[0xC]; // 0x210 80C0
+    s.pc = 0x212;
+}
+
+fn block_214(s: &mut State) {
+    s.v[0xA] = 0x10; // 0x214 6A10
+    s.v[0xB] = 0x00; // 0x216 6B00
+    s.v[0x0] = 0xFF; // 0x218 60FF
+    s.v[0x1] = 0x02; // 0x21A 6102
+    let (val, carry) = s.v[0x0].overflowing_add(s.v[0x1]); s.v[0x0] = val; s.v[0xF] = carry as u8; // 0x21C 8014
+    s.v[0xC] = s.v[0xF]; // 0x21E 8CF0
+    s.pc = 0x220;
+}
+
+fn block_222(s: &mut State) {
+    s.v[0x0] = s.v[0xC]; // 0x222 80C0
+    s.pc = 0x224;
+}
+
+fn block_226(s: &mut State) {
+    s.v[0xA] = 0x20; // 0x226 6A20
+    s.v[0xB] = 0x00; // 0x228 6B00
+    s.v[0x0] = 0x05; // 0x22A 6005
+    s.v[0x1] = 0x03; // 0x22C 6103
+    let (val, borrow) = s.v[0x0].overflowing_sub(s.v[0x1]); s.v[0x0] = val; s.v[0xF] = !borrow as u8; // 0x22E 8015
+    s.v[0xC] = s.v[0xF]; // 0x230 8CF0
+    s.pc = 0x232;
+}
+
+fn block_234(s: &mut State) {
+    s.v[0x0] = s.v[0xC]; // 0x234 80C0
+    s.pc = 0x236;
+}
+
+fn block_238(s: &mut State) {
+    s.v[0xA] = 0x30; // 0x238 6A30
+    s.v[0xB] = 0x00; // 0x23A 6B00
+    s.v[0x0] = 0x03; // 0x23C 6003
+    s.v[0x1] = 0x05; // 0x23E 6105
+    let (val, borrow) = s.v[0x0].overflowing_sub(s.v[0x1]); s.v[0x0] = val; s.v[0xF] = !borrow as u8; // 0x240 8015
+    s.v[0xC] = s.v[0xF]; // 0x242 8CF0
+    s.pc = 0x244;
+}
+
+fn block_246(s: &mut State) {
+    s.v[0x0] = s.v[0xC]; // 0x246 80C0
+    s.pc = 0x248;
+}
+
+fn block_24a(s: &mut State) {
+    s.v[0xA] = 0x00; // 0x24A 6A00
+    s.v[0xB] = 0x06; // 0x24C 6B06
+    s.v[0x0] = 0x03; // 0x24E 6003
+    s.v[0x1] = 0x05; // 0x250 6105
+    let (val, borrow) = s.v[0x1].overflowing_sub(s.v[0x0]); s.v[0x0] = val; s.v[0xF] = !borrow as u8; // 0x252 8017
+    s.v[0xC] = s.v[0xF]; // 0x254 8CF0
+    s.pc = 0x256;
+}
+
+fn block_258(s: &mut State) {
+    s.v[0x0] = s.v[0xC]; // 0x258 80C0
+    s.pc = 0x25A;
+}
+
+fn block_25c(s: &mut State) {
+    s.v[0xA] = 0x10; // 0x25C 6A10
+    s.v[0xB] = 0x06; // 0x25E 6B06
+    s.v[0xF] = 0x05; // 0x260 6F05
+    s.v[0x0] = 0xF0; // 0x262 60F0
+    s.v[0x1] = 0x0F; // 0x264 610F
+    s.v[0x0] |= s.v[0x1]; // 0x266 8011
+    s.v[0xC] = s.v[0xF]; // 0x268 8CF0
+    s.pc = 0x26A;
+}
+
+fn block_26c(s: &mut State) {
+    s.v[0x0] = s.v[0xC]; // 0x26C 80C0
+    s.pc = 0x26E;
+}
+
+fn block_270(s: &mut State) {
+    s.v[0xA] = 0x20; // 0x270 6A20
+    s.v[0xB] = 0x06; // 0x272 6B06
+    s.v[0x0] = 0x01; // 0x274 6001
+    s.v[0x1] = 0x06; // 0x276 6106
+    let src = s.v[0x1]; s.v[0xF] = src & 1; s.v[0x0] = src >> 1; // 0x278 8016
+    s.v[0xC] = s.v[0xF]; // 0x27A 8CF0
+    s.pc = 0x27C;
+}
+
+fn block_27e(s: &mut State) {
+    s.v[0x0] = s.v[0xC]; // 0x27E 80C0
+    s.pc = 0x280;
+}
+
+fn block_282(s: &mut State) {
+    s.v[0xA] = 0x30; // 0x282 6A30
+    s.v[0xB] = 0x06; // 0x284 6B06
+    s.v[0x0] = 0x81; // 0x286 6081
+    s.v[0x1] = 0x01; // 0x288 6101
+    let src = s.v[0x1]; s.v[0xF] = src >> 7; s.v[0x0] = src << 1; // 0x28A 801E
+    s.v[0xC] = s.v[0xF]; // 0x28C 8CF0
+    s.pc = 0x28E;
+}
+
+fn block_290(s: &mut State) {
+    s.v[0x0] = s.v[0xC]; // 0x290 80C0
+    s.pc = 0x292;
+}
+
+fn block_294(s: &mut State) {
+    s.v[0xA] = 0x00; // 0x294 6A00
+    s.v[0xB] = 0x0C; // 0x296 6B0C
+    s.l = 0x314; // 0x298 A314
+    s.v[0x0] = 0xAB; // 0x29A 60AB
+    s.v[0x1] = 0xCD; // 0x29C 61CD
+    s.pc = 0x29E;
+}
+
+fn block_2a0(s: &mut State) {
+    s.v[0x0] = 0x00; // 0x2A0 6000
+    s.pc = 0x2A2;
+}
+
+fn block_2a4(s: &mut State) {
+    s.v[0xC] = s.v[0xF]; // 0x2A4 8CF0
+    s.pc = 0x2A6;
+}
+
+fn block_2a8(s: &mut State) {
+    s.v[0x0] = s.v[0xC]; // 0x2A8 80C0
+    s.pc = 0x2AA;
+}
+
+fn block_2ac(s: &mut State) {
+    s.v[0xA] = 0x10; // 0x2AC 6A10
+    s.v[0xB] = 0x0C; // 0x2AE 6B0C
+    s.v[0x0] = 0x00; // 0x2B0 6000
+    s.v[0x2] = 0x02; // 0x2B2 6202
+    s.pc = s.v[0x0] as u16 + 0x2B6; // 0x2B4 B2B6
+}
+
+fn block_2e8(s: &mut State) {
+    s.v[0x3] = s.v[0x0]; // 0x2E8 8300
+    let src = s.v[0x3]; s.v[0xF] = src & 1; s.v[0x3] = src >> 1; // 0x2EA 8336
+    let src = s.v[0x3]; s.v[0xF] = src & 1; s.v[0x3] = src >> 1; // 0x2EC 8336
+    let src = s.v[0x3]; s.v[0xF] = src & 1; s.v[0x3] = src >> 1; // 0x2EE 8336
+    let src = s.v[0x3]; s.v[0xF] = src & 1; s.v[0x3] = src >> 1; // 0x2F0 8336
+    s.l = s.v[0x3] as u16 * 5; // 0x2F2 F329
+    s.pc = 0x2F4;
+}
+
+fn block_2f6(s: &mut State) {
+    s.v[0xA] = s.v[0xA].wrapping_add(0x05); // 0x2F6 7A05
+    s.v[0x3] = s.v[0x0]; // 0x2F8 8300
+    s.v[0x4] = 0x0F; // 0x2FA 640F
+    s.v[0x3] &= s.v[0x4]; // 0x2FC 8342
+    s.l = s.v[0x3] as u16 * 5; // 0x2FE F329
+    s.pc = 0x300;
+}
+
+fn block_302(s: &mut State) {
+    s.v[0xA] = s.v[0xA].wrapping_add(0x05); // 0x302 7A05
+    s.pc = 0x304;
+}
+
+fn block_306(s: &mut State) {
+    s.v[0x4] = 0x0F; // 0x306 640F
+    s.v[0x0] &= s.v[0x4]; // 0x308 8042
+    s.l = s.v[0x0] as u16 * 5; // 0x30A F029
+    s.pc = 0x30C;
+}
+
+fn block_30e(s: &mut State) {
+    s.v[0xA] = s.v[0xA].wrapping_add(0x06); // 0x30E 7A06
+    s.pc = 0x310;
+}
+
+pub static PROGRAM: Program = Program {
+    rom: &ROM,
+    quirks: Quirks {
+        vf_reset: false,
+        memory_increment: true,
+        shift_vy: true,
+        jump_vx: false,
+        clip_sprites: false,
+        display_wait: false,
+    },
+    blocks: &[
+        Block { start: 0x202, end: 0x20E, cycles: 6, run: block_202 },
+        Block { start: 0x210, end: 0x212, cycles: 1, run: block_210 },
+        Block { start: 0x214, end: 0x220, cycles: 6, run: block_214 },
+        Block { start: 0x222, end: 0x224, cycles: 1, run: block_222 },
+        Block { start: 0x226, end: 0x232, cycles: 6, run: block_226 },
+        Block { start: 0x234, end: 0x236, cycles: 1, run: block_234 },
+        Block { start: 0x238, end: 0x244, cycles: 6, run: block_238 },
+        Block { start: 0x246, end: 0x248, cycles: 1, run: block_246 },
+        Block { start: 0x24A, end: 0x256, cycles: 6, run: block_24a },
+        Block { start: 0x258, end: 0x25A, cycles: 1, run: block_258 },
+        Block { start: 0x25C, end: 0x26A, cycles: 7, run: block_25c },
+        Block { start: 0x26C, end: 0x26E, cycles: 1, run: block_26c },
+        Block { start: 0x270, end: 0x27C, cycles: 6, run: block_270 },
+        Block { start: 0x27E, end: 0x280, cycles: 1, run: block_27e },
+        Block { start: 0x282, end: 0x28E, cycles: 6, run: block_282 },
+        Block { start: 0x290, end: 0x292, cycles: 1, run: block_290 },
+        Block { start: 0x294, end: 0x29E, cycles: 5, run: block_294 },
+        Block { start: 0x2A0, end: 0x2A2, cycles: 1, run: block_2a0 },
+        Block { start: 0x2A4, end: 0x2A6, cycles: 1, run: block_2a4 },
+        Block { start: 0x2A8, end: 0x2AA, cycles: 1, run: block_2a8 },
+        Block { start: 0x2AC, end: 0x2B6, cycles: 5, run: block_2ac },
+        Block { start: 0x2E8, end: 0x2F4, cycles: 6, run: block_2e8 },
+        Block { start: 0x2F6, end: 0x300, cycles: 5, run: block_2f6 },
+        Block { start: 0x302, end: 0x304, cycles: 1, run: block_302 },
+        Block { start: 0x306, end: 0x30C, cycles: 3, run: block_306 },
+        Block { start: 0x30E, end: 0x310, cycles: 1, run: block_30e },
+    ],
+};
+
+/// Run program on terminal. The first argument is count of instructions per frame.
+fn main() {
+    let cycles_per_frame = std::env::args().nth(1).and_then(|arg| arg.parse().ok()).unwrap_or(15);
+    let mut machine = Machine::new(PROGRAM.rom, PROGRAM.quirks);
+    let mut runtime = Runtime::new(&PROGRAM);
+    let mut device = Device::new().expect("Failed to initialize terminal");
+
+    let pacing = Pacing::Frames{ cycles_per_frame };
+    let exit = frontend::run_with(&mut machine, &mut device, pacing, |_| Ok(()), |machine, budget| runtime.step(machine, budget));
+    drop(device);
+    match exit {
+        Ok(Exit::Quit) => (),
+        Ok(Exit::Fault(fault)) => println!("{}", fault),
+        Err(err) => println!("{}", err),
+    }
+}
@@ -0,0 +1,69 @@
+//! Recompiled from self_modifying.ch8 by `chipmunk recompile`. Build it against the chipmunk library.
+
+use chipmunk::engine::device::Device;
+use chipmunk::engine::frontend::{self, Exit, Pacing};
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::recompile::{Block, Program, Runtime, State};
+
+const ROM: [u8; 20] = [
+    0x62, 0x00, 0x60, 0x72, 0x61, 0x10, 0xA2, 0x0A, 0x63, 0x00, 0x72, 0x01, 0x32, 0x02, 0x12, 0x08,
+    0xF1, 0x55, 0x12, 0x08,
+];
+
+fn block_200(s: &mut State) {
+    s.v[0x2] = 0x00; // 0x200 6200
+    s.v[0x0] = 0x72; // 0x202 6072
+    s.v[0x1] = 0x10; // 0x204 6110
+    s.l = 0x20A; // 0x206 A20A
+    s.pc = 0x208;
+}
+
+fn block_208(s: &mut State) {
+    s.v[0x3] = 0x00; // 0x208 6300
+    s.v[0x2] = s.v[0x2].wrapping_add(0x01); // 0x20A 7201
+    s.pc = if s.v[0x2] == 0x02 { 0x210 } else { 0x20E }; // 0x20C 3202
+}
+
+fn block_20e(s: &mut State) {
+    s.pc = 0x208; // 0x20E 1208
+}
+
+fn block_212(s: &mut State) {
+    s.pc = 0x208; // 0x212 1208
+}
+
+pub static PROGRAM: Program = Program {
+    rom: &ROM,
+    quirks: Quirks {
+        vf_reset: false,
+        memory_increment: true,
+        shift_vy: true,
+        jump_vx: false,
+        clip_sprites: false,
+        display_wait: false,
+    },
+    blocks: &[
+        Block { start: 0x200, end: 0x208, cycles: 4, run: block_200 },
+        Block { start: 0x208, end: 0x20E, cycles: 3, run: block_208 },
+        Block { start: 0x20E, end: 0x210, cycles: 1, run: block_20e },
+        Block { start: 0x212, end: 0x214, cycles: 1, run: block_212 },
+    ],
+};
+
+/// Run program on terminal. The first argument is count of instructions per frame.
+fn main() {
+    let cycles_per_frame = std::env::args().nth(1).and_then(|arg| arg.parse().ok()).unwrap_or(15);
+    let mut machine = Machine::new(PROGRAM.rom, PROGRAM.quirks);
+    let mut runtime = Runtime::new(&PROGRAM);
+    let mut device = Device::new().expect("Failed to initialize terminal");
+
+    let pacing = Pacing::Frames{ cycles_per_frame };
+    let exit = frontend::run_with(&mut machine, &mut device, pacing, |_| Ok(()), |machine, budget| runtime.step(machine, budget));
+    drop(device);
+    match exit {
+        Ok(Exit::Quit) => (),
+        Ok(Exit::Fault(fault)) => println!("{}", fault),
+        Err(err) => println!("{}", err),
+    }
+}
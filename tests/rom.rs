@@ -0,0 +1,64 @@
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::rom::{Rom, V0, V1, V2, VF};
+
+#[test]
+fn labels_are_resolved() {
+    let rom = Rom::new()
+        .ld(V0, 5)
+        .jp("main")
+        .label("loop")
+        .ld_i("ball")
+        .drw(V0, V1, 5)
+        .jp("loop")
+        .label("main")
+        .call(0x300)
+        .sprite("ball", &[0x20, 0x70, 0xF8, 0x70, 0x20]);
+    assert_eq!((rom.addr("loop"), rom.addr("main"), rom.addr("ball")), (Some(0x204), Some(0x20A), Some(0x20C)));
+    assert_eq!(rom.build().unwrap(), [
+        0x60, 0x05, 0x12, 0x0A, 0xA2, 0x0C, 0xD0, 0x15, 0x12, 0x04, 0x23, 0x00,
+        0x20, 0x70, 0xF8, 0x70, 0x20,
+    ]);
+}
+
+#[test]
+fn label_errors_are_reported() {
+    assert_eq!(Rom::new().jp("nowhere").build(), Err("Unknown label nowhere".to_string()));
+    assert_eq!(Rom::new().label("a").cls().label("a").build(), Err("Label a is defined more than once".to_string()));
+}
+
+#[test]
+fn addresses_out_of_range_are_reported() {
+    assert_eq!(Rom::new().jp(0x1234).build(), Err("Address 0x1234 is out of range".to_string()));
+    assert_eq!(Rom::new().jp(0xFFF).build(), Ok(vec![0x1F, 0xFF]));
+
+    let rom = Rom::new().jp("far").bytes(&[0; 0xE00 - 2]).label("far");
+    assert_eq!(rom.addr("far"), Some(0x1000));
+    assert_eq!(rom.build(), Err("Label far at 0x1000 is out of range".to_string()));
+}
+
+#[test]
+fn opcode_behavior_is_tested_in_few_lines() {
+    let mut machine = Rom::new().ld(V0, 0xF0).ld(V1, 0x20).add(V0, V1).label("end").jp("end").machine(Quirks::default()).unwrap();
+    for _ in 0..3 {
+        machine.step().unwrap();
+    }
+    assert_eq!((machine.registers().general_register(0), machine.registers().general_register(0xF)), (0x10, 1));
+
+    let mut machine = Rom::new()
+        .ld(V2, 123)
+        .ld_i("digits")
+        .ld_b(V2)
+        .load(V2)
+        .se(V2, 3)
+        .ld(VF, 0xFF)
+        .label("digits")
+        .bytes(&[0, 0, 0])
+        .machine(Quirks::default())
+        .unwrap();
+    for _ in 0..6 {
+        machine.step().unwrap();
+    }
+    let registers = machine.registers();
+    assert_eq!((registers.general_register(0), registers.general_register(1), registers.general_register(2)), (1, 2, 3));
+    assert_eq!(registers.general_register(0xF), 0);
+}
@@ -0,0 +1,58 @@
+use chipmunk::engine::machine::{Machine, Fault};
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::sanitizer::{Sanitizer, Issue};
+
+fn run(rom: &[u8], stack_limit: usize, cycles: usize) -> (Sanitizer, Option<Fault>) {
+    let mut machine = Machine::new(rom, Quirks::default());
+    let mut sanitizer = Sanitizer::new(rom.len(), stack_limit);
+    for _ in 0..cycles {
+        sanitizer.observe(&machine);
+        if let Err(fault) = machine.step() {
+            return (sanitizer, Some(fault));
+        }
+    }
+    (sanitizer, None)
+}
+
+#[test]
+fn each_issue_is_reported_with_pc() {
+    let rom = [
+        0x6F, 0x05,             // 0x200 LD VF, 5
+        0x80, 0x14,             // 0x202 ADD V0, V1 clobbers VF
+        0xA1, 0x00,             // 0x204 LD I, 0x100
+        0xF0, 0x55,             // 0x206 LD [I], V0 writes interpreter area
+        0xA3, 0x00,             // 0x208 LD I, 0x300
+        0xD0, 0x01,             // 0x20A DRW reads never initialized memory
+        0xA2, 0x12,             // 0x20C LD I, 0x212
+        0xD0, 0x11,             // 0x20E DRW reads 0x212 as sprite
+        0x12, 0x12,             // 0x210 JP 0x212 executes sprite data
+        0x12, 0x15,             // 0x212 JP 0x215 which is odd
+        0x00, 0xAF, 0xFF,       // 0x215 LD I, 0xFFF
+        0xF1, 0x65, 0x00,       // 0x217 LD V1, [I] goes past the end
+    ];
+    let (sanitizer, fault) = run(&rom, 16, 20);
+
+    let issues: Vec<(Issue, u16)> = sanitizer.reports().iter().map(|report| (report.issue, report.pc)).collect();
+    assert_eq!(issues, vec![
+        (Issue::VfClobbered, 0x202),
+        (Issue::WriteReserved, 0x206),
+        (Issue::UninitializedRead, 0x20A),
+        (Issue::ExecuteData, 0x212),
+        (Issue::ExecuteOdd, 0x215),
+        (Issue::ExecuteOdd, 0x217),
+        (Issue::IndexOverflow, 0x217),
+        (Issue::UninitializedRead, 0x217),
+    ]);
+    assert_eq!(fault, Some(Fault::MemoryOutOfRange{ pc: 0x217, l: 0xFFF }));
+}
+
+#[test]
+fn stack_overflow_has_backtrace() {
+    // 0x200 CALL 0x202, 0x202 CALL 0x204, ... 0x20A CALL 0x20C
+    let rom: Vec<u8> = (0..6u8).flat_map(|i| vec![0x22, 0x02 + i * 2]).collect();
+    let (sanitizer, _) = run(&rom, 4, 6);
+
+    let report = &sanitizer.reports()[0];
+    assert_eq!((report.issue, report.pc), (Issue::StackOverflow, 0x208));
+    assert_eq!(report.backtrace, vec![0x206, 0x204, 0x202, 0x200]);
+}
@@ -0,0 +1,75 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use chipmunk::engine::screen::{Screen, MAX_WIDTH, MAX_HEIGHT};
+
+/// Draw sprite pixel by pixel into `pixels`, and return dirty rows and whether any pixel is erased.
+fn draw_reference(pixels: &mut [bool], (width, height): (usize, usize), (x, y): (u8, u8), bytes: &[u8], clip: bool) -> (u64, bool) {
+    let (origx, mut y) = (x as usize % width, y as usize % height);
+    let (mut dirty, mut is_any_erased) = (0, false);
+    for byte in bytes {
+        let mut x = origx;
+        for i in (0..8).rev() {
+            if byte & (1 << i) != 0 {
+                let pixel = &mut pixels[y * width + x];
+                is_any_erased |= *pixel;
+                *pixel = !*pixel;
+                dirty |= 1 << y;
+            }
+            x = (x + 1) % width;
+            if clip && x == 0 { break; }
+        }
+        y = (y + 1) % height;
+        if clip && y == 0 { break; }
+    }
+    (dirty, is_any_erased)
+}
+
+#[test]
+fn packed_rows_match_pixel_by_pixel_drawing() {
+    let mut rng = StdRng::seed_from_u64(37);
+    for &(width, height) in [(64, 32), (100, 50), (MAX_WIDTH, MAX_HEIGHT)].iter() {
+        for &clip in [false, true].iter() {
+            let mut screen = Screen::with_resolution(width, height);
+            let mut pixels = vec![false; width * height];
+            for _ in 0..300 {
+                let pos = (rng.gen(), rng.gen());
+                let sprite: Vec<u8> = (0..rng.gen_range(1, 16)).map(|_| rng.gen()).collect();
+                let expected = draw_reference(&mut pixels, (width, height), pos, &sprite, clip);
+                assert_eq!(screen.draw(pos, &sprite, clip), expected);
+            }
+            for y in 0..height {
+                for x in 0..width {
+                    assert_eq!(screen.is_drawn((x as u8, y as u8)), pixels[y * width + x]);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn sprite_row_wraps_around_right_edge() {
+    let mut screen = Screen::new();
+    let (dirty, is_any_erased) = screen.draw((60, 31), &[0xFF, 0x81], false);
+    assert_eq!((dirty, is_any_erased), (1 << 31 | 1, false));
+    assert_eq!(screen.row(31), 0xF << 124 | 0xF << 64);
+    assert_eq!(screen.row(0), 1 << 124 | 1 << 67);
+
+    let (dirty, is_any_erased) = screen.draw((60, 31), &[0x80], true);
+    assert_eq!((dirty, is_any_erased), (1 << 31, true));
+}
+
+#[test]
+fn positions_outside_screen_are_not_drawn() {
+    let mut screen = Screen::with_resolution(MAX_WIDTH, MAX_HEIGHT);
+    screen.draw((120, 63), &[0xFF], false);
+    assert!(screen.is_drawn((127, 63)));
+    for &pos in &[(128, 0), (255, 63), (0, 64), (255, 255)] {
+        assert!(!screen.is_drawn(pos));
+    }
+
+    let mut screen = Screen::new();
+    screen.draw((0, 0), &[0xFF], false);
+    assert!(screen.is_drawn((0, 0)));
+    assert!(!screen.is_drawn((64, 0)));
+    assert!(!screen.is_drawn((0, 32)));
+}
@@ -0,0 +1,92 @@
+use chipmunk::engine::frontend::{self, Headless, Pacing, Exit};
+use chipmunk::engine::replay::Replay;
+use chipmunk::engine::search::{self, SearchOptions, Strategy, Target};
+
+/// Combination lock which stores 0x2A into 0x300 after keys 3, 7 and 1 are pressed in order.
+const LOCK: [u8; 26] = [
+    0xF1, 0x0A,             // 0x200 LD V1, K
+    0x31, 0x03,             // 0x202 SE V1, 3
+    0x12, 0x00,             // 0x204 JP 0x200
+    0xF1, 0x0A,             // 0x206 LD V1, K
+    0x31, 0x07,             // 0x208 SE V1, 7
+    0x12, 0x00,             // 0x20A JP 0x200
+    0xF1, 0x0A,             // 0x20C LD V1, K
+    0x31, 0x01,             // 0x20E SE V1, 1
+    0x12, 0x00,             // 0x210 JP 0x200
+    0xA3, 0x00,             // 0x212 LD I, 0x300
+    0x60, 0x2A,             // 0x214 LD V0, 0x2A
+    0xF0, 0x55,             // 0x216 LD [I], V0
+    0x12, 0x18,             // 0x218 JP 0x218
+];
+
+/// Counter which stores V0 into 0x300 every frame, and increases V0 while key 5 is held.
+const COUNTER: [u8; 24] = [
+    0x61, 0x05,             // 0x200 LD V1, 5
+    0xF2, 0x07,             // 0x202 LD V2, DT
+    0x32, 0x00,             // 0x204 SE V2, 0
+    0x12, 0x02,             // 0x206 JP 0x202
+    0x62, 0x01,             // 0x208 LD V2, 1
+    0xF2, 0x15,             // 0x20A LD DT, V2
+    0xE1, 0xA1,             // 0x20C SKNP V1
+    0x70, 0x01,             // 0x20E ADD V0, 1
+    0xA3, 0x00,             // 0x210 LD I, 0x300
+    0xF0, 0x55,             // 0x212 LD [I], V0
+    0x12, 0x02,             // 0x214 JP 0x202
+    0x00, 0x00,
+];
+
+#[test]
+fn breadth_first_search_finds_shortest_input() {
+    let result = search::search(&LOCK, &SearchOptions::new(Target::Pc(0x218)));
+    let replay = result.replay.unwrap();
+    // Lock waits from the first frame, so a key pressed in it is never seen.
+    assert_eq!(replay.keys, [None, Some(3), Some(7), Some(1)]);
+    // Start, waits for each key, and 15 wrong keys kept in V1. Other inputs make the same states.
+    assert_eq!(result.states, 19);
+
+    let target = Target::Memory{ addr: 0x300, value: 0x2A };
+    let mut machine = replay.machine(&LOCK);
+    assert_eq!(replay.play(&mut machine, |machine| target.is_reached(machine)), Ok(true));
+}
+
+#[test]
+fn replay_file_is_played_by_headless_front_end() {
+    let target = Target::Memory{ addr: 0x300, value: 0x2A };
+    let replay = search::search(&LOCK, &SearchOptions::new(target)).replay.unwrap();
+
+    let mut text = Vec::new();
+    replay.write(&mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert_eq!(text, "quirks chipmunk\nseed 0\ncycles 15\nkeys - 3 7 1\n");
+    let replay = Replay::parse(&text).unwrap();
+
+    let mut machine = replay.machine(&LOCK);
+    let mut headless = Headless::with_frame_limit(replay.keys.len() as u64);
+    replay.schedule(&mut headless);
+    let exit = frontend::run(&mut machine, &mut headless, Pacing::Fixed{ cycles_per_frame: replay.cycles }, |_| Ok(()));
+    assert_eq!(exit.unwrap(), Exit::Quit);
+    assert!(target.is_reached(&machine));
+
+    assert_eq!(Replay::parse("keys - G").unwrap_err(), "line 1 : invalid value G");
+}
+
+#[test]
+fn beam_search_keeps_states_nearest_to_target() {
+    let mut options = SearchOptions::new(Target::Memory{ addr: 0x300, value: 20 });
+    options.strategy = Strategy::Beam{ width: 1 };
+    let result = search::search(&COUNTER, &options);
+    assert_eq!(result.replay.unwrap().keys, vec![Some(5); 20]);
+    assert_eq!(result.frames, 20);
+
+    let mut options = SearchOptions::new(Target::Memory{ addr: 0x300, value: 20 });
+    options.max_frames = 10;
+    let result = search::search(&COUNTER, &options);
+    assert!(result.replay.is_none());
+    // V0 and PC at the end of frame depend on whether key 5 is held, so new states are made every frame.
+    assert_eq!((result.frames, result.states), (10, 31));
+
+    options.max_states = 5;
+    let result = search::search(&COUNTER, &options);
+    assert!(result.replay.is_none());
+    assert_eq!(result.states, 5);
+}
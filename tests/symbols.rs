@@ -0,0 +1,37 @@
+use chipmunk::engine::symbols::SymbolTable;
+
+#[test]
+fn native_and_octo_symbols_are_parsed() {
+    let symbols = SymbolTable::parse("label 0x2A4 draw_player\nline 0x2A6 game.8o 120  # comment\n").unwrap();
+    assert_eq!(symbols.label(0x2A4), Some("draw_player"));
+    assert_eq!(symbols.line(0x2A6), Some(("game.8o", 120)));
+
+    let symbols = SymbolTable::parse(":const main 512\n:const draw_player 0x2A4\n:const SPEED 3\n:alias px v1\n").unwrap();
+    assert_eq!(symbols.labels().collect::<Vec<_>>(), [(0x200, "main"), (0x2A4, "draw_player")]);
+
+    // Numeric constants in program memory do not shadow labels.
+    let symbols = SymbolTable::parse(":const SCORE_MAX 1000\n:const score_label 0x3E8\nlabel 0x2A4 draw_player\n:const SPEED 0x2A4\n").unwrap();
+    assert_eq!(symbols.labels().collect::<Vec<_>>(), [(0x2A4, "draw_player"), (0x3E8, "score_label")]);
+    let symbols = SymbolTable::parse(":const main 0X200\n:const START 0x200\n").unwrap();
+    assert_eq!(symbols.labels().collect::<Vec<_>>(), [(0x200, "main")]);
+
+    assert_eq!(SymbolTable::parse(":const main x").unwrap_err(), "line 1 : invalid value x");
+    assert_eq!(SymbolTable::parse("sym main 0x200").unwrap_err(), "line 1 : unknown entry sym main 0x200");
+}
+
+#[test]
+fn addresses_are_described_and_resolved() {
+    let symbols = SymbolTable::parse("label 0x200 main\nlabel 0x2A4 draw_player\nline 0x2A6 game.8o 120\n").unwrap();
+    assert_eq!(symbols.locate(0x2A4).as_deref(), Some("draw_player"));
+    assert_eq!(symbols.locate(0x2A6).as_deref(), Some("draw_player+2"));
+    assert_eq!(symbols.describe(0x2A6), "0x2A6 draw_player+2 (game.8o:120)");
+    assert_eq!(SymbolTable::new().describe(0x2A6), "0x2A6");
+
+    assert_eq!(symbols.resolve("draw_player"), Ok(0x2A4));
+    assert_eq!(symbols.resolve("draw_player+4"), Ok(0x2A8));
+    assert_eq!(symbols.resolve("0x300"), Ok(0x300));
+    assert_eq!(symbols.resolve("0X300"), Ok(0x300));
+    assert_eq!(symbols.resolve("draw_player+0X4"), Ok(0x2A8));
+    assert_eq!(symbols.resolve("768"), Ok(0x300));
+    assert_eq!(symbols.resolve("nothing"), Err("Unknown symbol nothing".to_string()));
+}
@@ -0,0 +1,100 @@
+use std::{fs, io, path::Path};
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use chipmunk::engine::machine::Machine;
+use chipmunk::engine::quirks::Quirks;
+use chipmunk::engine::trace::{self, TraceWriter, TraceFormat, TraceFilter, Alignment, Divergence};
+
+/// Writer which can be inspected after being moved into trace writer.
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.borrow_mut().write(buf) }
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+fn record_trace(format: TraceFormat, filter: TraceFilter, quirks: Quirks) -> Vec<u8> {
+    let rom = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/roms/opcodes.ch8")).unwrap();
+    let buffer = SharedBuffer::default();
+    let mut tracer = TraceWriter::new(Box::new(buffer.clone()), format, filter).unwrap();
+    let mut machine = Machine::new(&rom, quirks);
+
+    for _ in 0..30 {
+        for _ in 0..15 {
+            tracer.trace(&machine).unwrap();
+            machine.step().unwrap();
+        }
+        machine.tick_timers();
+    }
+    let result = buffer.0.borrow().clone();
+    result
+}
+
+#[test]
+fn binary_and_text_traces_have_same_records() {
+    let binary = record_trace(TraceFormat::Binary, TraceFilter::default(), Quirks::default());
+    let text = record_trace(TraceFormat::Text, TraceFilter::default(), Quirks::default());
+
+    let from_binary = trace::read_trace(&binary[..]).unwrap();
+    let from_text = trace::read_trace(&text[..]).unwrap();
+    assert_eq!(from_binary.len(), 450);
+    assert_eq!(from_binary, from_text);
+    assert!(String::from_utf8(text).unwrap().starts_with("0000000000 00000000 0200 00E0"));
+}
+
+#[test]
+fn filter_restricts_addresses_and_frames() {
+    let filter = TraceFilter { addr: Some((0x300, 0x31F)), frames: Some((2, 4)) };
+    let records = trace::read_trace(&record_trace(TraceFormat::Binary, filter, Quirks::default())[..]).unwrap();
+
+    assert!(!records.is_empty());
+    assert!(records.iter().all(|r| (0x300..=0x31F).contains(&r.pc) && (2..=4).contains(&r.frame)));
+}
+
+#[test]
+fn diff_reports_first_divergent_instruction() {
+    use chipmunk::engine::quirks::QuirksProfile;
+
+    let left = trace::read_trace(&record_trace(TraceFormat::Binary, TraceFilter::default(), Quirks::default())[..]).unwrap();
+    let right = trace::read_trace(&record_trace(TraceFormat::Binary, TraceFilter::default(), QuirksProfile::Chip48.quirks())[..]).unwrap();
+
+    assert!(trace::first_divergence(&left, &left, Alignment::Cycle, true).is_none());
+    match trace::first_divergence(&left, &right, Alignment::Index, true) {
+        // SHR without shift_vy quirk is the first instruction which behaves differently.
+        Some(Divergence::Record{ left, fields, .. }) => {
+            assert_eq!(fields, vec!["V0".to_string(), "VF".to_string()]);
+            assert!(left.mnemonic().starts_with("LD V"));
+        },
+        other => panic!("Unexpected divergence {:?}", other),
+    }
+}
+
+#[test]
+fn diff_reports_trace_which_is_prefix_of_other() {
+    let records = trace::read_trace(&record_trace(TraceFormat::Binary, TraceFilter::default(), Quirks::default())[..]).unwrap();
+    let prefix = &records[..100];
+
+    for &alignment in [Alignment::Cycle, Alignment::Index].iter() {
+        match trace::first_divergence(prefix, &records, alignment, true) {
+            Some(Divergence::Length{ index, left_len, right_len }) => assert_eq!((index, left_len, right_len), (100, 100, 450)),
+            other => panic!("Unexpected divergence {:?} with {:?}", other, alignment),
+        }
+        match trace::first_divergence(&records, prefix, alignment, true) {
+            Some(Divergence::Length{ index, left_len, right_len }) => assert_eq!((index, left_len, right_len), (100, 450, 100)),
+            other => panic!("Unexpected divergence {:?} with {:?}", other, alignment),
+        }
+    }
+}
+
+#[test]
+fn text_of_record_has_label_of_pc() {
+    use chipmunk::engine::symbols::SymbolTable;
+
+    let records = trace::read_trace(&record_trace(TraceFormat::Binary, TraceFilter::default(), Quirks::default())[..]).unwrap();
+    let symbols = SymbolTable::parse("label 0x200 main\n").unwrap();
+    assert_eq!(records[0].to_text(&symbols), format!("{}  ; main", records[0]));
+    assert_eq!(records[1].to_text(&symbols), format!("{}  ; main+2", records[1]));
+    assert_eq!(records[0].to_text(&SymbolTable::new()), records[0].to_string());
+}